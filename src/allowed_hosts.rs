@@ -0,0 +1,51 @@
+use anyhow::Result;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use url::Url;
+
+/// The error returned when a request is made to a host which is not in the [allowed_hosts](crate::Settings::allowed_hosts)
+///
+/// The request is not sent, so the bearer token never leaves the client.
+#[derive(Clone, Debug)]
+pub struct HostNotAllowedError {
+    pub url: Url,
+}
+
+impl Display for HostNotAllowedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The host of {} is not allowed, the bearer token is only sent to the allowed hosts",
+            self.url
+        )
+    }
+}
+
+impl Error for HostNotAllowedError {}
+
+/// Fail with a [HostNotAllowedError](HostNotAllowedError) when the host of `url` doesn't match any of the `allowed_hosts`
+///
+/// A host matches `api.example.com` exactly, `*.example.com` matches every subdomain of `example.com`.
+pub(crate) fn check_allowed_host(allowed_hosts: Option<&[String]>, url: &Url) -> Result<()> {
+    let allowed_hosts = match allowed_hosts {
+        Some(allowed_hosts) => allowed_hosts,
+        None => return Ok(()),
+    };
+
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let allowed = allowed_hosts.iter().any(|allowed| {
+        let allowed = allowed.to_ascii_lowercase();
+        match allowed.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+            None => host == allowed,
+        }
+    });
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(HostNotAllowedError { url: url.clone() }.into())
+    }
+}
@@ -1,211 +1,2785 @@
-use crate::settings::Settings;
+use crate::allowed_hosts::check_allowed_host;
+use crate::authorized_client_builder::AuthorizedClientBuilder;
+use crate::authorized_request_builder::AuthorizedRequestBuilder;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::background_refresh::BackgroundRefresh;
+use crate::circuit_breaker::{is_failure, CircuitBreaker};
+use crate::client_assertion::{build_client_assertion, SigningKey, CLIENT_ASSERTION_TYPE};
+use crate::clock::{Clock, SystemClock};
+use crate::conditional::Conditional;
+use crate::content_type::{check_json_stream_content_type, is_json_lines_content_type};
+use crate::credentials_cell::CredentialsCell;
+use crate::deserialize_error::{deserialize_body, deserialize_json};
+use crate::discovery::discover_endpoints;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::download::{
+    is_interrupted, parse_content_range, part_path, resume_validator, unsatisfiable_range_total,
+    DownloadProgress, IncompleteDownloadError,
+};
+use crate::dpop::DPOP_SCHEME;
+use crate::graphql::{GraphQlRequest, GraphQlResponse};
+use crate::health_check::{HealthCheck, HealthReport};
+use crate::hedging::{execute_hedged, HedgeLimits};
+use crate::http_client::{oauth_http_client, HttpClients};
+use crate::idempotency::{new_idempotency_key, requires_idempotency_key};
+use crate::interceptor::Interceptor;
+use crate::json_body::JsonBody;
+use crate::json_stream::JsonStreamParser;
+#[cfg(feature = "jwt")]
+use crate::jwt::TokenClaims;
+use crate::metrics::{Metrics, MetricsRegistry, RequestMetrics, TokenRefreshMetrics};
+use crate::multipart_form::MultipartForm;
+use crate::network_error::NetworkError;
+use crate::pagination::{next_cursor_url, next_link};
+use crate::rate_limiter::Priority;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rate_limiter::RateLimiter;
+use crate::request_signer::RequestSigner;
+use crate::response_cache::{ResponseCache, SharedResponse};
+use crate::response_meta::{json_with_meta, ResponseMeta};
+use crate::response_size::{limit_response_size, unwrap_response_too_large};
+use crate::retry_policy::{retry_after, RetryPolicy};
+use crate::secret::SecretString;
+use crate::settings::{AuthType, ClientAuthMethod, GrantType, Settings};
+use crate::single_flight::SingleFlight;
+use crate::sse::{Event, EventParser, DEFAULT_RECONNECT_DELAY};
+use crate::status_error::{ApiError, StatusError, UnauthorizedError};
+use crate::token_exchange::{token_exchange_request, token_exchange_response};
+use crate::token_introspection::TokenIntrospection;
+use crate::token_observer::{TokenObserver, TokenRefreshEvent};
+use crate::token_provider::TokenProvider;
+use crate::token_store::{MemoryTokenStore, StoredToken, TokenStore};
+use crate::transport::HttpTransport;
+#[cfg(feature = "websocket")]
+use crate::websocket::{handshake_request, verify_handshake, WebSocketAuth};
+use crate::wire_log::{log_request, log_response};
+use crate::www_authenticate::BearerChallenge;
 use anyhow::{bail, Context, Result};
-use log::{debug, trace};
-use oauth2::basic::BasicClient;
+use bytes::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use log::{debug, trace, warn};
+use oauth2::basic::{BasicClient, BasicTokenResponse};
 use oauth2::http::StatusCode;
-use oauth2::reqwest::async_http_client;
-use oauth2::{AuthUrl, ClientId, ClientSecret, Scope, TokenResponse, TokenUrl};
-use reqwest::header::HeaderValue;
+use oauth2::{
+    AccessToken, AuthUrl, ClientId, ClientSecret, IntrospectionUrl, RefreshToken,
+    ResourceOwnerPassword, ResourceOwnerUsername, RevocationUrl, Scope, StandardRevocableToken,
+    TokenResponse, TokenUrl,
+};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+    IF_NONE_MATCH, IF_RANGE, RANGE,
+};
+#[cfg(feature = "websocket")]
+use reqwest::Upgraded;
 use reqwest::{Client, Method, Request, Response};
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::future::Future;
-use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::{RwLock, RwLockWriteGuard};
-use tokio::time::{sleep, Duration};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::fs::{self, File, OpenOptions};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncWriteExt;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::runtime::Handle;
+use tracing::{field, info_span, Instrument, Span};
 use url::Url;
 use void::Void;
 
+/// A client which adds a bearer token to every request, and refreshes it when it expires
+///
+/// Clones share the bearer token, a refresh by one clone is used by all of them.
+/// Use [fork_isolated](AuthorizedClient::fork_isolated) for a client which refreshes its bearer token independently.
 #[derive(Clone)]
 pub struct AuthorizedClient {
-    credentials: Arc<RwLock<Credentials>>,
+    credentials: Arc<CredentialsCell>,
     http_client: Client,
-    settings: Settings,
+    transport: Arc<dyn HttpTransport>,
+    token_transport: Arc<dyn HttpTransport>,
+    pub(crate) settings: Settings,
+    token_store: Arc<dyn TokenStore>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    default_headers: HeaderMap,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limiter: Option<Arc<RateLimiter>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    single_flight: Option<Arc<SingleFlight>>,
+    response_cache: Option<Arc<ResponseCache>>,
+    metrics: MetricsRegistry,
+    clock: Arc<dyn Clock>,
+    // The bearer token is supplied by the user instead of the auth server, see: with_static_token
+    static_token: bool,
+    // Replaces the token exchange when set, see: connect_with_token_provider
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    // The clients with a bearer token for another audience or scopes, see: for_audience
+    derived_clients: Arc<Mutex<HashMap<String, AuthorizedClient>>>,
+    // Aborts the background refresh task when the last clone is dropped
+    #[cfg(not(target_arch = "wasm32"))]
+    _background_refresh: Option<Arc<BackgroundRefresh>>,
 }
 
-const MAX_RETRY_COUNT: u8 = 3;
-
 impl AuthorizedClient {
+    /// Configure the settings, the http client and the extensions of a new `AuthorizedClient` using one builder
+    ///
+    /// ```no_run
+    ///# async fn doc_test() -> anyhow::Result<()> {
+    /// use authorized_client::{AuthorizedClient, RetryPolicy};
+    ///
+    /// let client = AuthorizedClient::builder()
+    ///     .client_id("xxxxxxxxxx")
+    ///     .client_secret("xxxxxxxxxx")
+    ///     .token_url("https://authorization-server.com/token")
+    ///     .retry_policy(RetryPolicy::new(3))
+    ///     .build()
+    ///     .await?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn builder() -> AuthorizedClientBuilder {
+        AuthorizedClientBuilder::new()
+    }
+
     /// Create a new `AuthorizedClient`
     ///
     /// This function immediately tries to get a bearer token from the auth server.
     /// When this fails your `settings` are probably incorrect
+    ///
+    /// Connection errors and timeouts are retried according to the [token_retry_policy](Settings::token_retry_policy),
+    /// use [connect_lazy](AuthorizedClient::connect_lazy) to start without contacting the auth server at all.
     pub async fn connect(settings: Settings) -> Result<Self> {
-        // Create the underlying http client, will be reused for every call
-        let http_client = Client::new();
+        Self::connect_with_store(settings, MemoryTokenStore::new()).await
+    }
 
-        trace!("Initial connect to '{}'", settings.token_url);
-        // Fetch the bearer token for the first time
-        let credentials = Arc::new(RwLock::new(Self::get_bearer_token(&settings).await?));
-        trace!(
-            "Successfully connected: Got bearer token from {}",
-            settings.token_url
+    /// Create a new `AuthorizedClient` which saves its bearer tokens in `token_store`
+    ///
+    /// When the store contains a bearer token which is still valid it's reused, otherwise a new one is requested from the auth server.
+    ///
+    /// See: [connect](AuthorizedClient::connect) for more info
+    pub async fn connect_with_store(
+        settings: Settings,
+        token_store: impl TokenStore + 'static,
+    ) -> Result<Self> {
+        // Create the underlying http clients, will be reused for every call
+        let http_clients = HttpClients::new(&settings)?;
+
+        Self::connect_with(settings, http_clients, Arc::new(token_store)).await
+    }
+
+    /// Create a new `AuthorizedClient` which uses `http_client` for every request, including the requests to the token endpoint
+    ///
+    /// The TLS, proxy and timeout settings are not applied, configure them on `http_client` instead.
+    ///
+    /// See: [connect](AuthorizedClient::connect) for more info
+    pub async fn connect_with_client(settings: Settings, http_client: Client) -> Result<Self> {
+        Self::connect_with(
+            settings,
+            HttpClients::from_client(http_client),
+            Arc::new(MemoryTokenStore::new()),
+        )
+        .await
+    }
+
+    /// Create a new `AuthorizedClient` which sends every request using `transport`, including the requests to the token endpoint
+    ///
+    /// Use this to test code which uses an `AuthorizedClient` without an auth server, e.g. with a `MockTransport` (requires the `mock` feature).
+    /// The TLS, proxy and timeout settings are not applied, they are the responsibility of the transport.
+    ///
+    /// See: [connect](AuthorizedClient::connect) for more info
+    pub async fn connect_with_transport(
+        settings: Settings,
+        transport: impl HttpTransport + 'static,
+    ) -> Result<Self> {
+        Self::connect_with(
+            settings,
+            HttpClients::from_transport(Arc::new(transport)),
+            Arc::new(MemoryTokenStore::new()),
+        )
+        .await
+    }
+
+    /// Create a new `AuthorizedClient` without getting a bearer token, the first token is requested when the first request is made
+    ///
+    /// This way a service can start while the auth server is unreachable, the invalid `settings` are only detected once a request is made.
+    /// Discovering the endpoints using the [issuer_url](Settings::issuer_url) requires the auth server, so it's not supported.
+    ///
+    /// ```
+    ///# fn doc_test() -> anyhow::Result<()> {
+    /// use authorized_client::{AuthorizedClient, Settings};
+    ///
+    /// let settings = Settings::builder()
+    ///     .client_id("xxxxxxxxxx")
+    ///     .client_secret("xxxxxxxxxx")
+    ///     .token_url("https://authorization-server.com/token")
+    ///     .build()?;
+    /// let client = AuthorizedClient::connect_lazy(settings)?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [connect](AuthorizedClient::connect) for more info
+    pub fn connect_lazy(settings: Settings) -> Result<Self> {
+        settings.validate()?;
+        let http_clients = HttpClients::new(&settings)?;
+
+        Self::connect_lazy_with(settings, http_clients, Arc::new(MemoryTokenStore::new()))
+    }
+
+    pub(crate) fn connect_lazy_with(
+        settings: Settings,
+        http_clients: HttpClients,
+        token_store: Arc<dyn TokenStore>,
+    ) -> Result<Self> {
+        settings.validate()?;
+        if settings.issuer_url.is_some() {
+            bail!("Invalid settings: the issuer_url can't be used with connect_lazy, set the token_url instead");
+        }
+        let http_clients = http_clients.with_dpop(&settings)?;
+
+        // Expired credentials make the first request fetch a bearer token
+        let credentials = Credentials::expired(Instant::now());
+
+        Ok(Self::from_credentials(
+            settings,
+            http_clients,
+            token_store,
+            credentials,
+        ))
+    }
+
+    pub(crate) async fn connect_with(
+        settings: Settings,
+        http_clients: HttpClients,
+        token_store: Arc<dyn TokenStore>,
+    ) -> Result<Self> {
+        // Fail early with a clear message instead of a vague error from the auth server
+        settings.validate()?;
+        let settings = discover_endpoints(settings, &*http_clients.token).await?;
+        let http_clients = http_clients.with_dpop(&settings)?;
+
+        let credentials =
+            match Self::load_stored_credentials(&settings, &*token_store, &SystemClock) {
+                Some(credentials) => {
+                    trace!("Reusing stored bearer token");
+                    credentials
+                }
+                None => {
+                    trace!("Initial connect to '{}'", settings.token_url);
+                    // Fetch the bearer token for the first time
+                    let credentials = Self::fetch_bearer_token(
+                        &settings,
+                        &*http_clients.token,
+                        &*token_store,
+                        &MetricsRegistry::default(),
+                        None,
+                        &SystemClock,
+                    )
+                    .await?;
+                    trace!(
+                        "Successfully connected: Got bearer token from {}",
+                        settings.token_url
+                    );
+                    credentials
+                }
+            };
+
+        Ok(Self::from_credentials(
+            settings,
+            http_clients,
+            token_store,
+            credentials,
+        ))
+    }
+
+    // Create the client once the first bearer token has been acquired
+    pub(crate) fn from_credentials(
+        settings: Settings,
+        http_clients: HttpClients,
+        token_store: Arc<dyn TokenStore>,
+        credentials: Credentials,
+    ) -> Self {
+        let credentials = Arc::new(CredentialsCell::new(credentials, settings.refresh_cooldown));
+        let metrics = MetricsRegistry::default();
+
+        // Keep the bearer token fresh in the background if requested
+        // Spawning the task requires a tokio runtime, without one the token is refreshed when a request is made
+        #[cfg(not(target_arch = "wasm32"))]
+        let background_refresh = match (settings.background_refresh, Handle::try_current()) {
+            (true, Ok(runtime)) => {
+                trace!("Starting background token refresh");
+                Some(Arc::new(BackgroundRefresh::spawn(
+                    &runtime,
+                    credentials.clone(),
+                    settings.clone(),
+                    http_clients.token.clone(),
+                    token_store.clone(),
+                    metrics.clone(),
+                    Arc::new(SystemClock),
+                )))
+            }
+            (true, Err(_)) => {
+                warn!("Background refresh requires a tokio runtime, refreshing the bearer token when a request is made instead");
+                None
+            }
+            (false, _) => None,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let rate_limiter = settings
+            .rate_limit
+            .clone()
+            .map(|rate_limit| Arc::new(RateLimiter::new(rate_limit)));
+
+        let circuit_breaker = settings
+            .circuit_breaker
+            .clone()
+            .map(|circuit_breaker| Arc::new(CircuitBreaker::new(circuit_breaker)));
+
+        let single_flight = if settings.deduplicate_gets {
+            Some(Arc::new(SingleFlight::new()))
+        } else {
+            None
+        };
+
+        let response_cache = settings
+            .response_cache
+            .clone()
+            .map(|response_cache| Arc::new(ResponseCache::new(response_cache)));
+
+        // The headers have been validated together with the settings
+        let default_headers = settings
+            .default_headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect();
+
+        AuthorizedClient {
+            credentials,
+            http_client: http_clients.api,
+            transport: http_clients.api_transport,
+            token_transport: http_clients.token,
+            settings,
+            token_store,
+            interceptors: Vec::new(),
+            signer: None,
+            default_headers,
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limiter,
+            circuit_breaker,
+            single_flight,
+            response_cache,
+            metrics,
+            clock: Arc::new(SystemClock),
+            static_token: false,
+            token_provider: None,
+            derived_clients: Arc::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            _background_refresh: background_refresh,
+        }
+    }
+
+    /// Create a new `AuthorizedClient` which uses `token` as bearer token instead of requesting one from the auth server
+    ///
+    /// Use this in integration tests or when the bearer token is supplied by e.g. a sidecar.
+    /// The token is never refreshed, when the server rejects it the request fails, replace it using [set_token](AuthorizedClient::set_token).
+    /// The default settings are used, the TLS, proxy and timeout settings can be configured on `http_client`.
+    ///
+    /// ```
+    /// use authorized_client::AuthorizedClient;
+    ///
+    /// let client = AuthorizedClient::with_static_token("my-token", reqwest::Client::new());
+    /// ```
+    pub fn with_static_token(token: impl Into<String>, http_client: Client) -> Self {
+        let mut client = Self::from_credentials(
+            Settings::without_auth_server(),
+            HttpClients::from_client(http_client),
+            Arc::new(MemoryTokenStore::new()),
+            Credentials::from_static_token(token.into(), &SystemClock),
+        );
+        client.static_token = true;
+        client
+    }
+
+    /// Create a new `AuthorizedClient` which gets its bearer tokens from `token_provider` instead of the auth server
+    ///
+    /// This function immediately requests the first bearer token from the provider.
+    /// The default settings are used, the TLS, proxy and timeout settings can be configured on `http_client`.
+    ///
+    /// See: [TokenProvider](TokenProvider) for more info
+    pub async fn connect_with_token_provider(
+        token_provider: impl TokenProvider + 'static,
+        http_client: Client,
+    ) -> Result<Self> {
+        let token_provider: Arc<dyn TokenProvider> = Arc::new(token_provider);
+        let credentials =
+            Self::fetch_provider_token(&*token_provider, &MetricsRegistry::default(), &SystemClock)
+                .await?;
+
+        let mut client = Self::from_credentials(
+            Settings::without_auth_server(),
+            HttpClients::from_client(http_client),
+            Arc::new(MemoryTokenStore::new()),
+            credentials,
         );
+        client.token_provider = Some(token_provider);
+        Ok(client)
+    }
+
+    /// Replace the bearer token of a client created using [with_static_token](AuthorizedClient::with_static_token)
+    ///
+    /// The new token is used by all clones of this client, requests which are already sent are not retried.
+    pub async fn set_token(&self, token: impl Into<String>) -> Result<()> {
+        if !self.static_token {
+            bail!(
+                "Only the bearer token of a client created using with_static_token can be replaced"
+            );
+        }
+
+        self.credentials
+            .store(Credentials::from_static_token(token.into(), &*self.clock));
+        trace!("Replaced static bearer token");
+        Ok(())
+    }
+
+    /// Register an interceptor which is called for every request, see: [Interceptor](Interceptor)
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Sign every request right before it's sent, see: [RequestSigner](RequestSigner)
+    ///
+    /// A client has a single signer, registering another one replaces it.
+    pub fn with_signer(mut self, signer: impl RequestSigner + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Create a client with its own bearer token, unlike a clone it's refreshed independently of this client
+    ///
+    /// The new client starts with a copy of the current bearer token, e.g. use this to give every worker its own token refresh.
+    ///
+    /// See: [with_scopes](AuthorizedClient::with_scopes) for what is shared with this client,
+    /// the new client gets its own background refresh when this client has one.
+    pub fn fork_isolated(&self) -> Self {
+        let credentials = Credentials::clone(&self.credentials.load());
 
-        Ok(AuthorizedClient {
+        let mut client = self.with_settings(self.settings.clone());
+        client.credentials = Arc::new(CredentialsCell::new(
             credentials,
-            http_client,
+            self.settings.refresh_cooldown,
+        ));
+        #[cfg(not(target_arch = "wasm32"))]
+        if self._background_refresh.is_some() {
+            client._background_refresh = client.spawn_background_refresh();
+        }
+        client
+    }
+
+    // Start a background refresh for the credentials of this client, it requires a tokio runtime
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_background_refresh(&self) -> Option<Arc<BackgroundRefresh>> {
+        let runtime = Handle::try_current().ok()?;
+        Some(Arc::new(BackgroundRefresh::spawn(
+            &runtime,
+            self.credentials.clone(),
+            self.settings.clone(),
+            self.token_transport.clone(),
+            self.token_store.clone(),
+            self.metrics.clone(),
+            self.clock.clone(),
+        )))
+    }
+
+    /// Create a client which requests bearer tokens with other `scopes`, e.g. for endpoints which require extra permissions
+    ///
+    /// The new client has its own bearer token, it's requested when the first request is made.
+    /// The http connection pool, interceptors, signer, metrics, rate limiter and circuit breaker are shared with this client.
+    /// The token store, response cache and background refresh are not, the token is refreshed when a request is made.
+    pub fn with_scopes<S>(&self, scopes: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        let mut settings = self.settings.clone();
+        settings.scopes = scopes.into_iter().map(Into::into).collect();
+
+        self.with_settings(settings)
+    }
+
+    /// Create a client which requests bearer tokens for another `audience`, e.g. when a gateway issues a token per api
+    ///
+    /// The audience is sent as the `audience` parameter of the token request, or as the audience of a [token exchange](GrantType::TokenExchange).
+    /// Use [for_audience](AuthorizedClient::for_audience) to reuse the client, and its bearer token, for every request to the audience.
+    ///
+    /// See: [with_scopes](AuthorizedClient::with_scopes) for what is shared with this client
+    pub fn with_audience(&self, audience: impl Into<String>) -> Self {
+        let audience = audience.into();
+        let mut settings = self.settings.clone();
+        match &mut settings.grant_type {
+            GrantType::TokenExchange {
+                audience: token_exchange_audience,
+                ..
+            } => *token_exchange_audience = Some(audience),
+            _ => {
+                settings
+                    .extra_token_params
+                    .insert("audience".to_string(), audience);
+            }
+        }
+
+        self.with_settings(settings)
+    }
+
+    /// Get the client with a bearer token for `audience`, it's created using [with_audience](AuthorizedClient::with_audience) when it's first used
+    ///
+    /// The clones of this client share the clients per audience, so every audience has a single bearer token which is refreshed on its own.
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    /// let invoices: serde_json::Value = client
+    ///     .for_audience("api://billing")
+    ///     .get(url::Url::parse("https://gateway.example.com/billing/invoices")?)
+    ///     .await?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn for_audience(&self, audience: &str) -> Self {
+        self.derived_client(format!("audience {}", audience), || {
+            self.with_audience(audience)
+        })
+    }
+
+    // Get a client with its own bearer token, it's created when it's first used and shared by the clones of this client
+    fn derived_client(&self, key: String, create: impl FnOnce() -> Self) -> Self {
+        self.derived_clients
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(create)
+            .clone()
+    }
+
+    // The scopes a `403 Forbidden` response asks for with an `insufficient_scope` error, when they differ from the scopes of this client
+    fn insufficient_scope_scopes(&self, headers: &HeaderMap) -> Option<Vec<String>> {
+        // The bearer tokens of static tokens and token providers don't depend on the scopes
+        if self.static_token || self.token_provider.is_some() {
+            return None;
+        }
+
+        let challenge = BearerChallenge::from_headers(headers)?;
+        if !challenge.is_insufficient_scope() {
+            return None;
+        }
+
+        let scopes: Vec<String> = challenge
+            .scope?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let unchanged = scopes.len() == self.settings.scopes.len()
+            && scopes
+                .iter()
+                .all(|scope| self.settings.scopes.contains(scope));
+        if scopes.is_empty() || unchanged {
+            return None;
+        }
+
+        Some(scopes)
+    }
+
+    // Create a client with other settings and its own bearer token
+    fn with_settings(&self, settings: Settings) -> Self {
+        // Responses can depend on the bearer token, so don't share them with this client
+        let single_flight = if settings.deduplicate_gets {
+            Some(Arc::new(SingleFlight::new()))
+        } else {
+            None
+        };
+        let response_cache = settings
+            .response_cache
+            .clone()
+            .map(|response_cache| Arc::new(ResponseCache::new(response_cache)));
+
+        AuthorizedClient {
+            // Already expired, this way the first request gets a bearer token for the new settings
+            credentials: Arc::new(CredentialsCell::new(
+                Credentials::expired(self.clock.now()),
+                settings.refresh_cooldown,
+            )),
             settings,
+            token_store: Arc::new(MemoryTokenStore::new()),
+            single_flight,
+            response_cache,
+            derived_clients: Arc::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            _background_refresh: None,
+            ..self.clone()
+        }
+    }
+
+    /// Register metrics which are called for every request and token refresh, see: [Metrics](Metrics)
+    ///
+    /// The metrics are shared with all clones of this client, including the ones made before this call.
+    pub fn with_metrics(self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics.add(Arc::new(metrics));
+        self
+    }
+
+    /// Decode the claims of the current bearer token, e.g. to debug a missing scope or an unexpected audience (requires the `jwt` feature)
+    ///
+    /// Fails when the bearer token is not a JWT, the signature is not verified.
+    /// The bearer token is refreshed first when it's (almost) expired.
+    #[cfg(feature = "jwt")]
+    pub async fn token_claims(&self) -> Result<TokenClaims> {
+        self.ensure_authenticated().await?;
+        TokenClaims::decode(self.credentials.load().access_token.expose_secret())
+    }
+
+    /// Use another clock to decide when the bearer token expires, e.g. a [ManualClock](crate::ManualClock) in tests
+    ///
+    /// Call this right after creating the client, clones made before this call keep the old clock.
+    /// The current bearer token keeps its expiry, combine this with [connect_lazy](AuthorizedClient::connect_lazy)
+    /// to request the first token using the new clock as well.
+    /// The background refresh is restarted with the new clock, it waits using the timer of the tokio runtime.
+    ///
+    /// ```
+    /// use authorized_client::{AuthorizedClient, ManualClock, Settings};
+    /// use std::time::Duration;
+    ///
+    ///# fn doc_test() -> anyhow::Result<()> {
+    /// let settings = Settings::builder()
+    ///     .client_id("xxxxxxxxxx")
+    ///     .client_secret("xxxxxxxxxx")
+    ///     .token_url("https://authorization-server.com/token")
+    ///     .build()?;
+    ///
+    /// let clock = ManualClock::new();
+    /// let client = AuthorizedClient::connect_lazy(settings)?.with_clock(clock.clone());
+    ///
+    /// // Makes the next request refresh the bearer token
+    /// clock.advance(Duration::from_secs(3600));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        #[cfg(not(target_arch = "wasm32"))]
+        if self._background_refresh.is_some() {
+            self._background_refresh = self.spawn_background_refresh().or(self._background_refresh);
+        }
+        self
+    }
+
+    /// Register an observer which is called every time a new bearer token is requested, see: [TokenObserver](TokenObserver)
+    ///
+    /// The observers are shared with all clones of this client, including the ones made before this call.
+    pub fn with_token_observer(self, token_observer: impl TokenObserver + 'static) -> Self {
+        self.metrics.add_token_observer(Arc::new(token_observer));
+        self
+    }
+
+    /// Get the current bearer token, e.g. to authenticate a connection which isn't made by this client
+    ///
+    /// The bearer token is refreshed first when it's (almost) expired.
+    pub async fn access_token(&self) -> Result<SecretString> {
+        self.ensure_authenticated().await?;
+        Ok(self.credentials.load().access_token.clone())
+    }
+
+    /// Get the time the current bearer token expires
+    ///
+    /// The bearer token is refreshed first when it's (almost) expired.
+    pub async fn token_expires_at(&self) -> Result<SystemTime> {
+        self.ensure_authenticated().await?;
+        let expires_in = self
+            .credentials
+            .load()
+            .expires_at
+            .saturating_duration_since(self.clock.now());
+        Ok(self.clock.system_time() + expires_in)
+    }
+
+    /// Check if a bearer token can be obtained and the [health_check_url](Settings::health_check_url) can be called, e.g. for a readiness probe
+    ///
+    /// A new bearer token is requested from the auth server, it replaces the current one.
+    /// This function never fails, the failed checks are part of the [HealthReport](HealthReport).
+    pub async fn health_check(&self) -> HealthReport {
+        let started_at = Instant::now();
+        // A static bearer token doesn't depend on the auth server
+        let result = if self.static_token {
+            Ok(())
+        } else {
+            self.force_refresh_authentication().await
+        };
+        let token_endpoint = HealthCheck::new(started_at.elapsed(), &result);
+
+        let endpoint = match &self.settings.health_check_url {
+            Some(health_check_url) => {
+                let started_at = Instant::now();
+                let result = async {
+                    let url = Url::parse(health_check_url)?;
+                    self.request(
+                        || Ok(Request::new(Method::GET, url.clone())),
+                        ignore_response,
+                    )
+                    .await
+                }
+                .await;
+                Some(HealthCheck::new(started_at.elapsed(), &result))
+            }
+            None => None,
+        };
+
+        HealthReport {
+            token_endpoint,
+            endpoint,
+        }
+    }
+
+    /// Ask the auth server for information about the current bearer token ([RFC 7662](https://tools.ietf.org/html/rfc7662))
+    ///
+    /// Requires the [introspection_url](Settings::introspection_url) setting.
+    pub async fn introspect_token(&self) -> Result<TokenIntrospection> {
+        let introspection_url = self
+            .settings
+            .introspection_url
+            .clone()
+            .context("No introspection_url configured")?;
+        self.ensure_authenticated().await?;
+
+        let oauth_client = Self::oauth_client(&self.settings)?
+            .set_introspection_uri(IntrospectionUrl::new(introspection_url)?);
+        let access_token = AccessToken::new(
+            self.credentials
+                .load()
+                .access_token
+                .expose_secret()
+                .to_string(),
+        );
+
+        let mut introspection_request = oauth_client.introspect(&access_token)?;
+        for (name, value) in Self::token_request_params(&self.settings)? {
+            introspection_request = introspection_request.add_extra_param(name, value);
+        }
+        let response = introspection_request
+            .request_async(|request| oauth_http_client(&*self.token_transport, request))
+            .await?;
+
+        Ok(TokenIntrospection::from_response(&response))
+    }
+
+    /// Revoke the current bearer token and refresh token at the auth server ([RFC 7009](https://tools.ietf.org/html/rfc7009)), e.g. on shutdown
+    ///
+    /// Requires the [revocation_url](Settings::revocation_url) setting.
+    /// The client stays usable, the next request gets a new bearer token.
+    pub async fn revoke_token(&self) -> Result<()> {
+        let revocation_url = self
+            .settings
+            .revocation_url
+            .clone()
+            .context("No revocation_url configured")?;
+        let oauth_client = Self::oauth_client(&self.settings)?
+            .set_revocation_uri(RevocationUrl::new(revocation_url)?);
+        let extra_params = Self::token_request_params(&self.settings)?;
+
+        let _refresh_lock = self.credentials.lock_refresh().await;
+        let mut credentials = Credentials::clone(&self.credentials.load());
+
+        // Revoke the refresh token first, most auth servers revoke the bearer tokens issued with it as well
+        let mut tokens = Vec::new();
+        if let Some(refresh_token) = credentials.refresh_token.take() {
+            tokens.push(StandardRevocableToken::RefreshToken(RefreshToken::new(
+                refresh_token.expose_secret().to_string(),
+            )));
+            self.credentials.store(credentials.clone());
+        }
+        tokens.push(StandardRevocableToken::AccessToken(AccessToken::new(
+            credentials.access_token.expose_secret().to_string(),
+        )));
+
+        for token in tokens {
+            let mut revocation_request = oauth_client.revoke_token(token)?;
+            for (name, value) in &extra_params {
+                revocation_request =
+                    revocation_request.add_extra_param(name.clone(), value.clone());
+            }
+            revocation_request
+                .request_async(|request| oauth_http_client(&*self.token_transport, request))
+                .await?;
+        }
+        trace!("Revoked bearer token");
+
+        // Expire the credentials, also in the store, the revoked token can't be used anymore
+        credentials.expires_at = self.clock.now();
+        Self::store_credentials(&*self.token_store, &credentials, &*self.clock);
+        self.credentials.store(credentials);
+
+        Ok(())
+    }
+
+    // Get a still valid bearer token from the store, problems with the store are logged and ignored
+    pub(crate) fn load_stored_credentials(
+        settings: &Settings,
+        token_store: &dyn TokenStore,
+        clock: &dyn Clock,
+    ) -> Option<Credentials> {
+        let stored_token = match token_store.get() {
+            Ok(stored_token) => stored_token?,
+            Err(error) => {
+                warn!("Failed to load stored bearer token: {:#}", error);
+                return None;
+            }
+        };
+
+        // Don't bother with tokens which have to be refreshed immediately
+        let expires_in = stored_token
+            .expires_at
+            .duration_since(clock.system_time())
+            .ok()?;
+        if expires_in <= settings.refresh_leeway {
+            trace!("Stored bearer token is (almost) expired");
+            return None;
+        }
+
+        let access_token = SecretString::new(stored_token.access_token);
+        Some(Credentials {
+            authorization: authorization_header(&access_token, settings.dpop),
+            access_token,
+            expires_at: clock.now().checked_add(expires_in)?,
+            refresh_token: stored_token.refresh_token.map(SecretString::new),
+            scopes: None,
         })
     }
 
-    // Internal method used to get a new bearer token from the auth server
-    async fn get_bearer_token(settings: &Settings) -> Result<Credentials> {
-        trace!("Preparing client credentials exchange");
-        // Create a new oauth "client"
-        let oauth_client = BasicClient::new(
-            ClientId::new(settings.client_id.clone()),
-            Some(ClientSecret::new(settings.client_secret.clone())),
-            AuthUrl::new("http://unused".to_string())?,
-            Some(TokenUrl::new(settings.token_url.clone())?),
+    // Get a new bearer token from the auth server and save it in the store
+    // When a refresh token is available it's used first, if that fails a full token exchange is done
+    pub(crate) async fn fetch_bearer_token(
+        settings: &Settings,
+        token_transport: &dyn HttpTransport,
+        token_store: &dyn TokenStore,
+        metrics: &MetricsRegistry,
+        refresh_token: Option<&str>,
+        clock: &dyn Clock,
+    ) -> Result<Credentials> {
+        let started_at = Instant::now();
+
+        // The span never contains token values
+        let span = info_span!(
+            "token_exchange",
+            token_url = %settings.token_url,
+            refresh = refresh_token.is_some()
         );
+        let credentials = async {
+            let retry_policy = &settings.token_retry_policy;
+            let mut attempt = 1;
+            loop {
+                let credentials = match refresh_token {
+                    Some(refresh_token) => {
+                        match Self::refresh_bearer_token(
+                            settings,
+                            token_transport,
+                            refresh_token,
+                            clock,
+                        )
+                        .await
+                        {
+                            Ok(credentials) => Ok(credentials),
+                            Err(error) => {
+                                debug!(
+                                    "Failed to use refresh token, falling back to a full token exchange: {:#}",
+                                    error
+                                );
+                                Self::get_bearer_token(settings, token_transport, clock).await
+                            }
+                        }
+                    }
+                    None => Self::get_bearer_token(settings, token_transport, clock).await,
+                };
 
-        // Build a client credentials request
-        let mut exchange_request = oauth_client.exchange_client_credentials();
+                match credentials {
+                    Err(error) if retry_policy.should_retry_token_error(&error, attempt) => {
+                        let delay = retry_policy.delay(attempt);
+                        debug!(
+                            "Token exchange attempt {} failed, retrying in {:?}: {:#}",
+                            attempt, delay, error
+                        );
+                        clock.sleep(delay).await;
+                        attempt += 1;
+                    }
+                    credentials => return credentials,
+                }
+            }
+        }
+        .instrument(span)
+        .await;
+
+        metrics.on_token_refresh(&TokenRefreshMetrics {
+            duration: started_at.elapsed(),
+            success: credentials.is_ok(),
+        });
+        let credentials = credentials?;
+
+        Self::store_credentials(token_store, &credentials, clock);
+        Self::notify_token_observers(metrics, &credentials, &settings.scopes, clock);
+
+        Ok(credentials)
+    }
+
+    // Get a new bearer token from a user supplied token provider
+    async fn fetch_provider_token(
+        token_provider: &dyn TokenProvider,
+        metrics: &MetricsRegistry,
+        clock: &dyn Clock,
+    ) -> Result<Credentials> {
+        let started_at = Instant::now();
+        let token = token_provider
+            .token()
+            .instrument(info_span!("token_provider"))
+            .await;
+
+        metrics.on_token_refresh(&TokenRefreshMetrics {
+            duration: started_at.elapsed(),
+            success: token.is_ok(),
+        });
+        let token = token.context("Failed to get a bearer token from the token provider")?;
+
+        let access_token = SecretString::new(token.access_token);
+        let credentials = Credentials {
+            // The token provider is only used with the default settings, which don't use DPoP
+            authorization: authorization_header(&access_token, false),
+            access_token,
+            expires_at: clock
+                .now()
+                .checked_add(token.expires_in)
+                .context("Duration was so long it caused an overflow")?,
+            refresh_token: None,
+            scopes: None,
+        };
+        Self::notify_token_observers(metrics, &credentials, &[], clock);
+
+        Ok(credentials)
+    }
+
+    // Tell the token observers about a new bearer token, the requested scopes are used when the auth server didn't mention the granted ones
+    fn notify_token_observers(
+        metrics: &MetricsRegistry,
+        credentials: &Credentials,
+        requested_scopes: &[String],
+        clock: &dyn Clock,
+    ) {
+        metrics.on_new_token(&TokenRefreshEvent {
+            expires_at: clock.system_time()
+                + credentials
+                    .expires_at
+                    .saturating_duration_since(clock.now()),
+            scopes: credentials
+                .scopes
+                .clone()
+                .unwrap_or_else(|| requested_scopes.to_vec()),
+            access_token: credentials.access_token.clone(),
+        });
+    }
 
-        // Add the requested scopes to the request
-        for scope in settings.scopes.iter().cloned() {
-            exchange_request = exchange_request.add_scope(Scope::new(scope));
+    // Save the bearer token in the store
+    // The token is usable even when saving it fails, so only log the problem
+    pub(crate) fn store_credentials(
+        token_store: &dyn TokenStore,
+        credentials: &Credentials,
+        clock: &dyn Clock,
+    ) {
+        let stored_token = StoredToken {
+            access_token: credentials.access_token.expose_secret().to_string(),
+            refresh_token: credentials
+                .refresh_token
+                .as_ref()
+                .map(|refresh_token| refresh_token.expose_secret().to_string()),
+            expires_at: clock.system_time()
+                + credentials
+                    .expires_at
+                    .saturating_duration_since(clock.now()),
+        };
+        if let Err(error) = token_store.put(&stored_token) {
+            warn!("Failed to store bearer token: {:#}", error);
         }
+    }
+
+    // Internal method used to get a new bearer token from the auth server
+    pub(crate) async fn get_bearer_token(
+        settings: &Settings,
+        token_transport: &dyn HttpTransport,
+        clock: &dyn Clock,
+    ) -> Result<Credentials> {
+        let oauth_client = Self::oauth_client(settings)?;
+        let scopes = settings.scopes.iter().cloned().map(Scope::new);
+        let extra_params = Self::token_request_params(settings)?;
+
+        let response = match &settings.grant_type {
+            GrantType::ClientCredentials => {
+                trace!("Preparing client credentials exchange");
+                // Exchange the client_id and client_secret for a bearer token
+                let mut exchange_request = oauth_client
+                    .exchange_client_credentials()
+                    .add_scopes(scopes);
+                for (name, value) in extra_params {
+                    exchange_request = exchange_request.add_extra_param(name, value);
+                }
+                let response = exchange_request
+                    .request_async(|request| oauth_http_client(token_transport, request))
+                    .await?;
+
+                trace!(
+                    "Successfully exchanged client_id and client_secret for a bearer token, expires in {:?}",
+                    response.expires_in()
+                );
+                response
+            }
+            GrantType::Password { username, password } => {
+                trace!("Preparing password exchange");
+                // Exchange the username and password for a bearer token
+                let username = ResourceOwnerUsername::new(username.clone());
+                let password = ResourceOwnerPassword::new(password.expose_secret().to_string());
+                let mut exchange_request = oauth_client
+                    .exchange_password(&username, &password)
+                    .add_scopes(scopes);
+                for (name, value) in extra_params {
+                    exchange_request = exchange_request.add_extra_param(name, value);
+                }
+                let response = exchange_request
+                    .request_async(|request| oauth_http_client(token_transport, request))
+                    .await?;
+
+                trace!(
+                    "Successfully exchanged username and password for a bearer token, expires in {:?}",
+                    response.expires_in()
+                );
+                response
+            }
+            GrantType::DeviceCode { .. } => {
+                bail!("The device code grant requires user interaction, use DeviceCodeFlow to connect again")
+            }
+            GrantType::TokenExchange { .. } => {
+                trace!("Preparing token exchange");
+                // Exchange the subject token for a bearer token
+                let request = token_exchange_request(settings, extra_params)?;
+                let response =
+                    token_exchange_response(oauth_http_client(token_transport, request).await?)?;
+
+                trace!(
+                    "Successfully exchanged subject token for a bearer token, expires in {:?}",
+                    response.expires_in()
+                );
+                response
+            }
+        };
 
-        // Exchange the client_id and client_secret for a bearer token
-        let response = exchange_request.request_async(async_http_client).await?;
+        Self::credentials_from_response(settings, &response, clock)
+    }
+
+    // Internal method used to get a new bearer token using a refresh token
+    async fn refresh_bearer_token(
+        settings: &Settings,
+        token_transport: &dyn HttpTransport,
+        refresh_token: &str,
+        clock: &dyn Clock,
+    ) -> Result<Credentials> {
+        trace!("Preparing refresh token exchange");
+        let oauth_client = Self::oauth_client(settings)?;
+
+        // Exchange the refresh token for a new bearer token
+        let refresh_token = RefreshToken::new(refresh_token.to_string());
+        let mut exchange_request = oauth_client.exchange_refresh_token(&refresh_token);
+        for (name, value) in Self::token_request_params(settings)? {
+            exchange_request = exchange_request.add_extra_param(name, value);
+        }
+        let response = exchange_request
+            .request_async(|request| oauth_http_client(token_transport, request))
+            .await?;
 
         trace!(
-            "Successfully exchanged client_id and client_secret for a bearer token: {:?}",
-            response
+            "Successfully exchanged refresh token for a bearer token, expires in {:?}",
+            response.expires_in()
         );
 
-        // Extract the required data
-        let expires_at = Instant::now()
-            .checked_add(
-                response
-                    .expires_in()
-                    .context("Expires in is missing in token response")?,
-            )
+        let mut credentials = Self::credentials_from_response(settings, &response, clock)?;
+
+        // The auth server doesn't have to issue a new refresh token, in that case the old one stays valid
+        if credentials.refresh_token.is_none() {
+            credentials.refresh_token = Some(SecretString::new(refresh_token.secret().to_owned()));
+        }
+
+        Ok(credentials)
+    }
+
+    // Create a new oauth "client"
+    pub(crate) fn oauth_client(settings: &Settings) -> Result<BasicClient> {
+        // Public clients don't have a client secret and a client assertion replaces the client secret
+        let client_secret = match settings.client_auth_method {
+            ClientAuthMethod::ClientSecret => Some(settings.client_secret.expose_secret())
+                .filter(|client_secret| !client_secret.is_empty())
+                .map(|client_secret| ClientSecret::new(client_secret.to_string())),
+            ClientAuthMethod::PrivateKeyJwt { .. } => None,
+        };
+
+        Ok(BasicClient::new(
+            ClientId::new(settings.client_id.clone()),
+            client_secret,
+            AuthUrl::new("http://unused".to_string())?,
+            Some(TokenUrl::new(settings.token_url.clone())?),
+        )
+        .set_auth_type(match settings.auth_type {
+            AuthType::BasicAuth => oauth2::AuthType::BasicAuth,
+            AuthType::RequestBody => oauth2::AuthType::RequestBody,
+        }))
+    }
+
+    // Extra parameters which have to be added to every token request
+    pub(crate) fn token_request_params(settings: &Settings) -> Result<Vec<(String, String)>> {
+        let mut params: Vec<_> = settings
+            .extra_token_params
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        if let ClientAuthMethod::PrivateKeyJwt {
+            key,
+            algorithm,
+            audience,
+        } = &settings.client_auth_method
+        {
+            // The assertion is only valid for a short time, so a new one is signed for every request
+            let key = SigningKey::from_pem(key.expose_secret(), *algorithm)?;
+            let audience = audience.as_deref().unwrap_or(&settings.token_url);
+            let assertion = build_client_assertion(&key, &settings.client_id, audience)?;
+
+            params.push((
+                "client_assertion_type".to_string(),
+                CLIENT_ASSERTION_TYPE.to_string(),
+            ));
+            params.push(("client_assertion".to_string(), assertion));
+        }
+
+        Ok(params)
+    }
+
+    // Extract the required data from a token response
+    pub(crate) fn credentials_from_response(
+        settings: &Settings,
+        response: &BasicTokenResponse,
+        clock: &dyn Clock,
+    ) -> Result<Credentials> {
+        // The exp claim of a JWT is exact, expires_in is rounded and doesn't include the time the response took
+        // Some auth servers leave out expires_in, then the lifetime from the settings is used
+        let expires_in = jwt_expires_in(response.access_token().secret(), clock)
+            .or_else(|| response.expires_in())
+            .or(settings.default_token_lifetime)
+            .context("Expires in is missing in token response, set the default_token_lifetime when the auth server leaves it out")?;
+        let expires_at = clock
+            .now()
+            .checked_add(expires_in)
             .context("Duration was so long it caused an overflow")?;
-        let access_token = response.access_token().secret().to_owned();
+        let access_token = SecretString::new(response.access_token().secret().to_owned());
+        let refresh_token = response
+            .refresh_token()
+            .map(|refresh_token| SecretString::new(refresh_token.secret().to_owned()));
+        let scopes = response
+            .scopes()
+            .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect());
 
-        // Return the fetched credentials
         Ok(Credentials {
+            authorization: authorization_header(&access_token, settings.dpop),
             access_token,
             expires_at,
+            refresh_token,
+            scopes,
         })
     }
 
-    /// Make a get request to the endpoint.
-    /// Expects the response to be a json object
+    /// Make a get request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// Use `serde_json::Value` as `R` to get the json without declaring a type, or [get_untyped](AuthorizedClient::get_untyped)
+    /// when the response can be empty or isn't always json.
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get<R>(&self, url: Url) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        if self.single_flight.is_some() || self.response_cache.is_some() {
+            // Only successful responses are shared and cached
+            let response = self.get_shared(url).await?;
+            return deserialize_body(
+                response.status,
+                &response.headers,
+                &response.body,
+                self.settings.accept_any_content_type,
+            );
+        }
+
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a get request to the endpoint.
+    /// Expects the response to be a json object, the status code and headers of the response are returned as well
+    ///
+    /// Use this when the headers contain important metadata, e.g. the remaining rate limit.
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_with_meta<R>(&self, url: Url) -> Result<ResponseMeta<R>>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            |response| json_with_meta(response, self.settings.accept_any_content_type),
+        )
+        .await
+    }
+
+    /// Make a get request to the endpoint, `query` is serialized into query parameters which are appended to the url.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_with_query<R, Q>(&self, url: Url, query: &Q) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+        Q: Serialize,
+    {
+        self.get(append_query(url, query)?).await
+    }
+
+    /// Make a get request to the endpoint.
+    /// Expects the response to be a json object, error responses are deserialized into `E`
+    ///
+    /// When the error response can be deserialized the returned error is an [ApiError](ApiError),
+    /// otherwise it's a [StatusError](StatusError).
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_with_error<R, E>(&self, url: Url) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+        E: for<'de> Deserialize<'de> + Debug + Send + Sync + 'static,
+    {
+        self.get(url).await.map_err(into_api_error::<E>)
+    }
+
+    /// Make get requests to the endpoints, at most `max_concurrency` requests are in flight at the same time.
+    /// Expects the responses to be json objects, the results are returned in the order of `urls`
+    ///
+    /// The requests share the bearer token, when it's rejected only one request refreshes it.
+    ///
+    /// See: [get](AuthorizedClient::get) for more info
+    pub async fn get_many<R>(
+        &self,
+        urls: impl IntoIterator<Item = Url>,
+        max_concurrency: usize,
+    ) -> Vec<Result<R>>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        stream::iter(urls)
+            .map(|url| self.get(url))
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Make a conditional get request to the endpoint, `etag` is sent in the `If-None-Match` header.
+    /// Expects the response to be a json object
+    ///
+    /// Returns the body and the new `ETag` when the resource changed, or [NotModified](Conditional::NotModified)
+    /// when the server responded with `304 Not Modified`. Pass `None` for the first request.
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    /// use authorized_client::Conditional;
+    ///
+    /// let url = url::Url::parse("https://protected-endpoint.com/status")?;
+    /// let mut etag = None;
+    /// loop {
+    ///     if let Conditional::Modified(status, new_etag) = client
+    ///         .get_if_none_match::<serde_json::Value>(url.clone(), etag.as_deref())
+    ///         .await?
+    ///     {
+    ///         println!("{}", status);
+    ///         etag = new_etag;
+    ///     }
+    ///     tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    /// }
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_if_none_match<R>(&self, url: Url, etag: Option<&str>) -> Result<Conditional<R>>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let result = self
+            .request(
+                || {
+                    let mut request = Request::new(Method::GET, url.clone());
+                    if let Some(etag) = etag {
+                        request.headers_mut().insert(IF_NONE_MATCH, etag.parse()?);
+                    }
+                    Ok(request)
+                },
+                |response| async move {
+                    let etag = response
+                        .headers()
+                        .get(ETAG)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string);
+                    let body = self.json(response).await?;
+                    Ok::<_, anyhow::Error>(Conditional::Modified(body, etag))
+                },
+            )
+            .await;
+
+        match result {
+            Err(error)
+                if error
+                    .downcast_ref::<StatusError>()
+                    .is_some_and(|error| error.status == StatusCode::NOT_MODIFIED) =>
+            {
+                Ok(Conditional::NotModified)
+            }
+            result => result,
+        }
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response without declaring a type, use this to explore an endpoint
+    ///
+    /// Unlike [get](AuthorizedClient::get) the content type isn't checked and an empty or non-json body is not an error, see: [JsonBody](JsonBody)
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_untyped(&self, url: Url) -> Result<JsonBody> {
+        let body = self.get_bytes(url).await?;
+        Ok(JsonBody::from_bytes(&body))
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response as plain text
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_plain_text(&self, url: Url) -> Result<String> {
+        if self.single_flight.is_some() || self.response_cache.is_some() {
+            let response = self.get_shared(url).await?;
+            return Ok(String::from_utf8_lossy(&response.body).into_owned());
+        }
+
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            Response::text,
+        )
+        .await
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response as raw bytes, use this for binary content like pdf's or images
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_bytes(&self, url: Url) -> Result<Bytes> {
+        if self.single_flight.is_some() || self.response_cache.is_some() {
+            return Ok(self.get_shared(url).await?.body);
+        }
+
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            Response::bytes,
+        )
+        .await
+    }
+
+    // Share the response body with concurrent get requests to the same url and with later requests using the cache
+    async fn get_shared(&self, url: Url) -> Result<SharedResponse> {
+        match &self.single_flight {
+            Some(single_flight) => {
+                single_flight
+                    .run(format!("GET {}", url), || self.get_cached(url))
+                    .await
+            }
+            None => self.get_cached(url).await,
+        }
+    }
+
+    async fn get_cached(&self, url: Url) -> Result<SharedResponse> {
+        let response_cache = match &self.response_cache {
+            Some(response_cache) => response_cache,
+            None => {
+                return self
+                    .request(
+                        || Ok(Request::new(Method::GET, url.clone())),
+                        shared_response,
+                    )
+                    .await
+            }
+        };
+
+        let cached = response_cache.get(url.as_str());
+        if let Some(cached) = &cached {
+            if cached.is_fresh() {
+                trace!("Using cached response for {}", url);
+                return Ok(cached.response.clone());
+            }
+        }
+
+        // Revalidate the cached response, the server responds with 304 when it's still valid
+        let etag = cached.as_ref().and_then(|cached| cached.etag.clone());
+        let result = self
+            .request(
+                || {
+                    let mut request = Request::new(Method::GET, url.clone());
+                    if let Some(etag) = &etag {
+                        request.headers_mut().insert(IF_NONE_MATCH, etag.clone());
+                    }
+                    Ok(request)
+                },
+                shared_response,
+            )
+            .await;
+
+        match (result, cached) {
+            (Ok(response), _) => {
+                response_cache.put(url.as_str(), response.clone());
+                Ok(response)
+            }
+            (Err(error), Some(cached)) => match error.downcast_ref::<StatusError>() {
+                Some(status_error) if status_error.status == StatusCode::NOT_MODIFIED => {
+                    trace!("Cached response for {} is still valid", url);
+                    response_cache.refresh(url.as_str(), &status_error.headers);
+                    Ok(cached.response)
+                }
+                _ => Err(error),
+            },
+            (Err(error), None) => Err(error),
+        }
+    }
+
+    /// Make a get request to the endpoint.
+    /// The response body is deserialized by `decode`, use this for other formats than json, e.g. XML
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    ///# let url = url::Url::parse("https://protected-endpoint.com/invoice.xml")?;
+    /// // E.g. `quick_xml::de::from_reader` for XML
+    /// let invoice: String = client
+    ///     .get_decoded(url, |body| String::from_utf8(body.to_vec()))
+    ///     .await?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_decoded<R, E>(
+        &self,
+        url: Url,
+        decode: impl FnOnce(&[u8]) -> Result<R, E>,
+    ) -> Result<R>
+    where
+        E: Into<anyhow::Error>,
+    {
+        let body = self.get_bytes(url).await?;
+        decode(&body)
+            .map_err(Into::into)
+            .context("Failed to deserialize the response")
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response as a stream of bytes, use this for large downloads which shouldn't be buffered in memory
+    ///
+    /// Only the initial request is retried when the bearer token gets rejected, reading the stream is not.
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn get_stream(&self, url: Url) -> Result<impl Stream<Item = Result<Bytes>>> {
+        let response = self
+            .request(
+                || Ok(Request::new(Method::GET, url.clone())),
+                return_response,
+            )
+            .await?;
+
+        Ok(stream::try_unfold(response, |mut response| async move {
+            Ok(response.chunk().await?.map(|chunk| (chunk, response)))
+        }))
+    }
+
+    /// Make a get request to the endpoint.
+    /// Expects the response to be a json array, or newline delimited json, the items are deserialized while the body is received
+    ///
+    /// Use this for large responses which shouldn't be buffered in memory, only the item which is being received is buffered.
+    /// The request is made when the stream is polled for the first time.
+    /// Only the initial request is retried when the bearer token gets rejected, reading the stream is not.
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    ///# #[derive(serde::Deserialize)]
+    ///# struct Record { id: u64 }
+    /// use futures_util::{pin_mut, TryStreamExt};
+    ///
+    /// let records = client.get_json_stream::<Record>(url::Url::parse("https://protected-endpoint.com/records")?);
+    /// pin_mut!(records);
+    /// while let Some(record) = records.try_next().await? {
+    ///     println!("{}", record.id);
+    /// }
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn get_json_stream<R>(&self, url: Url) -> impl Stream<Item = Result<R>> + '_
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.json_stream(url, false)
+    }
+
+    /// Make a get request to the endpoint.
+    /// Expects the response to be newline delimited json (`application/x-ndjson`), every line is deserialized into an item
+    ///
+    /// A line can be split over multiple chunks of the body, it's deserialized once it's complete. Empty lines are skipped.
+    /// Unlike [get_json_stream](AuthorizedClient::get_json_stream) a line which contains an array is a single item.
+    ///
+    /// See: [get_json_stream](AuthorizedClient::get_json_stream) for more info
+    pub fn get_ndjson<R>(&self, url: Url) -> impl Stream<Item = Result<R>> + '_
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.json_stream(url, true)
+    }
+
+    // Deserialize the items of a json array or newline delimited json while the body is received
+    fn json_stream<R>(&self, url: Url, lines: bool) -> impl Stream<Item = Result<R>> + '_
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let state = JsonStreamState {
+            url,
+            response: None,
+        };
+
+        stream::try_unfold(state, move |mut state| async move {
+            let (response, parser) = match &mut state.response {
+                Some(response) => response,
+                None => {
+                    let url = &state.url;
+                    let response = self
+                        .request(
+                            || {
+                                let mut request = Request::new(Method::GET, url.clone());
+                                if lines {
+                                    request.headers_mut().insert(
+                                        ACCEPT,
+                                        HeaderValue::from_static("application/x-ndjson"),
+                                    );
+                                }
+                                Ok(request)
+                            },
+                            return_response,
+                        )
+                        .await?;
+                    if !self.settings.accept_any_content_type {
+                        check_json_stream_content_type(response.headers())?;
+                    }
+
+                    // A line of newline delimited json can be an array as well, so don't guess the format when it's known
+                    let parser = if lines || is_json_lines_content_type(response.headers()) {
+                        JsonStreamParser::lines()
+                    } else {
+                        JsonStreamParser::new()
+                    };
+                    state.response.insert((response, parser))
+                }
+            };
+
+            loop {
+                let item = match parser.next_item()? {
+                    Some(item) => item,
+                    None => match response.chunk().await? {
+                        Some(chunk) => {
+                            parser.feed(&chunk);
+                            continue;
+                        }
+                        None => match parser.finish()? {
+                            Some(item) => item,
+                            None => return Ok(None),
+                        },
+                    },
+                };
+
+                let item = deserialize_body(response.status(), response.headers(), &item, true)?;
+                return Ok(Some((item, state)));
+            }
+        })
+    }
+
+    /// Make a get request to the endpoint and stream the response to the file at `path`.
+    /// Returns the size of the file
+    ///
+    /// See: [download_with_progress](AuthorizedClient::download_with_progress) for more info
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download(&self, url: Url, path: impl AsRef<Path>) -> Result<u64> {
+        self.download_with_progress(url, path, |_| {}).await
+    }
+
+    /// Make a get request to the endpoint and stream the response to the file at `path`, `on_progress` is called after every chunk.
+    /// Returns the size of the file
+    ///
+    /// The file is written to `<path>.part` first, it replaces `path` once it's complete. An existing file is never appended to.
+    /// A download which is interrupted is resumed using a `Range` request, up to [max_download_resumes](Settings::max_download_resumes) times.
+    /// The request includes the `ETag` or `Last-Modified` of the first response as `If-Range`, so a file which changed in the meantime is downloaded again.
+    /// Without either header the download starts over.
+    /// When the server sent the size of the file, the size of the downloaded file is verified,
+    /// a mismatch fails with an [IncompleteDownloadError](IncompleteDownloadError).
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    /// let url = url::Url::parse("https://protected-endpoint.com/reports/2024.csv")?;
+    /// client
+    ///     .download_with_progress(url, "2024.csv", |progress| {
+    ///         println!("{}/{:?} bytes", progress.downloaded, progress.total);
+    ///     })
+    ///     .await?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_with_progress(
+        &self,
+        url: Url,
+        path: impl AsRef<Path>,
+        mut on_progress: impl FnMut(DownloadProgress),
+    ) -> Result<u64> {
+        let path = path.as_ref();
+        let part_path = part_path(path);
+        let mut validator = None;
+        let mut offset = 0;
+        let mut resumes = 0;
+
+        loop {
+            let result = self
+                .download_from(&url, &part_path, offset, &mut validator, &mut on_progress)
+                .await;
+            match result {
+                Ok(size) => {
+                    fs::rename(&part_path, path).await.with_context(|| {
+                        format!(
+                            "Failed to move '{}' to '{}'",
+                            part_path.display(),
+                            path.display()
+                        )
+                    })?;
+                    return Ok(size);
+                }
+                Err(error)
+                    if resumes < self.settings.max_download_resumes && is_interrupted(&error) =>
+                {
+                    resumes += 1;
+                    warn!(
+                        "The download of {} was interrupted, resuming: {}",
+                        url, error
+                    );
+                    // Without a validator a changed file can't be detected, so the download starts over
+                    offset = match validator {
+                        Some(_) => fs::metadata(&part_path)
+                            .await
+                            .with_context(|| format!("Failed to read '{}'", part_path.display()))?
+                            .len(),
+                        None => 0,
+                    };
+                    self.clock
+                        .sleep(self.settings.download_resume_backoff.delay(resumes as u32))
+                        .await;
+                }
+                Err(error) => {
+                    // The partial file is only resumed by this call
+                    if let Err(remove_error) = fs::remove_file(&part_path).await {
+                        if remove_error.kind() != ErrorKind::NotFound {
+                            warn!(
+                                "Failed to remove '{}': {}",
+                                part_path.display(),
+                                remove_error
+                            );
+                        }
+                    }
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    // Download the rest of the file to `part_path`, starting at byte `offset`
+    // The validator of the first response is saved, a resumed request is only answered with a partial response when it still matches
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn download_from(
+        &self,
+        url: &Url,
+        part_path: &Path,
+        offset: u64,
+        validator: &mut Option<HeaderValue>,
+        on_progress: &mut impl FnMut(DownloadProgress),
+    ) -> Result<u64> {
+        let response = self
+            .request(
+                || {
+                    let mut request = Request::new(Method::GET, url.clone());
+                    if let (true, Some(validator)) = (offset > 0, &validator) {
+                        let headers = request.headers_mut();
+                        headers.insert(RANGE, format!("bytes={}-", offset).parse()?);
+                        headers.insert(IF_RANGE, validator.clone());
+                    }
+                    Ok(request)
+                },
+                return_response,
+            )
+            .await;
+
+        let mut response = match response {
+            Ok(response) => response,
+            Err(error) => match unsatisfiable_range_total(&error) {
+                // The file was already complete
+                Some(total) if offset > 0 && total == offset => {
+                    on_progress(DownloadProgress {
+                        downloaded: total,
+                        total: Some(total),
+                    });
+                    return Ok(total);
+                }
+                // The partial file is larger than the one on the server, start over
+                Some(_) if offset > 0 => {
+                    return Box::pin(self.download_from(url, part_path, 0, validator, on_progress))
+                        .await;
+                }
+                _ => return Err(error),
+            },
+        };
+
+        let (mut file, mut downloaded, total) =
+            if offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT {
+                let content_range = response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_content_range)
+                    .context("The partial response has no valid Content-Range header")?;
+                if content_range.start != Some(offset) {
+                    bail!(
+                        "The server resumed the download at byte {:?} instead of {}",
+                        content_range.start,
+                        offset
+                    );
+                }
+
+                let file = OpenOptions::new()
+                    .append(true)
+                    .open(part_path)
+                    .await
+                    .with_context(|| format!("Failed to open '{}'", part_path.display()))?;
+                (file, offset, content_range.total)
+            } else {
+                // The server sent the whole file, e.g. because it changed since the download started
+                *validator = resume_validator(response.headers());
+                let file = File::create(part_path)
+                    .await
+                    .with_context(|| format!("Failed to create '{}'", part_path.display()))?;
+                // `content_length` is the size of the received body, the header is the size the server announced
+                let content_length = response
+                    .headers()
+                    .get(CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok());
+                (file, 0, content_length)
+            };
+
+        on_progress(DownloadProgress { downloaded, total });
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(error) => {
+                    // The written bytes are kept, the download is resumed after them
+                    file.flush().await.ok();
+                    return Err(error.into());
+                }
+            };
+            file.write_all(&chunk)
+                .await
+                .with_context(|| format!("Failed to write '{}'", part_path.display()))?;
+            downloaded += chunk.len() as u64;
+            on_progress(DownloadProgress { downloaded, total });
+        }
+        file.flush()
+            .await
+            .with_context(|| format!("Failed to write '{}'", part_path.display()))?;
+
+        match total {
+            Some(expected) if expected != downloaded => Err(IncompleteDownloadError {
+                expected,
+                actual: downloaded,
+            }
+            .into()),
+            _ => Ok(downloaded),
+        }
+    }
+
+    /// Subscribe to the server-sent events of the endpoint
+    ///
+    /// When the connection drops the client reconnects, sending the id of the last event as `Last-Event-ID` so the server can resume.
+    /// A rejected bearer token is refreshed before reconnecting, like any other request.
+    /// The stream ends when the server responds with `204 No Content`, or with an error when reconnecting fails.
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    /// use futures_util::{pin_mut, TryStreamExt};
+    ///
+    /// let events = client.sse(url::Url::parse("https://protected-endpoint.com/events")?);
+    /// pin_mut!(events);
+    /// while let Some(event) = events.try_next().await? {
+    ///     println!("{}: {}", event.event, event.data);
+    /// }
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn sse(&self, url: Url) -> impl Stream<Item = Result<Event>> + '_ {
+        let state = SseState {
+            url,
+            response: None,
+            parser: EventParser::default(),
+        };
+
+        stream::try_unfold(state, move |mut state| async move {
+            loop {
+                if let Some(event) = state.parser.next_event() {
+                    return Ok(Some((event, state)));
+                }
+
+                let response = match &mut state.response {
+                    Some(response) => response,
+                    None => {
+                        let url = &state.url;
+                        let last_event_id = state.parser.last_event_id.clone();
+                        let response = self
+                            .request(
+                                || {
+                                    let mut request = Request::new(Method::GET, url.clone());
+                                    let headers = request.headers_mut();
+                                    headers.insert(
+                                        ACCEPT,
+                                        HeaderValue::from_static("text/event-stream"),
+                                    );
+                                    if let Some(last_event_id) = &last_event_id {
+                                        headers.insert("Last-Event-ID", last_event_id.parse()?);
+                                    }
+                                    Ok(request)
+                                },
+                                return_response,
+                            )
+                            .await?;
+
+                        // The server tells the client to stop reconnecting
+                        if response.status() == StatusCode::NO_CONTENT {
+                            return Ok(None);
+                        }
+                        state.response.insert(response)
+                    }
+                };
+
+                match response.chunk().await {
+                    Ok(Some(chunk)) => state.parser.feed(&chunk),
+                    result => {
+                        if let Err(error) = result {
+                            debug!("Server-sent events connection failed: {}", error);
+                        }
+                        let delay = state.parser.retry.unwrap_or(DEFAULT_RECONNECT_DELAY);
+                        debug!(
+                            "Server-sent events connection closed, reconnecting in {}ms",
+                            delay.as_millis()
+                        );
+                        state.response = None;
+                        state.parser.reset();
+                        self.clock.sleep(delay).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Upgrade a connection to the endpoint to a WebSocket, the bearer token is sent in the `Authorization` header
+    ///
+    /// Only the opening handshake is done, the returned connection is the raw upgraded stream.
+    /// It doesn't frame messages, wrap it using the WebSocket library of your choice,
+    /// e.g. `tokio_tungstenite::WebSocketStream::from_raw_socket(connection, Role::Client, None)`.
+    /// The bearer token is refreshed first when it's (almost) expired or rejected.
+    ///
+    /// The handshake waits for the [rate_limit](crate::Settings::rate_limit) and counts for the [circuit_breaker](crate::Settings::circuit_breaker),
+    /// the messages sent over the connection don't. The handshake isn't retried, see: [WebSocketAuth](WebSocketAuth)
+    #[cfg(feature = "websocket")]
+    pub async fn upgrade(&self, url: Url) -> Result<Upgraded> {
+        self.upgrade_with_auth(url, WebSocketAuth::Header).await
+    }
+
+    /// Upgrade a connection to the endpoint to a WebSocket, `auth` determines how the bearer token is sent
+    ///
+    /// See: [upgrade](AuthorizedClient::upgrade) for more info
+    #[cfg(feature = "websocket")]
+    pub async fn upgrade_with_auth(&self, url: Url, auth: WebSocketAuth) -> Result<Upgraded> {
+        let permit = match &self.circuit_breaker {
+            Some(circuit_breaker) => Some(circuit_breaker.try_acquire()?),
+            None => None,
+        };
+
+        let result = self.perform_upgrade(url, auth).await;
+        if let Some(permit) = permit {
+            permit.record(result.as_ref().err().is_some_and(is_failure));
+        }
+
+        result
+    }
+
+    #[cfg(feature = "websocket")]
+    async fn perform_upgrade(&self, url: Url, auth: WebSocketAuth) -> Result<Upgraded> {
+        self.ensure_authenticated().await?;
+
+        let mut refreshed = false;
+        loop {
+            let credentials = self.credentials.load();
+            let (mut request, key) = handshake_request(&url, &credentials, &auth)?;
+            check_allowed_host(self.settings.allowed_hosts.as_deref(), request.url())?;
+
+            let headers = request.headers_mut();
+            for (name, value) in &self.default_headers {
+                if !headers.contains_key(name) {
+                    headers.insert(name, value.clone());
+                }
+            }
+            for interceptor in &self.interceptors {
+                interceptor.on_request(&mut request).await?;
+            }
+            self.sign_request(&mut request).await?;
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter
+                    .acquire(Priority::default(), &*self.clock)
+                    .await;
+            }
+            let response = self
+                .transport
+                .execute(request)
+                .await
+                .map_err(|error| network_error(error, 1))?;
+            for interceptor in &self.interceptors {
+                interceptor.on_response(&response).await?;
+            }
+
+            match response.status() {
+                StatusCode::SWITCHING_PROTOCOLS => {
+                    verify_handshake(&response, &key)?;
+                    return Ok(response.upgrade().await?);
+                }
+                // The token is only refreshed once, the handshake isn't worth more attempts
+                StatusCode::UNAUTHORIZED if !refreshed => {
+                    refreshed = true;
+                    self.force_refresh_authentication().await?;
+                }
+                status => {
+                    let headers = response.headers().clone();
+                    let body = response.text().await.unwrap_or_default();
+                    return Err(StatusError {
+                        status,
+                        headers,
+                        body,
+                    }
+                    .into());
+                }
+            }
+        }
+    }
+
+    /// Make get requests to a long-poll endpoint, every response which contains a json body is yielded
+    ///
+    /// `url_builder` creates the url of the next request from the last item, it's called with `None` for the first request,
+    /// e.g. to add `?since=<id of the last event>`. A new request is made after every response, at most once per `interval`.
+    /// An empty response (e.g. `204 No Content`) or a timeout means there were no new items, the same url is requested again.
+    /// The bearer token is refreshed between the requests when it expires.
+    ///
+    /// Make sure the [request_timeout](crate::Settings::request_timeout) is longer than the time the server holds a request.
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    ///# #[derive(serde::Deserialize)]
+    ///# struct Events { last_id: u64 }
+    /// use futures_util::{pin_mut, TryStreamExt};
+    /// use std::time::Duration;
+    /// use url::Url;
+    ///
+    /// let events = client.poll(
+    ///     |last: Option<&Events>| {
+    ///         let since = last.map(|events| events.last_id).unwrap_or_default();
+    ///         Ok(Url::parse(&format!("https://protected-endpoint.com/events?since={}", since))?)
+    ///     },
+    ///     Duration::from_secs(1),
+    /// );
+    /// pin_mut!(events);
+    /// while let Some(events) = events.try_next().await? {
+    ///     println!("{}", events.last_id);
+    /// }
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn poll<'a, R, F>(
+        &'a self,
+        url_builder: F,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<R>> + 'a
+    where
+        R: for<'de> Deserialize<'de> + 'a,
+        F: FnMut(Option<&R>) -> Result<Url> + 'a,
+    {
+        let state = PollState {
+            url: None,
+            url_builder,
+            last_request: None,
+        };
+
+        stream::try_unfold(state, move |mut state| async move {
+            loop {
+                if let Some(last_request) = state.last_request {
+                    let elapsed = self.clock.now().saturating_duration_since(last_request);
+                    if elapsed < interval {
+                        self.clock.sleep(interval - elapsed).await;
+                    }
+                }
+
+                let url = match &state.url {
+                    Some(url) => url.clone(),
+                    None => state.url.insert((state.url_builder)(None)?).clone(),
+                };
+                state.last_request = Some(self.clock.now());
+
+                let result = self
+                    .request(
+                        || Ok(Request::new(Method::GET, url.clone())),
+                        |response| self.optional_json::<R>(response),
+                    )
+                    .await;
+                match result {
+                    Ok(Some(item)) => {
+                        state.url = Some((state.url_builder)(Some(&item))?);
+                        return Ok(Some((item, state)));
+                    }
+                    Ok(None) => trace!("The long poll ended without new items, polling again"),
+                    Err(error) if is_poll_timeout(&error) => {
+                        debug!("The long poll timed out, polling again")
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        })
+    }
+
+    /// Make get requests to the endpoint, following the `rel="next"` url of the `Link` header.
+    /// Expects every page to be a json object
+    ///
+    /// The pages are requested lazily, a page is only requested when the previous one has been consumed.
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn get_paginated<R>(&self, url: Url) -> impl Stream<Item = Result<R>> + '_
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        stream::try_unfold(Some(url), move |url| async move {
+            let url = match url {
+                Some(url) => url,
+                None => return Ok(None),
+            };
+
+            let (page, next) = self
+                .request(
+                    || Ok(Request::new(Method::GET, url.clone())),
+                    |response| async move {
+                        let next = next_link(response.headers(), response.url());
+                        let page = self.json::<R>(response).await?;
+                        Ok::<_, anyhow::Error>((page, next))
+                    },
+                )
+                .await?;
+
+            Ok(Some((page, next)))
+        })
+    }
+
+    /// Make get requests to the endpoint, following a cursor in the response body.
+    /// Expects every page to be a json object
+    ///
+    /// The cursor is looked up using the [JSON pointer](https://tools.ietf.org/html/rfc6901) `cursor_pointer`, e.g. `/meta/next_cursor`,
+    /// and sent in the `cursor_param` query parameter to request the next page.
+    /// The pagination stops when the cursor is missing, null or empty.
+    ///
+    /// See: [get_paginated](AuthorizedClient::get_paginated) for more info
+    pub fn get_paginated_with_cursor<R>(
+        &self,
+        url: Url,
+        cursor_pointer: impl Into<String>,
+        cursor_param: impl Into<String>,
+    ) -> impl Stream<Item = Result<R>> + '_
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let cursor_pointer = cursor_pointer.into();
+        let cursor_param = cursor_param.into();
+        let first_url = url.clone();
+
+        stream::try_unfold(Some(url), move |url| {
+            let first_url = first_url.clone();
+            let cursor_pointer = cursor_pointer.clone();
+            let cursor_param = cursor_param.clone();
+            async move {
+                let url = match url {
+                    Some(url) => url,
+                    None => return Ok(None),
+                };
+
+                let page: serde_json::Value = self.get(url).await?;
+                let next = next_cursor_url(&page, &first_url, &cursor_pointer, &cursor_param);
+                let page =
+                    serde_json::from_value(page).context("Failed to deserialize the response")?;
+
+                Ok(Some((page, next)))
+            }
+        })
+    }
+
+    /// Make a post request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn post<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::POST, &url, body),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a GraphQL request to the endpoint, `query` and `variables` are posted in the standard `{query, variables}` envelope.
+    /// The `data` of the response is deserialized into `R`
+    ///
+    /// When the response contains `errors` a [GraphQlErrors](crate::GraphQlErrors) is returned, it includes the partial data.
+    ///
+    /// ```no_run
+    ///# async fn doc_test(client: authorized_client::AuthorizedClient) -> anyhow::Result<()> {
+    /// use serde::Deserialize;
+    /// use serde_json::json;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Viewer {
+    ///     login: String,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Data {
+    ///     viewer: Viewer,
+    /// }
+    ///
+    /// let data: Data = client
+    ///     .graphql(
+    ///         url::Url::parse("https://protected-endpoint.com/graphql")?,
+    ///         "query { viewer { login } }",
+    ///         &json!({}),
+    ///     )
+    ///     .await?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn graphql<V, R>(&self, url: Url, query: &str, variables: &V) -> Result<R>
+    where
+        V: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        let response: GraphQlResponse =
+            self.post(url, &GraphQlRequest { query, variables }).await?;
+        response.into_data()
+    }
+
+    /// Make a post request to the endpoint.
+    /// Expects the response to be a json object or empty, an empty response (e.g. `204 No Content`) returns `None`
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn post_optional<B, R>(&self, url: Url, body: &B) -> Result<Option<R>>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::POST, &url, body),
+            |response| self.optional_json(response),
+        )
+        .await
+    }
+
+    /// Make a post request to the endpoint.
+    /// Get the response as plain text
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn post_plain_text<B>(&self, url: Url, body: &B) -> Result<String>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::POST, &url, body),
+            Response::text,
+        )
+        .await
+    }
+
+    /// Make a post request to the endpoint.
+    /// Ignore the response
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn post_ignore_response<B>(&self, url: Url, body: &B) -> Result<()>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::POST, &url, body),
+            ignore_response,
+        )
+        .await
+    }
+
+    /// Make a post request to the endpoint with a `application/x-www-form-urlencoded` body.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn post_form<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_form_request(Method::POST, &url, body),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a post request to the endpoint with a `multipart/form-data` body.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn post_multipart<R>(&self, url: Url, form: &MultipartForm) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_multipart_request(Method::POST, &url, form),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a put request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn put<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::PUT, &url, body),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a put request to the endpoint.
+    /// Expects the response to be a json object or empty, an empty response (e.g. `204 No Content`) returns `None`
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn put_optional<B, R>(&self, url: Url, body: &B) -> Result<Option<R>>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::PUT, &url, body),
+            |response| self.optional_json(response),
+        )
+        .await
+    }
+
+    /// Make a put request to the endpoint.
+    /// Get the response as plain text
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn put_plain_text<B>(&self, url: Url, body: &B) -> Result<String>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::PUT, &url, body),
+            Response::text,
+        )
+        .await
+    }
+
+    /// Make a put request to the endpoint.
+    /// Ignore the response
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn put_ignore_response<B>(&self, url: Url, body: &B) -> Result<()>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::PUT, &url, body),
+            ignore_response,
+        )
+        .await
+    }
+
+    /// Make a put request to the endpoint with a streamed body, use this for large uploads which shouldn't be buffered in memory.
+    /// Expects the response to be a json object
+    ///
+    /// A stream can only be sent once, when the request has to be retried (e.g. because the bearer token got rejected) an error is returned.
+    /// Use [put_stream_with](AuthorizedClient::put_stream_with) to retry using a new stream instead.
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn put_stream<S, R>(&self, url: Url, body: S, content_type: &str) -> Result<R>
+    where
+        S: Stream<Item = Result<Bytes>> + Send + 'static,
+        R: for<'de> Deserialize<'de>,
+    {
+        let content_type = HeaderValue::from_str(content_type).context("Invalid content type")?;
+        let body = Mutex::new(Some(body));
+
+        self.request(
+            || {
+                let body = body.lock().unwrap().take().context(
+                    "The streamed body has already been sent and can't be retried, use put_stream_with to retry using a new stream",
+                )?;
+                Ok(build_stream_request(Method::PUT, &url, body, &content_type))
+            },
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a put request to the endpoint with a streamed body, `new_body` is called to create the stream for every attempt.
+    /// Expects the response to be a json object
+    ///
+    /// Use this when the stream can be restarted, e.g. by opening the uploaded file again.
+    ///
+    /// See: [put_stream](AuthorizedClient::put_stream) for more info
+    pub async fn put_stream_with<F, S, R>(
+        &self,
+        url: Url,
+        new_body: F,
+        content_type: &str,
+    ) -> Result<R>
+    where
+        F: Fn() -> S,
+        S: Stream<Item = Result<Bytes>> + Send + 'static,
+        R: for<'de> Deserialize<'de>,
+    {
+        let content_type = HeaderValue::from_str(content_type).context("Invalid content type")?;
+
+        self.request(
+            || {
+                Ok(build_stream_request(
+                    Method::PUT,
+                    &url,
+                    new_body(),
+                    &content_type,
+                ))
+            },
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a patch request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn patch<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::PATCH, &url, body),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a patch request to the endpoint.
+    /// Expects the response to be a json object or empty, an empty response (e.g. `204 No Content`) returns `None`
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn patch_optional<B, R>(&self, url: Url, body: &B) -> Result<Option<R>>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::PATCH, &url, body),
+            |response| self.optional_json(response),
+        )
+        .await
+    }
+
+    /// Make a patch request to the endpoint.
+    /// Get the response as plain text
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn patch_plain_text<B>(&self, url: Url, body: &B) -> Result<String>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::PATCH, &url, body),
+            Response::text,
+        )
+        .await
+    }
+
+    /// Make a patch request to the endpoint.
+    /// Ignore the response
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn patch_ignore_response<B>(&self, url: Url, body: &B) -> Result<()>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::PATCH, &url, body),
+            ignore_response,
+        )
+        .await
+    }
+
+    /// Make a delete request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn delete<R>(&self, url: Url) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || Ok(Request::new(Method::DELETE, url.clone())),
+            |response| self.json(response),
+        )
+        .await
+    }
+
+    /// Make a delete request to the endpoint.
+    /// Expects the response to be a json object or empty, an empty response (e.g. `204 No Content`) returns `None`
     ///
     /// See: [request](AuthorizedClient::request) for more info
-    pub async fn get<R>(&self, url: Url) -> Result<R>
+    pub async fn delete_optional<R>(&self, url: Url) -> Result<Option<R>>
     where
         R: for<'de> Deserialize<'de>,
     {
         self.request(
-            || Ok(Request::new(Method::GET, url.clone())),
-            Response::json,
+            || Ok(Request::new(Method::DELETE, url.clone())),
+            |response| self.optional_json(response),
         )
         .await
     }
 
-    /// Make a get request to the endpoint.
+    /// Make a delete request to the endpoint.
     /// Get the response as plain text
     ///
     /// See: [request](AuthorizedClient::request) for more info
-    pub async fn get_plain_text(&self, url: Url) -> Result<String> {
+    pub async fn delete_plain_text(&self, url: Url) -> Result<String> {
         self.request(
-            || Ok(Request::new(Method::GET, url.clone())),
+            || Ok(Request::new(Method::DELETE, url.clone())),
             Response::text,
         )
         .await
     }
 
-    /// Make a post request to the endpoint.
-    /// Expects the response to be a json object
+    /// Make a delete request to the endpoint.
+    /// Ignore the response
     ///
     /// See: [request](AuthorizedClient::request) for more info
-    pub async fn post<B, R>(&self, url: Url, body: &B) -> Result<R>
+    pub async fn delete_ignore_response(&self, url: Url) -> Result<()> {
+        self.request(
+            || Ok(Request::new(Method::DELETE, url.clone())),
+            ignore_response,
+        )
+        .await
+    }
+
+    /// Make a head request to the endpoint.
+    /// Returns the response headers, a head response has no body
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub async fn head(&self, url: Url) -> Result<HeaderMap> {
+        self.request(
+            || Ok(Request::new(Method::HEAD, url.clone())),
+            extract_headers,
+        )
+        .await
+    }
+
+    /// Resolve `path` against the [base_url](Settings::base_url)
+    ///
+    /// The path is relative to the complete base url, a base url `https://api.example.com/api` and path `/v1/users`
+    /// resolve to `https://api.example.com/api/v1/users`. Absolute urls are rejected, this way the bearer token is never
+    /// sent to another host by accident, use the methods which accept a `Url` for those.
+    pub fn url(&self, path: &str) -> Result<Url> {
+        let base_url = self
+            .settings
+            .base_url
+            .as_deref()
+            .context("No base_url configured, use the methods which accept a Url instead")?;
+
+        if Url::parse(path).is_ok() || path.starts_with("//") {
+            bail!(
+                "Path '{}' is an absolute url, use the methods which accept a Url instead",
+                path
+            );
+        }
+
+        let mut base_url =
+            Url::parse(base_url).with_context(|| format!("Invalid base_url '{}'", base_url))?;
+        if !base_url.path().ends_with('/') {
+            let base_path = format!("{}/", base_url.path());
+            base_url.set_path(&base_path);
+        }
+
+        base_url
+            .join(path.trim_start_matches('/'))
+            .with_context(|| format!("Failed to join path '{}' to the base_url", path))
+    }
+
+    /// Make a get request to `path`, relative to the [base_url](Settings::base_url).
+    /// Expects the response to be a json object
+    ///
+    /// See: [url](AuthorizedClient::url) and [get](AuthorizedClient::get) for more info
+    pub async fn get_path<R>(&self, path: &str) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.get(self.url(path)?).await
+    }
+
+    /// Make a post request to `path`, relative to the [base_url](Settings::base_url).
+    /// Expects the response to be a json object
+    ///
+    /// See: [url](AuthorizedClient::url) and [post](AuthorizedClient::post) for more info
+    pub async fn post_path<B, R>(&self, path: &str, body: &B) -> Result<R>
     where
         B: Serialize,
         R: for<'de> Deserialize<'de>,
     {
-        self.request(|| build_post_request(&url, body), Response::json)
-            .await
+        self.post(self.url(path)?, body).await
     }
 
-    /// Make a post request to the endpoint.
-    /// Get the response as plain text
+    /// Make a put request to `path`, relative to the [base_url](Settings::base_url).
+    /// Expects the response to be a json object
     ///
-    /// See: [request](AuthorizedClient::request) for more info
-    pub async fn post_plain_text<B>(&self, url: Url, body: &B) -> Result<String>
+    /// See: [url](AuthorizedClient::url) and [put](AuthorizedClient::put) for more info
+    pub async fn put_path<B, R>(&self, path: &str, body: &B) -> Result<R>
     where
         B: Serialize,
+        R: for<'de> Deserialize<'de>,
     {
-        self.request(|| build_post_request(&url, body), Response::text)
-            .await
+        self.put(self.url(path)?, body).await
     }
 
-    /// Make a post request to the endpoint.
-    /// Ignore the response
+    /// Make a patch request to `path`, relative to the [base_url](Settings::base_url).
+    /// Expects the response to be a json object
     ///
-    /// See: [request](AuthorizedClient::request) for more info
-    pub async fn post_ignore_response<B>(&self, url: Url, body: &B) -> Result<()>
+    /// See: [url](AuthorizedClient::url) and [patch](AuthorizedClient::patch) for more info
+    pub async fn patch_path<B, R>(&self, path: &str, body: &B) -> Result<R>
     where
         B: Serialize,
+        R: for<'de> Deserialize<'de>,
     {
-        self.request(|| build_post_request(&url, body), ignore_response)
-            .await
+        self.patch(self.url(path)?, body).await
+    }
+
+    /// Make a delete request to `path`, relative to the [base_url](Settings::base_url).
+    /// Expects the response to be a json object
+    ///
+    /// See: [url](AuthorizedClient::url) and [delete](AuthorizedClient::delete) for more info
+    pub async fn delete_path<R>(&self, path: &str) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.delete(self.url(path)?).await
+    }
+
+    /// Create a request builder for the given method and url.
+    /// Use this to add custom headers or query parameters to a request
+    ///
+    /// The bearer token is added when the request gets sent, see: [AuthorizedRequestBuilder](AuthorizedRequestBuilder) for more info
+    pub fn request_builder(&self, method: Method, url: Url) -> AuthorizedRequestBuilder<'_> {
+        AuthorizedRequestBuilder::new(self, self.http_client.request(method, url))
+    }
+
+    // Deserialize a json response, the Content-Type is checked unless the settings disable it
+    async fn json<R>(&self, response: Response) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        deserialize_json(response, self.settings.accept_any_content_type).await
+    }
+
+    // Deserialize an optional json response, see: optional_json
+    async fn optional_json<R>(&self, response: Response) -> Result<Option<R>>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        optional_json_body(response, self.settings.accept_any_content_type).await
     }
 
     // Check if the bearer token isn't expired yet, if so get a new one
     async fn ensure_authenticated(&self) -> Result<()> {
-        // Verify that the credentials are not expired yet
-        // read lock: This will block until the write lock (if present) is released
-        if self.credentials.read().await.expires_at < Instant::now() {
-            trace!("Credentials appear to be expired, preparing to double check in a upgradable read lock and refresh if required");
-
-            // Acquire a write lock, only one write lock can access the data at once
-            let write_lock = self.credentials.write().await;
+        // Verify that the credentials are not expired yet, this doesn't wait for a refresh in progress
+        let generation = self.credentials.generation();
+        if self.needs_refresh(&self.credentials.load()) {
+            debug!("Credentials are (almost) expired, refreshing the authentication");
 
-            // We make sure no other write lock has updated the credentials in the time we were waiting to acquire the write lock
-            if write_lock.expires_at < Instant::now() {
-                debug!("Credentials are expired, refreshing the authentication");
-                self.refresh_authentication(write_lock).await?;
+            // When another task is refreshing already its result is used, also when it failed
+            let refresh = self
+                .credentials
+                .refresh(generation, &*self.clock, || self.refresh_authentication());
+            if let Err(error) = refresh.await {
+                // Keep using the current token during a short outage of the token endpoint
+                if !self
+                    .credentials
+                    .load()
+                    .is_servable_stale(self.settings.serve_stale, &*self.clock)
+                {
+                    return Err(error);
+                }
+                warn!(
+                    "Failed to refresh the bearer token, using the current one: {:#}",
+                    error
+                );
             }
         }
 
         Ok(())
     }
 
+    // Check if the credentials are expired or expire within the refresh leeway
+    // The placeholder credentials of a lazy client don't have a token yet, whatever the clock says
+    fn needs_refresh(&self, credentials: &Credentials) -> bool {
+        if credentials.authorization.is_none() {
+            return true;
+        }
+        match credentials
+            .expires_at
+            .checked_sub(self.settings.refresh_leeway)
+        {
+            Some(refresh_at) => refresh_at <= self.clock.now(),
+            None => true,
+        }
+    }
+
     // Get a new bearer token even if our internal code says it's still valid (might be invalidated on the server side)
     async fn force_refresh_authentication(&self) -> Result<()> {
         trace!("Force refreshing bearer token");
-        let write_lock = self.credentials.write().await;
-        self.refresh_authentication(write_lock).await
+        self.credentials
+            .refresh(self.credentials.generation(), &*self.clock, || {
+                self.refresh_authentication()
+            })
+            .await
     }
 
-    // Get a new bearer token and update save it
-    async fn refresh_authentication(
-        &self,
-        mut write_lock: RwLockWriteGuard<'_, Credentials>,
-    ) -> Result<()> {
-        debug!("Refreshing bearer token");
-        let result = Self::get_bearer_token(&self.settings).await?;
+    // Get a new bearer token after the server rejected `rejected_token`
+    // When concurrent requests get rejected only the first one refreshes, the others wait for its new token
+    async fn refresh_rejected_token(&self, rejected_token: &SecretString) -> Result<()> {
+        let generation = self.credentials.generation();
+        if self.credentials.load().access_token.expose_secret() != rejected_token.expose_secret() {
+            trace!("The rejected bearer token has already been replaced");
+            return Ok(());
+        }
+
+        trace!("Force refreshing bearer token");
+        self.credentials
+            .refresh(generation, &*self.clock, || self.refresh_authentication())
+            .await
+    }
+
+    // Get a new bearer token, only called by the refresh of the credentials cell so only one is requested at once
+    async fn refresh_authentication(&self) -> Result<Credentials> {
+        if self.static_token {
+            bail!("The static bearer token can't be refreshed, replace it using set_token");
+        }
 
-        write_lock.expires_at = result.expires_at;
-        write_lock.access_token = result.access_token;
+        debug!("Refreshing bearer token");
+        let result = match &self.token_provider {
+            Some(token_provider) => {
+                Self::fetch_provider_token(&**token_provider, &self.metrics, &*self.clock).await?
+            }
+            None => {
+                Self::fetch_bearer_token(
+                    &self.settings,
+                    &*self.token_transport,
+                    &*self.token_store,
+                    &self.metrics,
+                    self.credentials
+                        .load()
+                        .refresh_token
+                        .as_ref()
+                        .map(SecretString::expose_secret),
+                    &*self.clock,
+                )
+                .await?
+            }
+        };
 
         debug!("Refreshed bearer token");
-        Ok(())
+        Ok(result)
     }
 
     /// Make a request to the endpoint.
     ///
     /// A bearer token will automatically be included.
-    /// In case the bearer token gets rejected a new one is requested, this retry mechanism works 3 times, after that the client returns an error.
+    /// In case the bearer token gets rejected a new one is requested, this retry mechanism works [max_auth_retries](Settings::max_auth_retries) times, after that the client returns an error.
+    ///
+    /// Transient errors are retried according to the [retry_policy](Settings::retry_policy), by default they are not retried.
     ///
-    /// Note: only status code  `200` returns `Ok`, the rest returns an `Err`
+    /// When the [circuit_breaker](Settings::circuit_breaker) is open the request fails immediately with a [CircuitOpenError](crate::CircuitOpenError).
+    ///
+    /// Note: only `2xx` status codes return `Ok`, the rest returns an `Err`.
+    /// The error contains a [StatusError](StatusError) with the status, headers and body of the response
     pub async fn request<R, ExtractFut, ExtractError>(
         &self,
         request_builder: impl RequestBuilder,
@@ -213,41 +2787,316 @@ impl AuthorizedClient {
     ) -> Result<R>
     where
         ExtractFut: Future<Output = Result<R, ExtractError>>,
-        ExtractError: Error + Send + Sync + 'static,
+        ExtractError: Into<anyhow::Error>,
+    {
+        self.request_with_retry_policy(
+            request_builder,
+            response_builder,
+            &self.settings.retry_policy,
+            Priority::default(),
+        )
+        .await
+    }
+
+    // Make a request using another retry policy than the one in the settings, or another priority for the rate limiter
+    pub(crate) async fn request_with_retry_policy<R, ExtractFut, ExtractError>(
+        &self,
+        request_builder: impl RequestBuilder,
+        response_builder: impl FnOnce(Response) -> ExtractFut,
+        retry_policy: &RetryPolicy,
+        priority: Priority,
+    ) -> Result<R>
+    where
+        ExtractFut: Future<Output = Result<R, ExtractError>>,
+        ExtractError: Into<anyhow::Error>,
+    {
+        let permit = match &self.circuit_breaker {
+            Some(circuit_breaker) => Some(circuit_breaker.try_acquire()?),
+            None => None,
+        };
+
+        // The method and url are recorded once the request has been built
+        let span = info_span!(
+            "request",
+            method = field::Empty,
+            url = field::Empty,
+            status = field::Empty,
+            retries = 0,
+            auth_error = field::Empty
+        );
+        let started_at = Instant::now();
+        let mut outcome = RequestOutcome::default();
+        let result = self
+            .execute_request(
+                request_builder,
+                response_builder,
+                retry_policy,
+                priority,
+                &mut outcome,
+            )
+            .instrument(span)
+            .await
+            .map_err(unwrap_response_too_large);
+
+        if let Some(permit) = permit {
+            permit.record(result.as_ref().err().is_some_and(is_failure));
+        }
+
+        if let (Some(method), Some(url)) = (outcome.method, outcome.url) {
+            self.metrics.on_request(&RequestMetrics {
+                method,
+                url,
+                status: outcome.status,
+                duration: started_at.elapsed(),
+                retries: outcome.retries,
+                hedged: outcome.hedged,
+            });
+        }
+
+        result
+    }
+
+    // Call the signer with the body of the request, a streamed body can't be read without consuming it
+    async fn sign_request(&self, request: &mut Request) -> Result<()> {
+        let signer = match &self.signer {
+            Some(signer) => signer,
+            None => return Ok(()),
+        };
+
+        let body = match request.body() {
+            Some(body) => body
+                .as_bytes()
+                .context("A request with a streamed body can't be signed")?
+                .to_vec(),
+            None => Vec::new(),
+        };
+        signer.sign(request, &body).await
+    }
+
+    async fn execute_request<R, ExtractFut, ExtractError>(
+        &self,
+        request_builder: impl RequestBuilder,
+        response_builder: impl FnOnce(Response) -> ExtractFut,
+        retry_policy: &RetryPolicy,
+        priority: Priority,
+        outcome: &mut RequestOutcome,
+    ) -> Result<R>
+    where
+        ExtractFut: Future<Output = Result<R, ExtractError>>,
+        ExtractError: Into<anyhow::Error>,
     {
         // Ensure we don't attempt to make a request with an expired access token
         self.ensure_authenticated().await?;
 
         // Number of times we received unauthorized for a certain request
-        // When we reach MAX_RETRY_COUNT we stop trying
+        // When we reach the max_auth_retries of the settings we stop trying
         let mut unauthorized_retries = 0;
 
+        // Number of attempts for the retry policy, unauthorized retries are not counted
+        let mut attempt = 1;
+
+        // Number of times and total time we waited because of a Retry-After header
+        let mut retry_after_retries = 0;
+        let mut retry_after_waited = Duration::ZERO;
+
+        // The idempotency key is generated once, every attempt of this request uses the same key
+        let idempotency_key = match &self.settings.idempotency_keys {
+            Some(idempotency_keys) => Some((
+                HeaderName::from_bytes(idempotency_keys.header_name.as_bytes())?,
+                HeaderValue::from_str(&new_idempotency_key())?,
+            )),
+            None => None,
+        };
+
+        let span = Span::current();
+
         loop {
             // Build the request
             let mut request = request_builder.build(self.http_client.clone())?;
+            check_allowed_host(self.settings.allowed_hosts.as_deref(), request.url())?;
+            outcome.method = Some(request.method().clone());
+            outcome.url = Some(request.url().clone());
+            outcome.retries = attempt - 1 + unauthorized_retries as u32 + retry_after_retries;
+            span.record("method", request.method().as_str());
+            span.record("url", request.url().as_str());
+            span.record("retries", outcome.retries);
 
-            // Add the bearer token to the request headers
+            // Add the default headers, the idempotency key and the bearer token to the request headers
+            let requires_idempotency_key = requires_idempotency_key(request.method());
             let headers = request.headers_mut();
+            for (name, value) in &self.default_headers {
+                if !headers.contains_key(name) {
+                    headers.insert(name, value.clone());
+                }
+            }
+            if let (Some((name, value)), true) = (&idempotency_key, requires_idempotency_key) {
+                if !headers.contains_key(name) {
+                    headers.insert(name, value.clone());
+                }
+            }
+            let credentials = self.credentials.load();
             headers.insert(
                 "Authorization",
-                format!("Bearer {}", self.credentials.read().await.access_token).parse()?,
+                credentials
+                    .authorization
+                    .clone()
+                    .context("The bearer token is not a valid header value")?,
             );
 
-            // Execute the request
-            let response = self.http_client.execute(request).await?;
+            for interceptor in &self.interceptors {
+                interceptor.on_request(&mut request).await?;
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire(priority, &*self.clock).await;
+            }
+
+            // Signed after waiting for the rate limiter, so the timestamp of the signature is as fresh as possible
+            self.sign_request(&mut request).await?;
+
+            if let Some(wire_log) = &self.settings.wire_log {
+                log_request(wire_log, &request);
+            }
+
+            // Execute the request, retry transient errors according to the retry policy
+            let response = match &self.settings.hedge_after {
+                Some(hedge_after) => {
+                    let limits = HedgeLimits {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        rate_limiter: self.rate_limiter.as_deref(),
+                        #[cfg(not(target_arch = "wasm32"))]
+                        priority,
+                        circuit_breaker: self.circuit_breaker.as_deref(),
+                    };
+                    let (response, hedged) = execute_hedged(
+                        &*self.transport,
+                        request,
+                        *hedge_after,
+                        &*self.clock,
+                        limits,
+                    )
+                    .await;
+                    outcome.hedged |= hedged;
+                    response
+                }
+                None => self.transport.execute(request).await,
+            };
+            let response = match response {
+                Ok(response) => {
+                    let response = match &self.settings.wire_log {
+                        Some(wire_log) => log_response(wire_log, response)?,
+                        None => response,
+                    };
+                    match self.settings.max_response_size {
+                        Some(max_response_size) => {
+                            limit_response_size(response, max_response_size)?
+                        }
+                        None => response,
+                    }
+                }
+                Err(error)
+                    if error
+                        .downcast_ref::<reqwest::Error>()
+                        .is_some_and(|error| retry_policy.should_retry_error(error, attempt)) =>
+                {
+                    let delay = retry_policy.delay(attempt);
+                    debug!(
+                        "Request failed, retrying in {}ms (attempt {}): {}",
+                        delay.as_millis(),
+                        attempt,
+                        error
+                    );
+                    self.clock.sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(error) => return Err(network_error(error, attempt)),
+            };
+
+            outcome.status = Some(response.status());
+            span.record("status", response.status().as_u16());
+
+            for interceptor in &self.interceptors {
+                interceptor.on_response(&response).await?;
+            }
+
+            // When the server is throttling or temporarily unavailable: wait as long as the server asks and retry
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+            {
+                if let Some(delay) = retry_after(response.headers(), &*self.clock) {
+                    if retry_after_retries < retry_policy.max_retry_after_retries
+                        && retry_after_waited + delay <= retry_policy.retry_after_budget
+                    {
+                        retry_after_retries += 1;
+                        retry_after_waited += delay;
+                        debug!(
+                            "Received status code {} with Retry-After, retrying in {}ms",
+                            status.as_u16(),
+                            delay.as_millis()
+                        );
+                        self.clock.sleep(delay).await;
+                        continue;
+                    }
+                }
+            }
+
+            // When the bearer token lacks the scope the endpoint requires: retry once with a token for that scope
+            if response.status() == StatusCode::FORBIDDEN && self.settings.retry_insufficient_scope
+            {
+                if let Some(scopes) = self.insufficient_scope_scopes(response.headers()) {
+                    debug!(
+                        "The bearer token has insufficient scope, retrying with scopes '{}'",
+                        scopes.join(" ")
+                    );
+                    // The derived client doesn't escalate again, a server which keeps asking for other scopes would cause a retry loop
+                    let client =
+                        self.derived_client(format!("scopes {}", scopes.join(" ")), || {
+                            let mut settings = self.settings.clone();
+                            settings.scopes = scopes;
+                            settings.retry_insufficient_scope = false;
+                            self.with_settings(settings)
+                        });
+                    return Box::pin(client.execute_request(
+                        request_builder,
+                        response_builder,
+                        retry_policy,
+                        priority,
+                        outcome,
+                    ))
+                    .await;
+                }
+            }
 
-            // When the server returns 200: return the deserialized json
+            // When the server returns 2xx: return the extracted response
             // When the server returns 401: refresh authentication and retry
             // In other cases, throw an error
             match response.status() {
-                StatusCode::OK => return Ok(response_builder(response).await?),
+                status if status.is_success() => {
+                    return response_builder(response).await.map_err(Into::into)
+                }
                 StatusCode::UNAUTHORIZED => {
+                    let challenge = BearerChallenge::from_headers(response.headers());
+                    if let Some(challenge) = &challenge {
+                        debug!("The bearer token was rejected: {}", challenge);
+                        if let Some(error) = &challenge.error {
+                            span.record("auth_error", error.as_str());
+                        }
+                    }
+
                     // When we reached the maximum amount of retries: bail
-                    if unauthorized_retries == MAX_RETRY_COUNT {
-                        bail!(format!(
-                            "Failed to authenticate, retries = {} ",
-                            MAX_RETRY_COUNT
-                        ))
+                    // A new bearer token has the same scopes, so refreshing it doesn't help for insufficient_scope
+                    if unauthorized_retries >= self.settings.max_auth_retries
+                        || challenge
+                            .as_ref()
+                            .is_some_and(BearerChallenge::is_insufficient_scope)
+                    {
+                        return Err(UnauthorizedError {
+                            retries: unauthorized_retries,
+                            challenge,
+                        }
+                        .into());
                     }
 
                     // Increase the retry counter
@@ -256,27 +3105,50 @@ impl AuthorizedClient {
 
                     // If we have already retried once add some sleep time in between retries, we don't want to DDOS the oauth server
                     if unauthorized_retries > 1 {
-                        let sleep_duration = 500 * unauthorized_retries as u64;
-                        trace!("Sleeping for {}ms before retrying", sleep_duration);
-                        sleep(Duration::from_millis(sleep_duration)).await;
+                        let delay = self
+                            .settings
+                            .auth_retry_backoff
+                            .delay(unauthorized_retries as u32 - 1);
+                        trace!("Sleeping for {}ms before retrying", delay.as_millis());
+                        self.clock.sleep(delay).await;
                     }
 
                     // Refresh the bearer token
-                    self.force_refresh_authentication().await?;
+                    self.refresh_rejected_token(&credentials.access_token)
+                        .await?;
                 }
-                status_code => {
-                    bail!("Unsupported status code (CODE={})", status_code.as_u16())
+                status if retry_policy.should_retry_status(status, attempt) => {
+                    let delay = retry_policy.delay(attempt);
+                    debug!(
+                        "Received status code {}, retrying in {}ms (attempt {})",
+                        status.as_u16(),
+                        delay.as_millis(),
+                        attempt
+                    );
+                    self.clock.sleep(delay).await;
+                    attempt += 1;
+                }
+                status => {
+                    // Keep the response the server sent back, it usually explains what went wrong
+                    let headers = response.headers().clone();
+                    let body = response.text().await.unwrap_or_default();
+                    return Err(StatusError {
+                        status,
+                        headers,
+                        body,
+                    }
+                    .into());
                 }
             }
         }
     }
 }
 
-pub fn build_post_request<B>(url: &Url, body: &B) -> Result<Request>
+pub fn build_json_request<B>(method: Method, url: &Url, body: &B) -> Result<Request>
 where
     B: Serialize,
 {
-    let mut request = Request::new(Method::POST, url.clone());
+    let mut request = Request::new(method, url.clone());
 
     let headers = request.headers_mut();
     headers.append("Content-Type", HeaderValue::from_static("application/json"));
@@ -291,10 +3163,185 @@ where
     Ok(request)
 }
 
+// Append the serialized `query` to the query parameters of `url`
+fn append_query<Q>(mut url: Url, query: &Q) -> Result<Url>
+where
+    Q: Serialize,
+{
+    let query = serde_urlencoded::to_string(query).context("Failed to serialize query")?;
+    if !query.is_empty() {
+        let combined = match url.query() {
+            Some(existing) if !existing.is_empty() => format!("{}&{}", existing, query),
+            _ => query,
+        };
+        url.set_query(Some(&combined));
+    }
+
+    Ok(url)
+}
+
 async fn ignore_response(_: Response) -> Result<(), Void> {
     Ok(())
 }
 
+pub fn build_form_request<B>(method: Method, url: &Url, body: &B) -> Result<Request>
+where
+    B: Serialize,
+{
+    let mut request = Request::new(method, url.clone());
+
+    let headers = request.headers_mut();
+    headers.append(
+        "Content-Type",
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+
+    let request_body = request.body_mut();
+    *request_body = Some(
+        serde_urlencoded::to_string(body)
+            .context("Failed to serialize form body")?
+            .into(),
+    );
+
+    Ok(request)
+}
+
+// The body is sent as it's produced by the stream, using chunked transfer encoding
+fn build_stream_request<S>(
+    method: Method,
+    url: &Url,
+    body: S,
+    content_type: &HeaderValue,
+) -> Request
+where
+    S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+    let mut request = Request::new(method, url.clone());
+    request
+        .headers_mut()
+        .insert(CONTENT_TYPE, content_type.clone());
+    *request.body_mut() = Some(hyper::Body::wrap_stream(body).into());
+
+    request
+}
+
+pub fn build_multipart_request(method: Method, url: &Url, form: &MultipartForm) -> Result<Request> {
+    let mut request = Request::new(method, url.clone());
+
+    let headers = request.headers_mut();
+    headers.append("Content-Type", form.content_type().parse()?);
+
+    let request_body = request.body_mut();
+    *request_body = Some(form.to_bytes().into());
+
+    Ok(request)
+}
+
+// Try to turn a StatusError into an ApiError, any other error is returned unchanged
+fn into_api_error<E>(error: anyhow::Error) -> anyhow::Error
+where
+    E: for<'de> Deserialize<'de> + Debug + Send + Sync + 'static,
+{
+    let api_error = match error.downcast_ref::<StatusError>() {
+        Some(status_error) => match status_error.json::<E>() {
+            Ok(deserialized) => ApiError {
+                status: status_error.status,
+                headers: status_error.headers.clone(),
+                error: deserialized,
+            },
+            Err(_) => return error,
+        },
+        None => return error,
+    };
+
+    api_error.into()
+}
+
+/// Extract an optional json body from the response, an empty body returns `None`
+///
+/// Use this with [request](AuthorizedClient::request) for endpoints which can return `204 No Content`
+pub async fn optional_json<R>(response: Response) -> Result<Option<R>>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    optional_json_body(response, false).await
+}
+
+async fn optional_json_body<R>(
+    response: Response,
+    accept_any_content_type: bool,
+) -> Result<Option<R>>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    if body.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(deserialize_body(
+        status,
+        &headers,
+        &body,
+        accept_any_content_type,
+    )?))
+}
+
+// Wrap a failure of the http client in a NetworkError, errors of other transports are returned as is
+fn network_error(error: anyhow::Error, attempts: u32) -> anyhow::Error {
+    match error.downcast::<reqwest::Error>() {
+        Ok(error) => NetworkError::new(error, attempts).into(),
+        Err(error) => error,
+    }
+}
+
+// A long poll which timed out on the client, or on a proxy in between, is re-issued
+fn is_poll_timeout(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<StatusError>() {
+        return matches!(
+            error.status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT
+        );
+    }
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|error| error.is_timeout())
+}
+
+// The lifetime of a JWT access token according to its exp claim
+#[cfg(feature = "jwt")]
+fn jwt_expires_in(access_token: &str, clock: &dyn Clock) -> Option<Duration> {
+    crate::jwt::expires_in(access_token, clock.system_time())
+}
+
+#[cfg(not(feature = "jwt"))]
+fn jwt_expires_in(_access_token: &str, _clock: &dyn Clock) -> Option<Duration> {
+    None
+}
+
+pub(crate) async fn return_response(response: Response) -> Result<Response, Void> {
+    Ok(response)
+}
+
+async fn extract_headers(response: Response) -> Result<HeaderMap, Void> {
+    Ok(response.headers().clone())
+}
+
+// Read the whole response so it can be shared with other get requests, see: get_shared
+async fn shared_response(response: Response) -> Result<SharedResponse, reqwest::Error> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    Ok(SharedResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
 pub trait RequestBuilder {
     fn build(&self, client: Client) -> Result<Request>;
 }
@@ -308,8 +3355,89 @@ where
     }
 }
 
-#[derive(Clone)]
-struct Credentials {
-    access_token: String,
-    expires_at: Instant,
+// The response and parser of a streamed json body, the request is made when the stream is polled for the first time
+struct JsonStreamState {
+    url: Url,
+    response: Option<(Response, JsonStreamParser)>,
+}
+
+// The url of the next request of a long poll, see: poll
+struct PollState<F> {
+    url: Option<Url>,
+    url_builder: F,
+    last_request: Option<Instant>,
+}
+
+// The connection and parser of a server-sent events subscription
+struct SseState {
+    url: Url,
+    response: Option<Response>,
+    parser: EventParser,
+}
+
+// What happened during a request, used for the metrics
+#[derive(Default)]
+struct RequestOutcome {
+    method: Option<Method>,
+    url: Option<Url>,
+    status: Option<StatusCode>,
+    retries: u32,
+    hedged: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Credentials {
+    pub(crate) access_token: SecretString,
+    // The Authorization header with the access token, built once instead of for every request
+    // `None` when the access token can't be used in a header, the requests fail with a clear error
+    pub(crate) authorization: Option<HeaderValue>,
+    pub(crate) expires_at: Instant,
+    pub(crate) refresh_token: Option<SecretString>,
+    // The scopes the auth server granted, when the token response mentions them
+    pub(crate) scopes: Option<Vec<String>>,
+}
+
+impl Credentials {
+    // A static bearer token is used until it's replaced, so it never expires
+    fn from_static_token(access_token: String, clock: &dyn Clock) -> Self {
+        let access_token = SecretString::new(access_token);
+        Credentials {
+            // A static token is only used with the default settings, which don't use DPoP
+            authorization: authorization_header(&access_token, false),
+            access_token,
+            expires_at: clock.now() + Duration::from_secs(100 * 365 * 24 * 60 * 60),
+            refresh_token: None,
+            scopes: None,
+        }
+    }
+
+    // Check if the bearer token may still be used when a new one can't be fetched, see: Settings::serve_stale
+    // The placeholder credentials of a client which didn't get a token yet are never served
+    pub(crate) fn is_servable_stale(&self, serve_stale: Duration, clock: &dyn Clock) -> bool {
+        self.authorization.is_some()
+            && self
+                .expires_at
+                .checked_add(serve_stale)
+                .is_none_or(|stale_until| stale_until > clock.now())
+    }
+
+    // Credentials which make the next request fetch a bearer token
+    fn expired(expires_at: Instant) -> Self {
+        Credentials {
+            access_token: SecretString::default(),
+            authorization: None,
+            expires_at,
+            refresh_token: None,
+            scopes: None,
+        }
+    }
+}
+
+// The Authorization header for `access_token`, marked as sensitive so it's never logged
+fn authorization_header(access_token: &SecretString, dpop: bool) -> Option<HeaderValue> {
+    let scheme = if dpop { DPOP_SCHEME } else { "Bearer" };
+    let mut header =
+        HeaderValue::from_str(&format!("{} {}", scheme, access_token.expose_secret())).ok()?;
+    header.set_sensitive(true);
+    Some(header)
 }
@@ -0,0 +1,194 @@
+use crate::authorized_client::AuthorizedClient;
+use crate::http_client::HttpClients;
+use crate::interceptor::Interceptor;
+use crate::metrics::Metrics;
+use crate::request_signer::RequestSigner;
+use crate::retry_policy::RetryPolicy;
+use crate::settings::{ClientIdentity, ProxySettings, Settings, SettingsBuilder, TlsVersion};
+use crate::token_observer::TokenObserver;
+use crate::token_store::{MemoryTokenStore, TokenStore};
+use crate::transport::HttpTransport;
+use anyhow::Result;
+use reqwest::Client;
+use std::sync::Arc;
+
+/// Configure the settings and the extensions of an `AuthorizedClient` in one go
+///
+/// Create one using [builder](AuthorizedClient::builder).
+/// The most common settings have a method on the builder, use [settings](AuthorizedClientBuilder::settings) for the others.
+///
+/// ```
+/// use authorized_client::{AuthorizedClient, RetryPolicy};
+///
+///# fn doc_test() -> anyhow::Result<()> {
+/// let client = AuthorizedClient::builder()
+///     .client_id("xxxxxxxxxx")
+///     .client_secret("xxxxxxxxxx")
+///     .token_url("https://authorization-server.com/token")
+///     .base_url("https://api.example.com/v1")
+///     .retry_policy(RetryPolicy::new(3))
+///     .settings(|settings| settings.background_refresh(true))
+///     .try_build_lazy()?;
+///# Ok(())
+///# }
+/// ```
+pub struct AuthorizedClientBuilder {
+    settings: SettingsBuilder,
+    http: Http,
+    token_store: Arc<dyn TokenStore>,
+    extensions: Vec<Box<dyn FnOnce(AuthorizedClient) -> AuthorizedClient + Send>>,
+}
+
+// How the requests are sent, the last call to `http_client` or `transport` wins
+enum Http {
+    Settings,
+    Client(Client),
+    Transport(Arc<dyn HttpTransport>),
+}
+
+impl AuthorizedClientBuilder {
+    pub(crate) fn new() -> Self {
+        AuthorizedClientBuilder {
+            settings: Settings::builder(),
+            http: Http::Settings,
+            token_store: Arc::new(MemoryTokenStore::new()),
+            extensions: Vec::new(),
+        }
+    }
+
+    pub fn client_id(self, client_id: impl Into<String>) -> Self {
+        self.settings(|settings| settings.client_id(client_id))
+    }
+
+    pub fn client_secret(self, client_secret: impl Into<String>) -> Self {
+        self.settings(|settings| settings.client_secret(client_secret))
+    }
+
+    pub fn token_url(self, token_url: impl Into<String>) -> Self {
+        self.settings(|settings| settings.token_url(token_url))
+    }
+
+    /// Add a scope which is requested for the bearer token
+    pub fn scope(self, scope: impl Into<String>) -> Self {
+        self.settings(|settings| settings.scope(scope))
+    }
+
+    /// The url the paths passed to the `*_path` methods are relative to, see: [base_url](SettingsBuilder::base_url)
+    pub fn base_url(self, base_url: impl Into<String>) -> Self {
+        self.settings(|settings| settings.base_url(base_url))
+    }
+
+    /// PEM encoded root certificates which are trusted on top of the system root certificates
+    pub fn ca_bundle(self, ca_bundle: impl Into<String>) -> Self {
+        self.settings(|settings| settings.ca_bundle(ca_bundle))
+    }
+
+    /// The TLS client identity used for mutual TLS
+    pub fn client_identity(self, client_identity: ClientIdentity) -> Self {
+        self.settings(|settings| settings.client_identity(client_identity))
+    }
+
+    /// The minimum TLS version which is accepted
+    pub fn min_tls_version(self, min_tls_version: TlsVersion) -> Self {
+        self.settings(|settings| settings.min_tls_version(min_tls_version))
+    }
+
+    /// Send all requests through this proxy, by default the proxy environment variables are used
+    pub fn proxy(self, proxy: ProxySettings) -> Self {
+        self.settings(|settings| settings.proxy(proxy))
+    }
+
+    /// How requests which failed because of a transient error are retried, by default they are not retried
+    pub fn retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        self.settings(|settings| settings.retry_policy(retry_policy))
+    }
+
+    /// Change the other settings, the calls are applied in order so a later call overrides an earlier one
+    pub fn settings(mut self, configure: impl FnOnce(SettingsBuilder) -> SettingsBuilder) -> Self {
+        self.settings = configure(self.settings);
+        self
+    }
+
+    /// Send every request using `http_client`, see: [connect_with_client](AuthorizedClient::connect_with_client)
+    pub fn http_client(mut self, http_client: Client) -> Self {
+        self.http = Http::Client(http_client);
+        self
+    }
+
+    /// Send every request using `transport`, see: [connect_with_transport](AuthorizedClient::connect_with_transport)
+    pub fn transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.http = Http::Transport(Arc::new(transport));
+        self
+    }
+
+    /// Save the bearer tokens in `token_store`, see: [connect_with_store](AuthorizedClient::connect_with_store)
+    pub fn token_store(mut self, token_store: impl TokenStore + 'static) -> Self {
+        self.token_store = Arc::new(token_store);
+        self
+    }
+
+    /// Register an interceptor which is called for every request, see: [Interceptor](Interceptor)
+    pub fn interceptor(self, interceptor: impl Interceptor + 'static) -> Self {
+        self.extend(move |client| client.with_interceptor(interceptor))
+    }
+
+    /// Sign every request right before it's sent, see: [RequestSigner](RequestSigner)
+    pub fn signer(self, signer: impl RequestSigner + 'static) -> Self {
+        self.extend(move |client| client.with_signer(signer))
+    }
+
+    /// Register metrics which are called for every request and token refresh, see: [Metrics](Metrics)
+    pub fn metrics(self, metrics: impl Metrics + 'static) -> Self {
+        self.extend(move |client| client.with_metrics(metrics))
+    }
+
+    /// Register an observer which is called every time a new bearer token is requested, see: [TokenObserver](TokenObserver)
+    pub fn token_observer(self, token_observer: impl TokenObserver + 'static) -> Self {
+        self.extend(move |client| client.with_token_observer(token_observer))
+    }
+
+    /// Build the client and get the first bearer token, see: [connect](AuthorizedClient::connect)
+    pub async fn build(self) -> Result<AuthorizedClient> {
+        let settings = self.settings.build()?;
+        let http_clients = http_clients(self.http, &settings)?;
+        let client =
+            AuthorizedClient::connect_with(settings, http_clients, self.token_store).await?;
+
+        Ok(apply(client, self.extensions))
+    }
+
+    /// Build the client without getting a bearer token, see: [connect_lazy](AuthorizedClient::connect_lazy)
+    pub fn try_build_lazy(self) -> Result<AuthorizedClient> {
+        let settings = self.settings.build()?;
+        let http_clients = http_clients(self.http, &settings)?;
+        let client = AuthorizedClient::connect_lazy_with(settings, http_clients, self.token_store)?;
+
+        Ok(apply(client, self.extensions))
+    }
+
+    // The extensions are registered on the client once it has been created
+    fn extend(
+        mut self,
+        extension: impl FnOnce(AuthorizedClient) -> AuthorizedClient + Send + 'static,
+    ) -> Self {
+        self.extensions.push(Box::new(extension));
+        self
+    }
+}
+
+fn http_clients(http: Http, settings: &Settings) -> Result<HttpClients> {
+    Ok(match http {
+        Http::Settings => HttpClients::new(settings)?,
+        Http::Client(client) => HttpClients::from_client(client),
+        Http::Transport(transport) => HttpClients::from_transport(transport),
+    })
+}
+
+fn apply(
+    client: AuthorizedClient,
+    extensions: Vec<Box<dyn FnOnce(AuthorizedClient) -> AuthorizedClient + Send>>,
+) -> AuthorizedClient {
+    extensions
+        .into_iter()
+        .fold(client, |client, extension| extension(client))
+}
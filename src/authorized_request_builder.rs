@@ -0,0 +1,142 @@
+use crate::authorized_client::{return_response, AuthorizedClient};
+use crate::rate_limiter::Priority;
+use crate::retry_policy::RetryPolicy;
+use anyhow::{Context, Result};
+use oauth2::http;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Body, Response};
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// A request builder which is bound to an `AuthorizedClient`
+///
+/// Create one using [request_builder](AuthorizedClient::request_builder).
+/// The bearer token is added when the request is sent, so custom headers and query parameters can be added freely.
+pub struct AuthorizedRequestBuilder<'a> {
+    client: &'a AuthorizedClient,
+    builder: reqwest::RequestBuilder,
+    retry_policy: Option<RetryPolicy>,
+    audience: Option<String>,
+    priority: Priority,
+}
+
+impl<'a> AuthorizedRequestBuilder<'a> {
+    pub(crate) fn new(client: &'a AuthorizedClient, builder: reqwest::RequestBuilder) -> Self {
+        AuthorizedRequestBuilder {
+            client,
+            builder,
+            retry_policy: None,
+            audience: None,
+            priority: Priority::default(),
+        }
+    }
+
+    /// Add a header to the request
+    pub fn header<K, V>(self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.map(|builder| builder.header(key, value))
+    }
+
+    /// Add a set of headers to the request
+    pub fn headers(self, headers: HeaderMap) -> Self {
+        self.map(|builder| builder.headers(headers))
+    }
+
+    /// Add query parameters to the url of the request
+    pub fn query<T>(self, query: &T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        self.map(|builder| builder.query(query))
+    }
+
+    /// Set a json body on the request
+    pub fn json<T>(self, json: &T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        self.map(|builder| builder.json(json))
+    }
+
+    /// Set a raw body on the request
+    pub fn body<T>(self, body: T) -> Self
+    where
+        T: Into<Body>,
+    {
+        self.map(|builder| builder.body(body))
+    }
+
+    /// Set a timeout for this request, this overrides the [request_timeout](crate::Settings::request_timeout)
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.map(|builder| builder.timeout(timeout))
+    }
+
+    /// Set the retry policy for this request, this overrides the [retry_policy](crate::Settings::retry_policy)
+    ///
+    /// Use `RetryPolicy::new(1)` to disable the retries for this request.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Attach the bearer token for `audience` instead of the token of the client, see: [for_audience](AuthorizedClient::for_audience)
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Set the priority of this request for the [rate_limit](crate::Settings::rate_limit), see: [Priority](Priority)
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Send the request
+    ///
+    /// The request goes through the same authentication and retry logic as [request](AuthorizedClient::request),
+    /// this requires the body to be cloneable, which is the case for every body except streams.
+    pub async fn send(self) -> Result<Response> {
+        let request = self.builder.build()?;
+        let audience_client;
+        let client = match &self.audience {
+            Some(audience) => {
+                audience_client = self.client.for_audience(audience);
+                &audience_client
+            }
+            None => self.client,
+        };
+        let retry_policy = self
+            .retry_policy
+            .as_ref()
+            .unwrap_or(&client.settings.retry_policy);
+
+        client
+            .request_with_retry_policy(
+                || {
+                    request
+                        .try_clone()
+                        .context("Failed to clone the request, streaming bodies are not supported")
+                },
+                return_response,
+                retry_policy,
+                self.priority,
+            )
+            .await
+    }
+
+    fn map(self, f: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder) -> Self {
+        AuthorizedRequestBuilder {
+            client: self.client,
+            builder: f(self.builder),
+            retry_policy: self.retry_policy,
+            audience: self.audience,
+            priority: self.priority,
+        }
+    }
+}
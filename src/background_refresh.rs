@@ -0,0 +1,98 @@
+use crate::authorized_client::AuthorizedClient;
+use crate::clock::Clock;
+use crate::credentials_cell::CredentialsCell;
+use crate::metrics::MetricsRegistry;
+use crate::secret::SecretString;
+use crate::settings::Settings;
+use crate::token_store::TokenStore;
+use crate::transport::HttpTransport;
+use log::{debug, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+// Time to wait before trying again when the background refresh failed
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Handle to the task which refreshes the bearer token in the background
+///
+/// The task is aborted when the handle is dropped, this happens when the last `AuthorizedClient` clone is dropped.
+pub(crate) struct BackgroundRefresh {
+    handle: JoinHandle<()>,
+}
+
+impl BackgroundRefresh {
+    pub(crate) fn spawn(
+        runtime: &Handle,
+        credentials: Arc<CredentialsCell>,
+        settings: Settings,
+        token_transport: Arc<dyn HttpTransport>,
+        token_store: Arc<dyn TokenStore>,
+        metrics: MetricsRegistry,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let handle = runtime.spawn(async move {
+            loop {
+                // Wake up when the credentials enter the refresh leeway
+                let generation = credentials.generation();
+                let expires_at = credentials.load().expires_at;
+                // The time until the refresh according to the clock
+                let refresh_in = expires_at
+                    .checked_sub(settings.refresh_leeway)
+                    .unwrap_or(expires_at)
+                    .saturating_duration_since(clock.now());
+                clock.sleep(refresh_in).await;
+
+                // When a request refreshed the credentials in the meantime its result is used instead
+                debug!("Refreshing bearer token in the background");
+                let refresh = credentials.refresh(generation, &*clock, || async {
+                    AuthorizedClient::fetch_bearer_token(
+                        &settings,
+                        &*token_transport,
+                        &*token_store,
+                        &metrics,
+                        credentials
+                            .load()
+                            .refresh_token
+                            .as_ref()
+                            .map(SecretString::expose_secret),
+                        &*clock,
+                    )
+                    .await
+                });
+                match refresh.await {
+                    Ok(()) => {
+                        let lifetime = credentials
+                            .load()
+                            .expires_at
+                            .saturating_duration_since(clock.now());
+                        debug!("Refreshed bearer token in the background");
+
+                        // Don't refresh in a tight loop when the token lives shorter than the leeway
+                        if lifetime <= settings.refresh_leeway {
+                            warn!("The bearer token lifetime is shorter than the refresh leeway");
+                            clock.sleep(RETRY_DELAY).await;
+                        }
+                    }
+                    Err(error) => {
+                        warn!(
+                            "Failed to refresh bearer token in the background, retrying in {}s: {:#}",
+                            RETRY_DELAY.as_secs(),
+                            error
+                        );
+                        clock.sleep(RETRY_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        BackgroundRefresh { handle }
+    }
+}
+
+impl Drop for BackgroundRefresh {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
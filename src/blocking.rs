@@ -0,0 +1,666 @@
+//! A blocking `AuthorizedClient`, enable it using the `blocking` feature
+//!
+//! It supports the same settings as the async client, except for the ones which require an async runtime:
+//! background refresh, rate limiting, the circuit breaker, request deduplication and the response cache are ignored.
+//! The device code grant isn't supported either.
+//!
+//! ```no_run
+//!# fn doc_test() -> anyhow::Result<()> {
+//! use authorized_client::blocking::AuthorizedClient;
+//! use authorized_client::Settings;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Profile {
+//!     name: String,
+//! }
+//!
+//! let settings = Settings::builder()
+//!     .client_id("xxxxxxxxxx")
+//!     .client_secret("xxxxxxxxxx")
+//!     .token_url("https://authorization-server.com/token")
+//!     .build()?;
+//!
+//! let client = AuthorizedClient::connect(settings)?;
+//! let profile: Profile = client.get("https://api.example.com/profile".parse()?)?;
+//!# Ok(())
+//!# }
+//! ```
+
+use crate::allowed_hosts::check_allowed_host;
+use crate::authorized_client::Credentials;
+use crate::clock::SystemClock;
+use crate::deserialize_error::blocking_deserialize_json;
+use crate::discovery::{apply_metadata, metadata_urls};
+use crate::http_client::{
+    blocking_oauth_http_client, configure_client_builder, configure_connection_pool,
+};
+use crate::json_body::JsonBody;
+use crate::network_error::NetworkError;
+use crate::response_size::blocking_limit_response_size;
+use crate::retry_policy::retry_after;
+use crate::secret::SecretString;
+use crate::settings::{GrantType, Settings};
+use crate::status_error::{StatusError, UnauthorizedError};
+use crate::token_exchange::{token_exchange_request, token_exchange_response};
+use crate::token_store::{MemoryTokenStore, TokenStore};
+use crate::www_authenticate::BearerChallenge;
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use log::{debug, trace, warn};
+use oauth2::basic::BasicTokenResponse;
+use oauth2::{RefreshToken, ResourceOwnerPassword, ResourceOwnerUsername, Scope, TokenResponse};
+use reqwest::blocking::{Client, Request, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::redirect::Policy;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// The blocking variant of [AuthorizedClient](crate::AuthorizedClient)
+#[derive(Clone)]
+pub struct AuthorizedClient {
+    credentials: Arc<RwLock<Credentials>>,
+    http_client: Client,
+    token_http_client: Client,
+    settings: Settings,
+    token_store: Arc<dyn TokenStore>,
+    default_headers: HeaderMap,
+    // The end of the refresh cooldown and the error of the failed refresh, see: Settings::refresh_cooldown
+    failed_refresh: Arc<Mutex<Option<(Instant, String)>>>,
+}
+
+impl AuthorizedClient {
+    /// Create a new `AuthorizedClient`
+    ///
+    /// See: [connect](crate::AuthorizedClient::connect) for more info
+    pub fn connect(settings: Settings) -> Result<Self> {
+        Self::connect_with_store(settings, MemoryTokenStore::new())
+    }
+
+    /// Create a new `AuthorizedClient` which saves its bearer tokens in `token_store`
+    ///
+    /// See: [connect_with_store](crate::AuthorizedClient::connect_with_store) for more info
+    pub fn connect_with_store(
+        settings: Settings,
+        token_store: impl TokenStore + 'static,
+    ) -> Result<Self> {
+        settings.validate()?;
+        if settings.dpop {
+            bail!("Invalid settings: dpop is not supported by the blocking client");
+        }
+
+        let mut api_builder = configure_connection_pool!(
+            configure_client_builder!(Client::builder(), &settings),
+            &settings.connection_pool
+        )
+        .redirect(settings.redirect_policy.to_reqwest_policy());
+        if let Some(request_timeout) = settings.request_timeout {
+            api_builder = api_builder.timeout(request_timeout);
+        }
+        let http_client = api_builder
+            .build()
+            .context("Failed to create http client")?;
+
+        // Following redirects on the token endpoint opens the client up to SSRF vulnerabilities
+        let mut token_builder =
+            configure_client_builder!(Client::builder(), &settings).redirect(Policy::none());
+        if let Some(token_exchange_timeout) = settings.token_exchange_timeout {
+            token_builder = token_builder.timeout(token_exchange_timeout);
+        }
+        let token_http_client = token_builder
+            .build()
+            .context("Failed to create token http client")?;
+
+        let settings = discover_endpoints(settings, &token_http_client)?;
+
+        let token_store: Arc<dyn TokenStore> = Arc::new(token_store);
+        let credentials = match crate::AuthorizedClient::load_stored_credentials(
+            &settings,
+            &*token_store,
+            &SystemClock,
+        ) {
+            Some(credentials) => {
+                trace!("Reusing stored bearer token");
+                credentials
+            }
+            None => {
+                trace!("Initial connect to '{}'", settings.token_url);
+                fetch_bearer_token(&settings, &token_http_client, &*token_store, None)?
+            }
+        };
+
+        // The headers have been validated together with the settings
+        let default_headers = settings
+            .default_headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect();
+
+        Ok(AuthorizedClient {
+            credentials: Arc::new(RwLock::new(credentials)),
+            http_client,
+            token_http_client,
+            settings,
+            token_store,
+            default_headers,
+            failed_refresh: Arc::default(),
+        })
+    }
+
+    /// Make a get request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn get<R>(&self, url: Url) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            |response| blocking_deserialize_json(response, self.settings.accept_any_content_type),
+        )
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response without declaring a type, see: [JsonBody](JsonBody)
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn get_untyped(&self, url: Url) -> Result<JsonBody> {
+        let body = self.get_bytes(url)?;
+        Ok(JsonBody::from_bytes(&body))
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response as plain text
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn get_plain_text(&self, url: Url) -> Result<String> {
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            Response::text,
+        )
+    }
+
+    /// Make a get request to the endpoint.
+    /// Get the response as raw bytes
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn get_bytes(&self, url: Url) -> Result<Bytes> {
+        self.request(
+            || Ok(Request::new(Method::GET, url.clone())),
+            Response::bytes,
+        )
+    }
+
+    /// Make a post request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn post<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::POST, &url, body),
+            |response| blocking_deserialize_json(response, self.settings.accept_any_content_type),
+        )
+    }
+
+    /// Make a post request to the endpoint.
+    /// Ignores the response
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn post_ignore_response<B>(&self, url: Url, body: &B) -> Result<()>
+    where
+        B: Serialize,
+    {
+        self.request(
+            || build_json_request(Method::POST, &url, body),
+            |_| Ok::<_, reqwest::Error>(()),
+        )
+    }
+
+    /// Make a put request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn put<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::PUT, &url, body),
+            |response| blocking_deserialize_json(response, self.settings.accept_any_content_type),
+        )
+    }
+
+    /// Make a patch request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn patch<B, R>(&self, url: Url, body: &B) -> Result<R>
+    where
+        B: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || build_json_request(Method::PATCH, &url, body),
+            |response| blocking_deserialize_json(response, self.settings.accept_any_content_type),
+        )
+    }
+
+    /// Make a delete request to the endpoint.
+    /// Expects the response to be a json object
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn delete<R>(&self, url: Url) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        self.request(
+            || Ok(Request::new(Method::DELETE, url.clone())),
+            |response| blocking_deserialize_json(response, self.settings.accept_any_content_type),
+        )
+    }
+
+    /// Make a delete request to the endpoint.
+    /// Ignores the response
+    ///
+    /// See: [request](AuthorizedClient::request) for more info
+    pub fn delete_ignore_response(&self, url: Url) -> Result<()> {
+        self.request(
+            || Ok(Request::new(Method::DELETE, url.clone())),
+            |_| Ok::<_, reqwest::Error>(()),
+        )
+    }
+
+    fn ensure_authenticated(&self) -> Result<()> {
+        if self.needs_refresh(&self.credentials.read().unwrap()) {
+            let mut write_lock = self.credentials.write().unwrap();
+
+            // We make sure no other thread has updated the credentials in the time we were waiting for the write lock
+            if self.needs_refresh(&write_lock) {
+                debug!("Credentials are (almost) expired, refreshing the authentication");
+                if let Err(error) = self.refresh_authentication(&mut write_lock) {
+                    // Keep using the current token during a short outage of the token endpoint
+                    if !write_lock.is_servable_stale(self.settings.serve_stale, &SystemClock) {
+                        return Err(error);
+                    }
+                    warn!(
+                        "Failed to refresh the bearer token, using the current one: {:#}",
+                        error
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check if the credentials are expired or expire within the refresh leeway
+    fn needs_refresh(&self, credentials: &Credentials) -> bool {
+        match credentials
+            .expires_at
+            .checked_sub(self.settings.refresh_leeway)
+        {
+            Some(refresh_at) => refresh_at <= Instant::now(),
+            None => true,
+        }
+    }
+
+    fn refresh_authentication(&self, credentials: &mut Credentials) -> Result<()> {
+        let mut failed_refresh = self.failed_refresh.lock().unwrap();
+        if let Some((retry_at, error)) = &*failed_refresh {
+            let remaining = retry_at.saturating_duration_since(Instant::now());
+            if !remaining.is_zero() {
+                bail!(
+                    "Not requesting a new bearer token for another {}ms, the last refresh failed: {}",
+                    remaining.as_millis(),
+                    error
+                );
+            }
+        }
+
+        debug!("Refreshing bearer token");
+        let result = fetch_bearer_token(
+            &self.settings,
+            &self.token_http_client,
+            &*self.token_store,
+            credentials
+                .refresh_token
+                .as_ref()
+                .map(SecretString::expose_secret),
+        );
+        *failed_refresh = match &result {
+            Ok(_) => None,
+            Err(error) => Instant::now()
+                .checked_add(self.settings.refresh_cooldown)
+                .map(|retry_at| (retry_at, format!("{:#}", error))),
+        };
+        *credentials = result?;
+        debug!("Refreshed bearer token");
+        Ok(())
+    }
+
+    /// Make a request to the endpoint.
+    ///
+    /// A bearer token will automatically be included.
+    /// In case the bearer token gets rejected a new one is requested, this retry mechanism works [max_auth_retries](Settings::max_auth_retries) times, after that the client returns an error.
+    ///
+    /// Transient errors are retried according to the [retry_policy](Settings::retry_policy), by default they are not retried.
+    ///
+    /// Note: only `2xx` status codes return `Ok`, the rest returns an `Err`.
+    /// The error contains a [StatusError](StatusError) with the status, headers and body of the response
+    pub fn request<R, ExtractError>(
+        &self,
+        request_builder: impl Fn() -> Result<Request>,
+        response_builder: impl FnOnce(Response) -> Result<R, ExtractError>,
+    ) -> Result<R>
+    where
+        ExtractError: Into<anyhow::Error>,
+    {
+        // Ensure we don't attempt to make a request with an expired access token
+        self.ensure_authenticated()?;
+
+        let retry_policy = &self.settings.retry_policy;
+        let mut unauthorized_retries = 0;
+        let mut attempt = 1;
+        let mut retry_after_retries = 0;
+        let mut retry_after_waited = Duration::ZERO;
+
+        loop {
+            let mut request = request_builder()?;
+            check_allowed_host(self.settings.allowed_hosts.as_deref(), request.url())?;
+
+            // Add the default headers and the bearer token to the request headers
+            let headers = request.headers_mut();
+            for (name, value) in &self.default_headers {
+                if !headers.contains_key(name) {
+                    headers.insert(name, value.clone());
+                }
+            }
+            let authorization = self.credentials.read().unwrap().authorization.clone();
+            headers.insert(
+                "Authorization",
+                authorization.context("The bearer token is not a valid header value")?,
+            );
+
+            let response = match self.http_client.execute(request) {
+                Ok(response) => response,
+                Err(error) if retry_policy.should_retry_error(&error, attempt) => {
+                    let delay = retry_policy.delay(attempt);
+                    debug!(
+                        "Request failed, retrying in {}ms (attempt {}): {}",
+                        delay.as_millis(),
+                        attempt,
+                        error
+                    );
+                    sleep(delay);
+                    attempt += 1;
+                    continue;
+                }
+                Err(error) => return Err(NetworkError::new(error, attempt).into()),
+            };
+
+            let response = match self.settings.max_response_size {
+                Some(max_response_size) => {
+                    blocking_limit_response_size(response, max_response_size)?
+                }
+                None => response,
+            };
+
+            // When the server is throttling or temporarily unavailable: wait as long as the server asks and retry
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+            {
+                if let Some(delay) = retry_after(response.headers(), &SystemClock) {
+                    if retry_after_retries < retry_policy.max_retry_after_retries
+                        && retry_after_waited + delay <= retry_policy.retry_after_budget
+                    {
+                        retry_after_retries += 1;
+                        retry_after_waited += delay;
+                        sleep(delay);
+                        continue;
+                    }
+                }
+            }
+
+            match status {
+                status if status.is_success() => {
+                    return response_builder(response).map_err(Into::into)
+                }
+                StatusCode::UNAUTHORIZED => {
+                    let challenge = BearerChallenge::from_headers(response.headers());
+                    if let Some(challenge) = &challenge {
+                        debug!("The bearer token was rejected: {}", challenge);
+                    }
+
+                    // Refreshing the bearer token doesn't help for insufficient_scope
+                    if unauthorized_retries >= self.settings.max_auth_retries
+                        || challenge
+                            .as_ref()
+                            .is_some_and(BearerChallenge::is_insufficient_scope)
+                    {
+                        return Err(UnauthorizedError {
+                            retries: unauthorized_retries,
+                            challenge,
+                        }
+                        .into());
+                    }
+
+                    unauthorized_retries += 1;
+                    trace!("Unauthorized retry: {}", unauthorized_retries);
+
+                    // Don't DDOS the oauth server
+                    if unauthorized_retries > 1 {
+                        sleep(
+                            self.settings
+                                .auth_retry_backoff
+                                .delay(unauthorized_retries as u32 - 1),
+                        );
+                    }
+
+                    trace!("Force refreshing bearer token");
+                    self.refresh_authentication(&mut self.credentials.write().unwrap())?;
+                }
+                status if retry_policy.should_retry_status(status, attempt) => {
+                    let delay = retry_policy.delay(attempt);
+                    debug!(
+                        "Received status code {}, retrying in {}ms (attempt {})",
+                        status.as_u16(),
+                        delay.as_millis(),
+                        attempt
+                    );
+                    sleep(delay);
+                    attempt += 1;
+                }
+                status => {
+                    let headers = response.headers().clone();
+                    let body = response.text().unwrap_or_default();
+                    return Err(StatusError {
+                        status,
+                        headers,
+                        body,
+                    }
+                    .into());
+                }
+            }
+        }
+    }
+}
+
+pub fn build_json_request<B>(method: Method, url: &Url, body: &B) -> Result<Request>
+where
+    B: Serialize,
+{
+    let mut request = Request::new(method, url.clone());
+    request
+        .headers_mut()
+        .append("Content-Type", HeaderValue::from_static("application/json"));
+    *request.body_mut() = Some(
+        serde_json::to_string(&body)
+            .context("Failed to serialize body")?
+            .into(),
+    );
+
+    Ok(request)
+}
+
+// Discover the endpoints using the issuer url, see: [discover_endpoints](crate::discovery::discover_endpoints)
+fn discover_endpoints(settings: Settings, client: &Client) -> Result<Settings> {
+    let issuer_url = match &settings.issuer_url {
+        Some(issuer_url) => issuer_url.clone(),
+        None => return Ok(settings),
+    };
+
+    for metadata_url in metadata_urls(&issuer_url)? {
+        let response = match client.get(metadata_url.clone()).send() {
+            Ok(response) if response.status().is_success() => response,
+            _ => {
+                debug!("No auth server metadata at {}", metadata_url);
+                continue;
+            }
+        };
+
+        let metadata = blocking_deserialize_json(response, settings.accept_any_content_type)
+            .with_context(|| format!("Invalid auth server metadata at {}", metadata_url))?;
+        return apply_metadata(settings, metadata);
+    }
+
+    bail!(
+        "Failed to discover the endpoints of issuer '{}'",
+        issuer_url
+    )
+}
+
+// Get a new bearer token from the auth server and save it in the store
+// When a refresh token is available it's used first, if that fails a full token exchange is done
+fn fetch_bearer_token(
+    settings: &Settings,
+    token_http_client: &Client,
+    token_store: &dyn TokenStore,
+    refresh_token: Option<&str>,
+) -> Result<Credentials> {
+    let retry_policy = &settings.token_retry_policy;
+    let mut attempt = 1;
+    let credentials = loop {
+        let credentials = match refresh_token {
+            Some(refresh_token) => {
+                match refresh_bearer_token(settings, token_http_client, refresh_token) {
+                    Ok(credentials) => Ok(credentials),
+                    Err(error) => {
+                        debug!(
+                            "Failed to use refresh token, falling back to a full token exchange: {:#}",
+                            error
+                        );
+                        get_bearer_token(settings, token_http_client)
+                    }
+                }
+            }
+            None => get_bearer_token(settings, token_http_client),
+        };
+
+        match credentials {
+            Err(error) if retry_policy.should_retry_token_error(&error, attempt) => {
+                let delay = retry_policy.delay(attempt);
+                debug!(
+                    "Token exchange attempt {} failed, retrying in {:?}: {:#}",
+                    attempt, delay, error
+                );
+                sleep(delay);
+                attempt += 1;
+            }
+            credentials => break credentials?,
+        }
+    };
+
+    crate::AuthorizedClient::store_credentials(token_store, &credentials, &SystemClock);
+
+    Ok(credentials)
+}
+
+fn get_bearer_token(settings: &Settings, token_http_client: &Client) -> Result<Credentials> {
+    let oauth_client = crate::AuthorizedClient::oauth_client(settings)?;
+    let scopes = settings.scopes.iter().cloned().map(Scope::new);
+    let extra_params = crate::AuthorizedClient::token_request_params(settings)?;
+
+    let response: BasicTokenResponse = match &settings.grant_type {
+        GrantType::ClientCredentials => {
+            let mut exchange_request = oauth_client
+                .exchange_client_credentials()
+                .add_scopes(scopes);
+            for (name, value) in extra_params {
+                exchange_request = exchange_request.add_extra_param(name, value);
+            }
+            exchange_request
+                .request(|request| blocking_oauth_http_client(token_http_client, request))?
+        }
+        GrantType::Password { username, password } => {
+            let username = ResourceOwnerUsername::new(username.clone());
+            let password = ResourceOwnerPassword::new(password.expose_secret().to_string());
+            let mut exchange_request = oauth_client
+                .exchange_password(&username, &password)
+                .add_scopes(scopes);
+            for (name, value) in extra_params {
+                exchange_request = exchange_request.add_extra_param(name, value);
+            }
+            exchange_request
+                .request(|request| blocking_oauth_http_client(token_http_client, request))?
+        }
+        GrantType::DeviceCode { .. } => {
+            bail!("The blocking client doesn't support the device code grant")
+        }
+        GrantType::TokenExchange { .. } => {
+            let request = token_exchange_request(settings, extra_params)?;
+            token_exchange_response(blocking_oauth_http_client(token_http_client, request)?)?
+        }
+    };
+
+    trace!(
+        "Successfully got a bearer token, expires in {:?}",
+        response.expires_in()
+    );
+    crate::AuthorizedClient::credentials_from_response(settings, &response, &SystemClock)
+}
+
+fn refresh_bearer_token(
+    settings: &Settings,
+    token_http_client: &Client,
+    refresh_token: &str,
+) -> Result<Credentials> {
+    let oauth_client = crate::AuthorizedClient::oauth_client(settings)?;
+
+    let refresh_token = RefreshToken::new(refresh_token.to_string());
+    let mut exchange_request = oauth_client.exchange_refresh_token(&refresh_token);
+    for (name, value) in crate::AuthorizedClient::token_request_params(settings)? {
+        exchange_request = exchange_request.add_extra_param(name, value);
+    }
+    let response = exchange_request
+        .request(|request| blocking_oauth_http_client(token_http_client, request))?;
+
+    trace!(
+        "Successfully exchanged refresh token for a bearer token, expires in {:?}",
+        response.expires_in()
+    );
+
+    let mut credentials =
+        crate::AuthorizedClient::credentials_from_response(settings, &response, &SystemClock)?;
+
+    // The auth server doesn't have to issue a new refresh token, in that case the old one stays valid
+    if credentials.refresh_token.is_none() {
+        credentials.refresh_token = Some(SecretString::new(refresh_token.secret().to_owned()));
+    }
+
+    Ok(credentials)
+}
@@ -0,0 +1,326 @@
+use crate::interceptor::BoxFuture;
+use crate::transport::HttpTransport;
+use crate::wire_log::is_sensitive_header;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use oauth2::http;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use url::{form_urlencoded, Url};
+
+const REDACTED: &str = "[redacted]";
+
+// Secrets in the form bodies of token requests and the json bodies of token responses
+const SENSITIVE_FIELDS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "client_secret",
+    "client_assertion",
+    "password",
+    "assertion",
+    "subject_token",
+    "actor_token",
+];
+
+/// Record the requests and responses to a file and replay them in tests, see: [cassette](crate::Settings::cassette)
+///
+/// The cassette is a json file, the tokens, secrets and cookies are scrubbed before it's written.
+/// A replayed request is matched on its method and url, every recorded interaction is replayed once in the recorded order.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CassetteSettings {
+    /// The path of the cassette file
+    pub path: String,
+    pub mode: CassetteMode,
+}
+
+/// Whether a [cassette](CassetteSettings) is recorded or replayed
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CassetteMode {
+    /// Send the requests and save the interactions, an existing cassette is overwritten
+    Record,
+    /// Don't send any request, return the recorded responses instead
+    Replay,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<RecordedBody>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: RecordedBody,
+}
+
+// Text bodies are saved as is so the cassette stays readable
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum RecordedBody {
+    Text(String),
+    Binary { base64: String },
+}
+
+/// The interactions of a cassette, shared by the transport to the token endpoint and the one to the other endpoints
+pub(crate) struct Cassette {
+    settings: CassetteSettings,
+    state: Mutex<CassetteState>,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    replayed: Vec<bool>,
+}
+
+impl Cassette {
+    pub(crate) fn load(settings: &CassetteSettings) -> Result<Arc<Self>> {
+        let interactions = match settings.mode {
+            CassetteMode::Record => Vec::new(),
+            CassetteMode::Replay => {
+                let cassette = fs::read_to_string(&settings.path)
+                    .with_context(|| format!("Failed to read cassette '{}'", settings.path))?;
+                serde_json::from_str(&cassette)
+                    .with_context(|| format!("Invalid cassette '{}'", settings.path))?
+            }
+        };
+
+        Ok(Arc::new(Cassette {
+            settings: settings.clone(),
+            state: Mutex::new(CassetteState {
+                replayed: vec![false; interactions.len()],
+                interactions,
+            }),
+        }))
+    }
+
+    // The whole cassette is written after every interaction, so it's complete even when the test panics
+    fn record(&self, interaction: Interaction) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.interactions.push(interaction);
+
+        let cassette = serde_json::to_string_pretty(&state.interactions)?;
+        fs::write(&self.settings.path, cassette)
+            .with_context(|| format!("Failed to write cassette '{}'", self.settings.path))
+    }
+
+    fn replay(&self, request: &Request) -> Result<RecordedResponse> {
+        let method = request.method().as_str();
+        let url = scrub_url(request.url());
+
+        let mut state = self.state.lock().unwrap();
+        let CassetteState {
+            interactions,
+            replayed,
+        } = &mut *state;
+        let index = interactions
+            .iter()
+            .zip(replayed.iter())
+            .position(|(interaction, replayed)| {
+                !replayed && interaction.request.method == method && interaction.request.url == url
+            })
+            .with_context(|| {
+                format!(
+                    "Cassette '{}' has no interaction left for {} {}",
+                    self.settings.path, method, url
+                )
+            })?;
+
+        replayed[index] = true;
+        Ok(interactions[index].response.clone())
+    }
+}
+
+/// Records or replays the requests of `inner` using a [Cassette](Cassette)
+pub(crate) struct CassetteTransport {
+    pub(crate) inner: Arc<dyn HttpTransport>,
+    pub(crate) cassette: Arc<Cassette>,
+}
+
+impl HttpTransport for CassetteTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            if self.cassette.settings.mode == CassetteMode::Replay {
+                let url = request.url().clone();
+                let recorded = self.cassette.replay(&request)?;
+                return build_response(
+                    url,
+                    recorded.status,
+                    &recorded.headers,
+                    decode_body(&recorded.body)?,
+                );
+            }
+
+            let recorded_request = RecordedRequest {
+                method: request.method().to_string(),
+                url: scrub_url(request.url()),
+                headers: scrub_headers(request.headers()),
+                body: request
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .map(|body| encode_body(&scrub_body(request.headers(), body))),
+            };
+
+            // The body is read completely, so a recorded response is never streamed
+            let response = self.inner.execute(request).await?;
+            let url = response.url().clone();
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?.to_vec();
+
+            self.cassette.record(Interaction {
+                request: recorded_request,
+                response: RecordedResponse {
+                    status,
+                    headers: scrub_headers(&headers),
+                    body: encode_body(&scrub_body(&headers, &body)),
+                },
+            })?;
+
+            let headers = headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            build_response(url, status, &headers, body)
+        })
+    }
+}
+
+fn build_response(
+    url: Url,
+    status: u16,
+    headers: &[(String, String)],
+    body: Vec<u8>,
+) -> Result<Response> {
+    let mut builder = http::Response::builder().status(status).url(url);
+    if let Some(header_map) = builder.headers_mut() {
+        for (name, value) in headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                // The scrubbed body can have another length than the recorded one
+                if name != CONTENT_LENGTH {
+                    header_map.append(name, value);
+                }
+            }
+        }
+    }
+
+    Ok(builder
+        .body(body)
+        .context("Failed to build the cassette response")?
+        .into())
+}
+
+fn scrub_url(url: &Url) -> String {
+    if url.query().is_none() {
+        return url.to_string();
+    }
+
+    let mut scrubbed = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if SENSITIVE_FIELDS.contains(&name.as_ref()) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    scrubbed.query_pairs_mut().clear().extend_pairs(pairs);
+    scrubbed.to_string()
+}
+
+fn scrub_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_sensitive_header(name) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+// Scrub the secrets from form and json bodies, other bodies are recorded as is
+fn scrub_body(headers: &HeaderMap, body: &[u8]) -> Vec<u8> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    if content_type.starts_with("application/x-www-form-urlencoded") {
+        let pairs = form_urlencoded::parse(body).map(|(name, value)| {
+            let value = if SENSITIVE_FIELDS.contains(&name.as_ref()) {
+                REDACTED.into()
+            } else {
+                value
+            };
+            (name, value)
+        });
+        return form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish()
+            .into_bytes();
+    }
+
+    if content_type.starts_with("application/json") {
+        if let Ok(serde_json::Value::Object(mut object)) = serde_json::from_slice(body) {
+            for field in SENSITIVE_FIELDS {
+                if let Some(value) = object.get_mut(*field) {
+                    *value = REDACTED.into();
+                }
+            }
+            if let Ok(body) = serde_json::to_vec(&object) {
+                return body;
+            }
+        }
+    }
+
+    body.to_vec()
+}
+
+fn encode_body(body: &[u8]) -> RecordedBody {
+    match std::str::from_utf8(body) {
+        Ok(text) => RecordedBody::Text(text.to_string()),
+        Err(_) => RecordedBody::Binary {
+            base64: STANDARD.encode(body),
+        },
+    }
+}
+
+fn decode_body(body: &RecordedBody) -> Result<Vec<u8>> {
+    match body {
+        RecordedBody::Text(text) => Ok(text.as_bytes().to_vec()),
+        RecordedBody::Binary { base64 } => STANDARD
+            .decode(base64)
+            .context("Invalid base64 body in the cassette"),
+    }
+}
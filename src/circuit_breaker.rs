@@ -0,0 +1,293 @@
+use crate::network_error::{NetworkError, NetworkErrorKind};
+use crate::status_error::StatusError;
+use log::{info, warn};
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stop calling the endpoints for a while when they keep failing, see: [circuit_breaker](crate::Settings::circuit_breaker)
+///
+/// Connection errors, timeouts and `5xx` responses count as failures.
+/// After `failure_threshold` consecutive failures the circuit opens and requests fail immediately with a [CircuitOpenError](CircuitOpenError).
+/// Once `open_duration` has passed, `half_open_probes` requests are let through, the circuit closes again when all of them succeed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitBreakerSettings {
+    /// The number of consecutive failures which opens the circuit
+    pub failure_threshold: u32,
+    /// How long requests fail immediately once the circuit is open
+    #[serde(with = "crate::duration_format")]
+    pub open_duration: Duration,
+    /// The number of requests which have to succeed before the circuit closes again
+    pub half_open_probes: u32,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        CircuitBreakerSettings {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            half_open_probes: 1,
+        }
+    }
+}
+
+/// The error returned when the circuit breaker is open, no request has been made
+///
+/// Use `anyhow::Error::downcast_ref` to check for this error.
+#[derive(Debug)]
+pub struct CircuitOpenError;
+
+impl Display for CircuitOpenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Circuit breaker is open, the endpoints are failing")
+    }
+}
+
+impl Error for CircuitOpenError {}
+
+/// The state of the circuit, shared by all clones of an `AuthorizedClient`
+pub(crate) struct CircuitBreaker {
+    settings: CircuitBreakerSettings,
+    state: Mutex<State>,
+}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    // `probes` is the number of probes in flight
+    HalfOpen { probes: u32, successes: u32 },
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(settings: CircuitBreakerSettings) -> Self {
+        CircuitBreaker {
+            settings,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Check if a request is allowed, the outcome has to be recorded on the returned permit
+    pub(crate) fn try_acquire(&self) -> Result<Permit<'_>, CircuitOpenError> {
+        let mut state = self.state.lock().unwrap();
+
+        let probe = match &mut *state {
+            State::Closed { .. } => false,
+            State::Open { until } => {
+                if Instant::now() < *until {
+                    return Err(CircuitOpenError);
+                }
+                *state = State::HalfOpen {
+                    probes: 1,
+                    successes: 0,
+                };
+                true
+            }
+            State::HalfOpen { probes, successes } => {
+                if *probes + *successes >= self.settings.half_open_probes {
+                    return Err(CircuitOpenError);
+                }
+                *probes += 1;
+                true
+            }
+        };
+
+        Ok(Permit {
+            circuit_breaker: self,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn open(&self, state: &mut State) {
+        warn!(
+            "Circuit breaker opened, requests fail for the next {}ms",
+            self.settings.open_duration.as_millis()
+        );
+        *state = State::Open {
+            until: Instant::now() + self.settings.open_duration,
+        };
+    }
+}
+
+/// Permission to make a single request
+pub(crate) struct Permit<'a> {
+    circuit_breaker: &'a CircuitBreaker,
+    probe: bool,
+    recorded: bool,
+}
+
+impl Permit<'_> {
+    /// Record the outcome of the request
+    pub(crate) fn record(mut self, failed: bool) {
+        self.recorded = true;
+        let circuit_breaker = self.circuit_breaker;
+        let mut state = circuit_breaker.state.lock().unwrap();
+
+        match &mut *state {
+            State::Closed { failures } => {
+                if !failed {
+                    *failures = 0;
+                } else {
+                    *failures += 1;
+                    if *failures >= circuit_breaker.settings.failure_threshold {
+                        circuit_breaker.open(&mut state);
+                    }
+                }
+            }
+            State::HalfOpen { probes, successes } if self.probe => {
+                if failed {
+                    circuit_breaker.open(&mut state);
+                } else {
+                    *probes = probes.saturating_sub(1);
+                    *successes += 1;
+                    if *successes >= circuit_breaker.settings.half_open_probes {
+                        info!("Circuit breaker closed, the endpoints are healthy again");
+                        *state = State::Closed { failures: 0 };
+                    }
+                }
+            }
+            // Outcomes of requests which started before the circuit opened are ignored
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    // A probe which was cancelled before it finished frees its slot
+    fn drop(&mut self) {
+        if self.recorded || !self.probe {
+            return;
+        }
+        if let State::HalfOpen { probes, .. } = &mut *self.circuit_breaker.state.lock().unwrap() {
+            *probes = probes.saturating_sub(1);
+        }
+    }
+}
+
+/// Check if the request failed because the endpoint is unavailable
+pub(crate) fn is_failure(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<NetworkError>() {
+        return error.kind != NetworkErrorKind::Other;
+    }
+    if let Some(error) = error.downcast_ref::<StatusError>() {
+        return error.status.is_server_error();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+    use reqwest::StatusCode;
+
+    fn circuit_breaker(open_duration: Duration, half_open_probes: u32) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerSettings {
+            failure_threshold: 3,
+            open_duration,
+            half_open_probes,
+        })
+    }
+
+    fn record(circuit_breaker: &CircuitBreaker, failed: bool) {
+        circuit_breaker.try_acquire().unwrap().record(failed);
+    }
+
+    fn status_error(status: StatusCode) -> anyhow::Error {
+        StatusError {
+            status,
+            headers: HeaderMap::new(),
+            body: String::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let circuit_breaker = circuit_breaker(Duration::from_secs(60), 1);
+
+        record(&circuit_breaker, true);
+        record(&circuit_breaker, true);
+        // A success resets the count
+        record(&circuit_breaker, false);
+        record(&circuit_breaker, true);
+        record(&circuit_breaker, true);
+        assert!(circuit_breaker.try_acquire().is_ok());
+
+        record(&circuit_breaker, true);
+        assert!(circuit_breaker.try_acquire().is_err());
+    }
+
+    #[test]
+    fn closes_when_the_probes_succeed() {
+        let circuit_breaker = circuit_breaker(Duration::ZERO, 2);
+        for _ in 0..3 {
+            record(&circuit_breaker, true);
+        }
+
+        // Only `half_open_probes` requests are let through
+        let first = circuit_breaker.try_acquire().unwrap();
+        let second = circuit_breaker.try_acquire().unwrap();
+        assert!(circuit_breaker.try_acquire().is_err());
+
+        first.record(false);
+        assert!(circuit_breaker.try_acquire().is_err());
+        second.record(false);
+
+        // Closed again, the failures are counted from zero
+        record(&circuit_breaker, true);
+        record(&circuit_breaker, true);
+        assert!(circuit_breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn a_failed_probe_opens_the_circuit_again() {
+        let circuit_breaker = circuit_breaker(Duration::ZERO, 1);
+        for _ in 0..3 {
+            record(&circuit_breaker, true);
+        }
+        record(&circuit_breaker, true);
+
+        assert!(matches!(
+            *circuit_breaker.state.lock().unwrap(),
+            State::Open { .. }
+        ));
+    }
+
+    #[test]
+    fn a_dropped_probe_frees_its_slot() {
+        let circuit_breaker = circuit_breaker(Duration::ZERO, 1);
+        for _ in 0..3 {
+            record(&circuit_breaker, true);
+        }
+
+        let probe = circuit_breaker.try_acquire().unwrap();
+        assert!(circuit_breaker.try_acquire().is_err());
+        drop(probe);
+
+        assert!(circuit_breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn outcomes_from_before_the_circuit_opened_are_ignored() {
+        let circuit_breaker = circuit_breaker(Duration::from_secs(60), 1);
+        let late = circuit_breaker.try_acquire().unwrap();
+        for _ in 0..3 {
+            record(&circuit_breaker, true);
+        }
+
+        late.record(false);
+
+        assert!(circuit_breaker.try_acquire().is_err());
+    }
+
+    #[test]
+    fn only_server_errors_are_failures() {
+        assert!(is_failure(&status_error(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(!is_failure(&status_error(StatusCode::NOT_FOUND)));
+        assert!(!is_failure(&anyhow::anyhow!("Failed to deserialize")));
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{
+    EcdsaKeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING, RSA_PKCS1_SHA256,
+};
+use rustls_pemfile::Item;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The `client_assertion_type` for JWT bearer client assertions ([RFC 7523](https://tools.ietf.org/html/rfc7523))
+pub(crate) const CLIENT_ASSERTION_TYPE: &str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+// How long a client assertion is valid, it's only used for a single token request
+const ASSERTION_LIFETIME_SECS: u64 = 300;
+
+/// The algorithm used to sign a client assertion
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum JwtAlgorithm {
+    /// RSASSA-PKCS1-v1_5 using SHA-256, requires an RSA private key
+    RS256,
+    /// ECDSA using P-256 and SHA-256, requires an EC private key
+    ES256,
+}
+
+/// A private key which signs client assertions
+pub(crate) enum SigningKey {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair),
+}
+
+impl SigningKey {
+    /// Parse a PEM encoded private key (PKCS#8, PKCS#1 or SEC1) for the given algorithm
+    pub(crate) fn from_pem(pem: &str, algorithm: JwtAlgorithm) -> Result<Self> {
+        let item = rustls_pemfile::read_one(&mut pem.as_bytes())
+            .context("Failed to read private key")?
+            .context("The private key doesn't contain a PEM block")?;
+
+        match (algorithm, item) {
+            (JwtAlgorithm::RS256, Item::PKCS8Key(der)) => RsaKeyPair::from_pkcs8(&der)
+                .map(SigningKey::Rsa)
+                .map_err(|error| anyhow!("Invalid RSA private key: {}", error)),
+            (JwtAlgorithm::RS256, Item::RSAKey(der)) => RsaKeyPair::from_der(&der)
+                .map(SigningKey::Rsa)
+                .map_err(|error| anyhow!("Invalid RSA private key: {}", error)),
+            (JwtAlgorithm::ES256, Item::PKCS8Key(der)) => EcdsaKeyPair::from_pkcs8(
+                &ECDSA_P256_SHA256_FIXED_SIGNING,
+                &der,
+                &SystemRandom::new(),
+            )
+            .map(SigningKey::Ecdsa)
+            .map_err(|error| anyhow!("Invalid EC private key: {}", error)),
+            (JwtAlgorithm::ES256, Item::ECKey(_)) => {
+                bail!("SEC1 EC private keys are not supported, convert the key to PKCS#8")
+            }
+            (algorithm, _) => bail!(
+                "The private key is not a private key which can be used with {:?}",
+                algorithm
+            ),
+        }
+    }
+
+    fn algorithm(&self) -> JwtAlgorithm {
+        match self {
+            SigningKey::Rsa(_) => JwtAlgorithm::RS256,
+            SigningKey::Ecdsa(_) => JwtAlgorithm::ES256,
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let rng = SystemRandom::new();
+
+        match self {
+            SigningKey::Rsa(key_pair) => {
+                let mut signature = vec![0; key_pair.public().modulus_len()];
+                key_pair
+                    .sign(&RSA_PKCS1_SHA256, &rng, message, &mut signature)
+                    .map_err(|_| anyhow!("Failed to sign client assertion"))?;
+                Ok(signature)
+            }
+            SigningKey::Ecdsa(key_pair) => Ok(key_pair
+                .sign(&rng, message)
+                .map_err(|_| anyhow!("Failed to sign client assertion"))?
+                .as_ref()
+                .to_vec()),
+        }
+    }
+}
+
+/// Build a signed client assertion, the client id is used as issuer and subject
+pub(crate) fn build_client_assertion(
+    key: &SigningKey,
+    client_id: &str,
+    audience: &str,
+) -> Result<String> {
+    let issued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("The system time is before the unix epoch")?
+        .as_secs();
+
+    let mut jti = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut jti)
+        .map_err(|_| anyhow!("Failed to generate client assertion id"))?;
+    let jti: String = jti.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    let header = json!({
+        "alg": format!("{:?}", key.algorithm()),
+        "typ": "JWT",
+    });
+    let claims = json!({
+        "iss": client_id,
+        "sub": client_id,
+        "aud": audience,
+        "jti": jti,
+        "iat": issued_at,
+        "exp": issued_at + ASSERTION_LIFETIME_SECS,
+    });
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = key.sign(signing_input.as_bytes())?;
+
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
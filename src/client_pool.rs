@@ -0,0 +1,103 @@
+use crate::authorized_client::AuthorizedClient;
+use crate::settings::Settings;
+use anyhow::Result;
+use log::trace;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// A cache of `AuthorizedClient`s, one per key, e.g. per tenant
+///
+/// The client of a key is created when it's first used, using the settings returned by `settings_for`.
+/// Clients which haven't been used for `idle_timeout` are removed, their bearer token is dropped with them.
+///
+/// ```no_run
+///# async fn doc_test() -> anyhow::Result<()> {
+/// use authorized_client::{AuthorizedClientPool, Settings};
+/// use std::time::Duration;
+///
+/// let pool = AuthorizedClientPool::new(Duration::from_secs(600), |tenant: &String| {
+///     Settings::from_env_with_prefix(&format!("{}_", tenant.to_uppercase()))
+/// });
+///
+/// let client = pool.client(&"tenant_a".to_string()).await?;
+///# Ok(())
+///# }
+/// ```
+pub struct AuthorizedClientPool<K> {
+    settings_for: Box<SettingsFor<K>>,
+    idle_timeout: Duration,
+    entries: Mutex<HashMap<K, PoolEntry>>,
+}
+
+// Returns the settings of the client for a key
+type SettingsFor<K> = dyn Fn(&K) -> Result<Settings> + Send + Sync;
+
+struct PoolEntry {
+    // Concurrent callers for the same key wait for the same connect
+    client: Arc<OnceCell<AuthorizedClient>>,
+    last_used: Instant,
+}
+
+impl<K> AuthorizedClientPool<K>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(
+        idle_timeout: Duration,
+        settings_for: impl Fn(&K) -> Result<Settings> + Send + Sync + 'static,
+    ) -> Self {
+        AuthorizedClientPool {
+            settings_for: Box::new(settings_for),
+            idle_timeout,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the client of `key`, connecting it when it's not in the pool yet
+    ///
+    /// When connecting fails nothing is cached, the next call tries again.
+    pub async fn client(&self, key: &K) -> Result<AuthorizedClient> {
+        let client = {
+            let mut entries = self.entries.lock().unwrap();
+            self.evict_idle(&mut entries);
+
+            let entry = entries.entry(key.clone()).or_insert_with(|| PoolEntry {
+                client: Arc::new(OnceCell::new()),
+                last_used: Instant::now(),
+            });
+            entry.last_used = Instant::now();
+            entry.client.clone()
+        };
+
+        let client = client
+            .get_or_try_init(|| async {
+                trace!("Connecting a new client for the pool");
+                AuthorizedClient::connect((self.settings_for)(key)?).await
+            })
+            .await?;
+
+        Ok(client.clone())
+    }
+
+    /// Remove the client of `key` from the pool, e.g. when the credentials of a tenant changed
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    /// The number of clients in the pool, including the ones which are still connecting
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn evict_idle(&self, entries: &mut HashMap<K, PoolEntry>) {
+        let idle_timeout = self.idle_timeout;
+        entries.retain(|_, entry| entry.last_used.elapsed() < idle_timeout);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::interceptor::BoxFuture;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The source of the current time and the timer, used to decide when the bearer token expires and to wait between retries
+///
+/// Replace it using [with_clock](crate::AuthorizedClient::with_clock) to test the refresh behavior without waiting for a token to expire.
+pub trait Clock: Send + Sync {
+    /// The monotonic time, the expiry of the bearer token is compared with it
+    fn now(&self) -> Instant;
+
+    /// The wall clock time, used for the expiry of the bearer tokens in the [TokenStore](crate::TokenStore)
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    /// Wait for `duration`, used for the delays between retries, the rate limit, hedging and the background refresh
+    ///
+    /// The default uses the tokio timer, override it to use the timer of another runtime.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    /// Wait for `duration`, used for the delays between retries and hedging
+    ///
+    /// There's no timer on wasm32 without a runtime, the default returns immediately so retries are not delayed.
+    /// Override it using the timer of the browser, e.g. `gloo-timers`.
+    #[cfg(target_arch = "wasm32")]
+    fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// The clock of the operating system, it's used by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The clock of the tokio runtime, it stands still while the time is paused using `tokio::time::pause`
+///
+/// Use this in tests which use `tokio::time::advance`, the background refresh uses the timer of the runtime as well.
+/// Pausing the time requires the `test-util` feature of tokio.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+/// A clock which only moves when it's advanced, clones share the same time
+///
+/// ```
+/// use authorized_client::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now() - start, Duration::from_secs(60));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    started_at: Instant,
+    started_at_system_time: SystemTime,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// A clock which starts at the current time
+    pub fn new() -> Self {
+        ManualClock {
+            started_at: Instant::now(),
+            started_at_system_time: SystemTime::now(),
+            elapsed: Arc::default(),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.started_at + *self.elapsed.lock().unwrap()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.started_at_system_time + *self.elapsed.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_transport::{MockResponse, MockTransport};
+    use crate::{AuthorizedClient, SettingsBuilder};
+    use reqwest::StatusCode;
+    use std::future::Future;
+    use url::Url;
+
+    const TOKEN_URL: &str = "https://auth.example.com/token";
+    const URL: &str = "https://api.example.com/info";
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    // A lazy client, its first token is requested using the manual clock so the token expires exactly an hour later
+    fn client(
+        configure: impl FnOnce(SettingsBuilder) -> SettingsBuilder,
+    ) -> (AuthorizedClient, MockTransport, ManualClock) {
+        let transport = MockTransport::new();
+        let clock = ManualClock::new();
+        let client = AuthorizedClient::builder()
+            .client_id("client")
+            .client_secret("secret")
+            .token_url(TOKEN_URL)
+            .settings(configure)
+            .transport(transport.clone())
+            .try_build_lazy()
+            .unwrap()
+            .with_clock(clock.clone());
+        (client, transport, clock)
+    }
+
+    fn push_token(transport: &MockTransport) {
+        transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+    }
+
+    fn push_token_error(transport: &MockTransport) {
+        transport.push_response(
+            TOKEN_URL,
+            MockResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+        );
+    }
+
+    async fn get(client: &AuthorizedClient, transport: &MockTransport) -> anyhow::Result<()> {
+        transport.push_response(URL, MockResponse::new(StatusCode::OK).body("{}"));
+        client
+            .get::<serde_json::Value>(Url::parse(URL)?)
+            .await
+            .map(drop)
+    }
+
+    fn token_requests(transport: &MockTransport) -> usize {
+        transport
+            .requests()
+            .iter()
+            .filter(|request| request.url.as_str() == TOKEN_URL)
+            .count()
+    }
+
+    #[test]
+    fn refreshes_within_the_refresh_leeway() {
+        block_on(async {
+            let (client, transport, clock) =
+                client(|settings| settings.refresh_leeway(Duration::from_secs(60)));
+            push_token(&transport);
+            get(&client, &transport).await.unwrap();
+
+            clock.advance(Duration::from_secs(3600 - 61));
+            get(&client, &transport).await.unwrap();
+            assert_eq!(token_requests(&transport), 1);
+
+            clock.advance(Duration::from_secs(2));
+            push_token(&transport);
+            get(&client, &transport).await.unwrap();
+            assert_eq!(token_requests(&transport), 2);
+        });
+    }
+
+    #[test]
+    fn waits_for_the_refresh_cooldown_after_a_failed_refresh() {
+        block_on(async {
+            let (client, transport, clock) =
+                client(|settings| settings.refresh_cooldown(Duration::from_secs(10)));
+            push_token_error(&transport);
+            assert!(get(&client, &transport).await.is_err());
+            assert_eq!(token_requests(&transport), 1);
+
+            // During the cooldown no token is requested
+            clock.advance(Duration::from_secs(9));
+            let error = get(&client, &transport).await.unwrap_err();
+            assert!(error.to_string().contains("the last refresh failed"));
+            assert_eq!(token_requests(&transport), 1);
+
+            clock.advance(Duration::from_secs(2));
+            push_token(&transport);
+            get(&client, &transport).await.unwrap();
+            assert_eq!(token_requests(&transport), 2);
+        });
+    }
+
+    #[test]
+    fn serves_the_expired_token_within_the_serve_stale_window() {
+        block_on(async {
+            let (client, transport, clock) = client(|settings| {
+                settings
+                    .refresh_leeway(Duration::ZERO)
+                    .refresh_cooldown(Duration::ZERO)
+                    .serve_stale(Duration::from_secs(60))
+            });
+            push_token(&transport);
+            get(&client, &transport).await.unwrap();
+
+            clock.advance(Duration::from_secs(3600 + 59));
+            push_token_error(&transport);
+            get(&client, &transport).await.unwrap();
+            assert_eq!(token_requests(&transport), 2);
+
+            clock.advance(Duration::from_secs(2));
+            push_token_error(&transport);
+            assert!(get(&client, &transport).await.is_err());
+            assert_eq!(token_requests(&transport), 3);
+        });
+    }
+
+    #[test]
+    fn never_serves_the_placeholder_token_of_a_lazy_client() {
+        block_on(async {
+            let (client, transport, _) = client(|settings| {
+                settings
+                    .refresh_cooldown(Duration::ZERO)
+                    .serve_stale(Duration::from_secs(60))
+            });
+            push_token_error(&transport);
+
+            let error = get(&client, &transport).await.unwrap_err();
+            assert!(!format!("{:#}", error).contains("not a valid header value"));
+            assert_eq!(token_requests(&transport), 1);
+        });
+    }
+}
@@ -0,0 +1,23 @@
+/// The result of a conditional request, see: [get_if_none_match](crate::AuthorizedClient::get_if_none_match)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conditional<R> {
+    /// The resource changed, the new body and the `ETag` of the new version are returned
+    Modified(R, Option<String>),
+    /// The resource didn't change, the server responded with `304 Not Modified`
+    NotModified,
+}
+
+impl<R> Conditional<R> {
+    /// Whether the resource changed
+    pub fn is_modified(&self) -> bool {
+        matches!(self, Conditional::Modified(..))
+    }
+
+    /// The new body, `None` when the resource didn't change
+    pub fn modified(self) -> Option<R> {
+        match self {
+            Conditional::Modified(body, _) => Some(body),
+            Conditional::NotModified => None,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::deserialize_error::body_snippet;
+use anyhow::Result;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// The media types of newline delimited json, `application/x-ndjson` is the most common one
+const JSON_LINES_CONTENT_TYPES: &[&str] = &[
+    "application/x-ndjson",
+    "application/jsonl",
+    "application/x-jsonlines",
+];
+
+/// The error returned when a successful response should contain json but has another `Content-Type`, e.g. the html error page of a proxy
+///
+/// Disable the check using [accept_any_content_type](crate::Settings::accept_any_content_type).
+/// Use `anyhow::Error::downcast_ref` to get access to the details.
+#[derive(Clone, Debug)]
+pub struct UnexpectedContentTypeError {
+    pub expected: String,
+    pub actual: String,
+    /// The start of the body, it's truncated after 512 bytes
+    pub body_snippet: String,
+}
+
+impl Display for UnexpectedContentTypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unexpected Content-Type '{}', expected '{}'",
+            self.actual, self.expected
+        )?;
+        if !self.body_snippet.is_empty() {
+            write!(f, ", body: {}", self.body_snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for UnexpectedContentTypeError {}
+
+/// Check that the body is json, `application/json` and the `+json` suffix (e.g. `application/problem+json`) are accepted
+///
+/// A response without `Content-Type` is accepted, the body is deserialized anyway.
+pub(crate) fn check_json_content_type(headers: &HeaderMap, body: &[u8]) -> Result<()> {
+    let content_type = match headers.get(CONTENT_TYPE) {
+        Some(content_type) => String::from_utf8_lossy(content_type.as_bytes()).into_owned(),
+        None => return Ok(()),
+    };
+
+    let media_type = media_type(&content_type);
+    if media_type == JSON_CONTENT_TYPE || media_type.ends_with("+json") {
+        return Ok(());
+    }
+
+    Err(UnexpectedContentTypeError {
+        expected: JSON_CONTENT_TYPE.to_string(),
+        actual: content_type,
+        body_snippet: body_snippet(body),
+    }
+    .into())
+}
+
+/// Check that a streamed body is json or newline delimited json, see: [check_json_content_type](check_json_content_type)
+///
+/// The body hasn't been received yet, so the error doesn't contain a snippet of it.
+pub(crate) fn check_json_stream_content_type(headers: &HeaderMap) -> Result<()> {
+    if is_json_lines_content_type(headers) {
+        return Ok(());
+    }
+    check_json_content_type(headers, &[])
+}
+
+/// Whether the body is newline delimited json according to its `Content-Type`
+pub(crate) fn is_json_lines_content_type(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .map(|content_type| media_type(&String::from_utf8_lossy(content_type.as_bytes())))
+        .is_some_and(|media_type| JSON_LINES_CONTENT_TYPES.contains(&media_type.as_str()))
+}
+
+// Ignore the parameters, e.g. `; charset=utf-8`
+fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
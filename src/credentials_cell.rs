@@ -0,0 +1,170 @@
+use crate::authorized_client::Credentials;
+use crate::clock::Clock;
+use anyhow::{anyhow, bail, Result};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Mutex, MutexGuard};
+
+/// The current credentials of an `AuthorizedClient`, shared by its clones
+///
+/// The credentials are read from a `watch` channel: a clone of an `Arc` under its short read lock, which is not held during a refresh.
+/// Refreshes are serialized by `refresh_lock`, a tokio `Mutex` which is held while the new token is fetched.
+/// Only one refresh is in flight at once, the tasks which need a refresh meanwhile wait for its result instead of refreshing again.
+/// After a failed refresh the next one waits for the cooldown, until then a refresh fails immediately.
+pub(crate) struct CredentialsCell {
+    current: watch::Sender<Arc<Credentials>>,
+    refresh: watch::Sender<RefreshState>,
+    refresh_lock: Mutex<()>,
+    cooldown: Duration,
+}
+
+#[derive(Clone, Default)]
+struct RefreshState {
+    // Incremented every time a refresh finishes, successful or not
+    generation: u64,
+    in_flight: bool,
+    // The error of the last refresh, errors can't be cloned so it's shared with the waiting tasks
+    error: Option<Arc<anyhow::Error>>,
+    // The end of the cooldown after the last refresh failed
+    retry_at: Option<Instant>,
+}
+
+impl CredentialsCell {
+    pub(crate) fn new(credentials: Credentials, cooldown: Duration) -> Self {
+        CredentialsCell {
+            current: watch::Sender::new(Arc::new(credentials)),
+            refresh: watch::Sender::new(RefreshState::default()),
+            refresh_lock: Mutex::new(()),
+            cooldown,
+        }
+    }
+
+    /// The current credentials
+    pub(crate) fn load(&self) -> Arc<Credentials> {
+        self.current.borrow().clone()
+    }
+
+    /// Replace the credentials, requests which already loaded the old ones keep using them
+    pub(crate) fn store(&self, credentials: Credentials) {
+        self.current.send_replace(Arc::new(credentials));
+    }
+
+    /// The number of finished refreshes, get it before loading the credentials and pass it to [refresh](CredentialsCell::refresh)
+    pub(crate) fn generation(&self) -> u64 {
+        self.refresh.borrow().generation
+    }
+
+    /// Replace the credentials with the result of `fetch`, unless another task refreshed them since `generation`
+    ///
+    /// When a refresh is already in flight this waits for it and returns its result, `fetch` is not called.
+    /// When the task which is refreshing is cancelled one of the waiting tasks takes over.
+    /// During the cooldown after a failed refresh this fails immediately with the error of that refresh.
+    pub(crate) async fn refresh<F, Fut>(
+        &self,
+        generation: u64,
+        clock: &dyn Clock,
+        fetch: F,
+    ) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Credentials>>,
+    {
+        let mut receiver = self.refresh.subscribe();
+        loop {
+            let now = clock.now();
+            let mut leader = false;
+            let mut cooling_down = None;
+            self.refresh.send_if_modified(|state| {
+                if state.generation != generation || state.in_flight {
+                    return false;
+                }
+                match (state.retry_at, &state.error) {
+                    (Some(retry_at), Some(error)) if retry_at > now => {
+                        cooling_down = Some((retry_at - now, error.clone()));
+                        false
+                    }
+                    _ => {
+                        state.in_flight = true;
+                        leader = true;
+                        true
+                    }
+                }
+            });
+            if let Some((remaining, error)) = cooling_down {
+                bail!(
+                    "Not requesting a new bearer token for another {}ms, the last refresh failed: {:#}",
+                    remaining.as_millis(),
+                    error
+                );
+            }
+            if leader {
+                break;
+            }
+
+            let state = receiver
+                .wait_for(|state| state.generation != generation || !state.in_flight)
+                .await?
+                .clone();
+            if state.generation != generation {
+                return match state.error {
+                    Some(error) => Err(anyhow!("{:#}", error)),
+                    None => Ok(()),
+                };
+            }
+            // The task which was refreshing got cancelled, try to take over
+        }
+
+        let in_flight = InFlight {
+            refresh: &self.refresh,
+        };
+        let result = {
+            let _refresh_lock = self.lock_refresh().await;
+            fetch().await
+        };
+
+        let (result, error, retry_at) = match result {
+            Ok(credentials) => {
+                self.store(credentials);
+                (Ok(()), None, None)
+            }
+            Err(error) => {
+                let shared = Arc::new(anyhow!("{:#}", error));
+                (
+                    Err(error),
+                    Some(shared),
+                    clock.now().checked_add(self.cooldown),
+                )
+            }
+        };
+        self.refresh.send_modify(|state| {
+            state.generation += 1;
+            state.in_flight = false;
+            state.error = error;
+            state.retry_at = retry_at;
+        });
+        drop(in_flight);
+
+        result
+    }
+
+    /// Wait until no other task is changing the credentials, e.g. to revoke them without a refresh in between
+    pub(crate) async fn lock_refresh(&self) -> MutexGuard<'_, ()> {
+        self.refresh_lock.lock().await
+    }
+}
+
+// Marks the refresh as no longer in flight when the refreshing task is cancelled
+struct InFlight<'a> {
+    refresh: &'a watch::Sender<RefreshState>,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.refresh.send_if_modified(|state| {
+            let cancelled = state.in_flight;
+            state.in_flight = false;
+            cancelled
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use crate::content_type::check_json_content_type;
+use anyhow::Result;
+use oauth2::http::StatusCode;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::Response;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The maximum number of bytes of the body which are included in a [DeserializeError](DeserializeError)
+const BODY_SNIPPET_SIZE: usize = 512;
+
+/// The error returned when the json body of a successful response doesn't match the expected type
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the details, e.g. to log them.
+#[derive(Clone, Debug)]
+pub struct DeserializeError {
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+    /// The path to the field which failed to deserialize, e.g. `items[2].id`, `.` when it's the body itself
+    pub path: String,
+    /// The message of `serde_json`
+    pub message: String,
+    /// The start of the body, it's truncated after 512 bytes
+    pub body_snippet: String,
+}
+
+impl Display for DeserializeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to deserialize the response (CODE={}, Content-Type={}) at '{}': {}",
+            self.status.as_u16(),
+            self.content_type.as_deref().unwrap_or("none"),
+            self.path,
+            self.message
+        )?;
+        if !self.body_snippet.is_empty() {
+            write!(f, ", body: {}", self.body_snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DeserializeError {}
+
+/// Read the body of `response` and deserialize it as json
+pub(crate) async fn deserialize_json<R>(
+    response: Response,
+    accept_any_content_type: bool,
+) -> Result<R>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+
+    deserialize_body(status, &headers, &body, accept_any_content_type)
+}
+
+/// Read the body of a blocking `response` and deserialize it as json
+#[cfg(feature = "blocking")]
+pub(crate) fn blocking_deserialize_json<R>(
+    response: reqwest::blocking::Response,
+    accept_any_content_type: bool,
+) -> Result<R>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes()?;
+
+    deserialize_body(status, &headers, &body, accept_any_content_type)
+}
+
+/// Deserialize a json `body`, the error includes the path of the field which failed
+pub(crate) fn deserialize_body<R>(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+    accept_any_content_type: bool,
+) -> Result<R>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    if !accept_any_content_type {
+        check_json_content_type(headers, body)?;
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    serde_path_to_error::deserialize(&mut deserializer).map_err(|error| {
+        DeserializeError {
+            status,
+            content_type: headers
+                .get(CONTENT_TYPE)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned()),
+            path: error.path().to_string(),
+            message: error.inner().to_string(),
+            body_snippet: body_snippet(body),
+        }
+        .into()
+    })
+}
+
+/// The start of `body` for error messages
+pub(crate) fn body_snippet(body: &[u8]) -> String {
+    String::from_utf8_lossy(&body[..body.len().min(BODY_SNIPPET_SIZE)]).into_owned()
+}
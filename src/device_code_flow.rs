@@ -0,0 +1,151 @@
+use crate::authorized_client::AuthorizedClient;
+use crate::clock::SystemClock;
+use crate::discovery::discover_endpoints;
+use crate::http_client::{oauth_http_client, HttpClients};
+use crate::settings::{GrantType, Settings};
+use crate::token_store::{MemoryTokenStore, TokenStore};
+use anyhow::{bail, Result};
+use log::trace;
+use oauth2::devicecode::StandardDeviceAuthorizationResponse;
+use oauth2::{DeviceAuthorizationUrl, Scope, TokenResponse};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// The code the user has to enter at the verification uri to authorize the client
+#[derive(Clone, Debug)]
+pub struct DeviceUserCode {
+    pub user_code: String,
+    pub verification_uri: String,
+    /// The verification uri which already contains the user code, not every auth server provides this
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: Duration,
+}
+
+/// Connect using the device authorization grant ([RFC 8628](https://tools.ietf.org/html/rfc8628))
+///
+/// This is meant for interactive CLI tools, the user authorizes the client by visiting a url on another device.
+/// The `settings` must use [GrantType::DeviceCode](GrantType::DeviceCode).
+///
+/// ```no_run
+///# async fn doc_test() -> anyhow::Result<()> {
+/// use authorized_client::{DeviceCodeFlow, GrantType, Settings};
+///
+/// let settings = Settings::builder()
+///     .client_id("xxxxxxxxxx")
+///     .token_url("https://authorization-server.com/token")
+///     .grant_type(GrantType::DeviceCode {
+///         device_authorization_url: "https://authorization-server.com/device".to_string(),
+///     })
+///     .build()?;
+///
+/// let client = DeviceCodeFlow::new(settings)
+///     .connect(|code| {
+///         println!("Visit {} and enter the code {}", code.verification_uri, code.user_code)
+///     })
+///     .await?;
+///# Ok(())
+///# }
+/// ```
+pub struct DeviceCodeFlow {
+    settings: Settings,
+    token_store: Arc<dyn TokenStore>,
+}
+
+impl DeviceCodeFlow {
+    pub fn new(settings: Settings) -> Self {
+        DeviceCodeFlow {
+            settings,
+            token_store: Arc::new(MemoryTokenStore::new()),
+        }
+    }
+
+    /// Save the bearer token in `token_store`, a still valid stored token is reused without asking the user again
+    pub fn token_store(mut self, token_store: impl TokenStore + 'static) -> Self {
+        self.token_store = Arc::new(token_store);
+        self
+    }
+
+    /// Start the device authorization and wait until the user authorized the client
+    ///
+    /// `on_user_code` is called with the code the user has to enter, show it to the user.
+    pub async fn connect(
+        self,
+        on_user_code: impl FnOnce(&DeviceUserCode),
+    ) -> Result<AuthorizedClient> {
+        let settings = self.settings;
+        settings.validate()?;
+
+        let device_authorization_url = match &settings.grant_type {
+            GrantType::DeviceCode {
+                device_authorization_url,
+            } => device_authorization_url.clone(),
+            _ => bail!("DeviceCodeFlow requires the device code grant type"),
+        };
+
+        let http_clients = HttpClients::new(&settings)?;
+        let settings = discover_endpoints(settings, &*http_clients.token).await?;
+
+        if let Some(credentials) =
+            AuthorizedClient::load_stored_credentials(&settings, &*self.token_store, &SystemClock)
+        {
+            trace!("Reusing stored bearer token");
+            return Ok(AuthorizedClient::from_credentials(
+                settings,
+                http_clients,
+                self.token_store,
+                credentials,
+            ));
+        }
+        let token_transport = &*http_clients.token;
+
+        trace!("Starting device authorization");
+        let oauth_client = AuthorizedClient::oauth_client(&settings)?
+            .set_device_authorization_url(DeviceAuthorizationUrl::new(device_authorization_url)?);
+
+        let details: StandardDeviceAuthorizationResponse = oauth_client
+            .exchange_device_code()?
+            .add_scopes(settings.scopes.iter().cloned().map(Scope::new))
+            .request_async(|request| oauth_http_client(token_transport, request))
+            .await?;
+
+        on_user_code(&DeviceUserCode {
+            user_code: details.user_code().secret().to_owned(),
+            verification_uri: details.verification_uri().to_string(),
+            verification_uri_complete: details
+                .verification_uri_complete()
+                .map(|uri| uri.secret().to_owned()),
+            expires_in: details.expires_in(),
+        });
+
+        // Poll the token endpoint until the user authorized the client or the device code expired
+        trace!("Waiting for the user to authorize the device");
+        let mut exchange_request = oauth_client.exchange_device_access_token(&details);
+        for (name, value) in AuthorizedClient::token_request_params(&settings)? {
+            exchange_request = exchange_request.add_extra_param(name, value);
+        }
+        let response = exchange_request
+            .request_async(
+                |request| oauth_http_client(token_transport, request),
+                sleep,
+                None,
+            )
+            .await?;
+
+        trace!(
+            "Successfully exchanged device code for a bearer token, expires in {:?}",
+            response.expires_in()
+        );
+
+        let credentials =
+            AuthorizedClient::credentials_from_response(&settings, &response, &SystemClock)?;
+        AuthorizedClient::store_credentials(&*self.token_store, &credentials, &SystemClock);
+
+        Ok(AuthorizedClient::from_credentials(
+            settings,
+            http_clients,
+            self.token_store,
+            credentials,
+        ))
+    }
+}
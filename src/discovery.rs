@@ -0,0 +1,115 @@
+use crate::deserialize_error::deserialize_json;
+use crate::settings::Settings;
+use crate::transport::HttpTransport;
+use anyhow::{bail, Context, Result};
+use log::{debug, trace};
+use reqwest::{Method, Request};
+use serde::Deserialize;
+use url::Url;
+
+/// The part of the auth server metadata we use, see: [RFC 8414](https://tools.ietf.org/html/rfc8414)
+#[derive(Deserialize)]
+pub(crate) struct ServerMetadata {
+    issuer: Option<String>,
+    token_endpoint: String,
+    introspection_endpoint: Option<String>,
+    revocation_endpoint: Option<String>,
+}
+
+/// The urls where the metadata of `issuer_url` can be found, in the order they should be tried
+///
+/// OpenID Connect appends the well-known path to the issuer, RFC 8414 inserts it before the path of the issuer.
+pub(crate) fn metadata_urls(issuer_url: &str) -> Result<Vec<Url>> {
+    let issuer = Url::parse(issuer_url).with_context(|| {
+        format!(
+            "Invalid settings: issuer_url '{}' is not a valid url",
+            issuer_url
+        )
+    })?;
+    let issuer_path = issuer.path().trim_end_matches('/');
+
+    let mut openid_configuration = issuer.clone();
+    openid_configuration.set_path(&format!("{}/.well-known/openid-configuration", issuer_path));
+
+    let mut oauth_authorization_server = issuer.clone();
+    oauth_authorization_server.set_path(&format!(
+        "/.well-known/oauth-authorization-server{}",
+        issuer_path
+    ));
+
+    Ok(vec![openid_configuration, oauth_authorization_server])
+}
+
+/// Fill in the endpoints which are not configured explicitly
+pub(crate) fn apply_metadata(mut settings: Settings, metadata: ServerMetadata) -> Result<Settings> {
+    let issuer_url = settings.issuer_url.as_deref().unwrap_or_default();
+    if let Some(issuer) = &metadata.issuer {
+        // Prevents an attacker who controls the metadata from impersonating another auth server
+        if issuer.trim_end_matches('/') != issuer_url.trim_end_matches('/') {
+            bail!(
+                "The auth server metadata is for issuer '{}' instead of '{}'",
+                issuer,
+                issuer_url
+            );
+        }
+    }
+
+    if settings.token_url.is_empty() {
+        settings.token_url = metadata.token_endpoint;
+    }
+    if settings.introspection_url.is_none() {
+        settings.introspection_url = metadata.introspection_endpoint;
+    }
+    if settings.revocation_url.is_none() {
+        settings.revocation_url = metadata.revocation_endpoint;
+    }
+
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// Discover the endpoints using the [issuer_url](Settings::issuer_url), the settings are returned as is when it's not set
+pub(crate) async fn discover_endpoints(
+    settings: Settings,
+    transport: &dyn HttpTransport,
+) -> Result<Settings> {
+    let issuer_url = match &settings.issuer_url {
+        Some(issuer_url) => issuer_url.clone(),
+        None => return Ok(settings),
+    };
+
+    for metadata_url in metadata_urls(&issuer_url)? {
+        trace!("Fetching auth server metadata from {}", metadata_url);
+        let response = match transport
+            .execute(Request::new(Method::GET, metadata_url.clone()))
+            .await
+        {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                debug!(
+                    "No auth server metadata at {} (CODE={})",
+                    metadata_url,
+                    response.status().as_u16()
+                );
+                continue;
+            }
+            Err(error) => {
+                debug!(
+                    "Failed to fetch auth server metadata from {}: {}",
+                    metadata_url, error
+                );
+                continue;
+            }
+        };
+
+        let metadata = deserialize_json(response, settings.accept_any_content_type)
+            .await
+            .with_context(|| format!("Invalid auth server metadata at {}", metadata_url))?;
+        return apply_metadata(settings, metadata);
+    }
+
+    bail!(
+        "Failed to discover the endpoints of issuer '{}'",
+        issuer_url
+    )
+}
@@ -0,0 +1,105 @@
+use crate::response_size::ResponseTooLargeError;
+use crate::status_error::StatusError;
+use oauth2::http::StatusCode;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_RANGE, ETAG, LAST_MODIFIED};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// The progress of a [download](crate::AuthorizedClient::download_with_progress)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// The number of bytes in the file, including the bytes of an earlier, interrupted download
+    pub downloaded: u64,
+    /// The size of the complete file, when the server sent it
+    pub total: Option<u64>,
+}
+
+/// The error returned when a download ended before the file was complete
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the expected and actual size.
+#[derive(Clone, Debug)]
+pub struct IncompleteDownloadError {
+    /// The size the server announced in the `Content-Length` or `Content-Range` header
+    pub expected: u64,
+    /// The size of the downloaded file
+    pub actual: u64,
+}
+
+impl Display for IncompleteDownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The download is incomplete, expected {} bytes but got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for IncompleteDownloadError {}
+
+/// A `Content-Range: bytes <start>-<end>/<total>` header
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ContentRange {
+    pub(crate) start: Option<u64>,
+    pub(crate) total: Option<u64>,
+}
+
+/// Parse a `Content-Range` header, the start is missing for `bytes */<total>` and the total for `bytes <start>-<end>/*`
+pub(crate) fn parse_content_range(value: &str) -> Option<ContentRange> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+
+    let start = match range.trim() {
+        "*" => None,
+        range => Some(range.split_once('-')?.0.trim().parse().ok()?),
+    };
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+
+    Some(ContentRange { start, total })
+}
+
+/// The size of the complete file, when the server responded `416 Range Not Satisfiable` to a resumed download
+pub(crate) fn unsatisfiable_range_total(error: &anyhow::Error) -> Option<u64> {
+    let error = error.downcast_ref::<StatusError>()?;
+    if error.status != StatusCode::RANGE_NOT_SATISFIABLE {
+        return None;
+    }
+
+    let content_range = error.headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    parse_content_range(content_range)?.total
+}
+
+/// Whether the connection dropped while the body was read, the download can be resumed from where it stopped
+pub(crate) fn is_interrupted(error: &anyhow::Error) -> bool {
+    if error
+        .chain()
+        .any(|cause| cause.is::<ResponseTooLargeError>())
+    {
+        return false;
+    }
+
+    error.is::<IncompleteDownloadError>()
+        || error
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .any(|error| error.is_body() || error.is_timeout())
+}
+
+/// The file a download is written to until it's complete, `<path>.part`
+pub(crate) fn part_path(path: &Path) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+/// The `If-Range` value which makes sure a resumed download is the same file, a strong `ETag` or else the `Last-Modified` date
+pub(crate) fn resume_validator(headers: &HeaderMap) -> Option<HeaderValue> {
+    // A weak ETag can't be used for a range request, see: RFC 9110 section 13.1.5
+    match headers.get(ETAG) {
+        Some(etag) if !etag.as_bytes().starts_with(b"W/") => Some(etag.clone()),
+        _ => headers.get(LAST_MODIFIED).cloned(),
+    }
+}
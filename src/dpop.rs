@@ -0,0 +1,200 @@
+use crate::interceptor::BoxFuture;
+use crate::transport::HttpTransport;
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use log::debug;
+use oauth2::http::StatusCode;
+use reqwest::header::{HeaderName, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Method, Request, Response};
+use ring::digest::{digest, SHA256};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// The `Authorization` scheme of a DPoP-bound access token
+pub(crate) const DPOP_SCHEME: &str = "DPoP";
+
+const DPOP: HeaderName = HeaderName::from_static("dpop");
+const DPOP_NONCE: HeaderName = HeaderName::from_static("dpop-nonce");
+
+/// The key pair which the DPoP proofs are signed with ([RFC 9449](https://tools.ietf.org/html/rfc9449))
+///
+/// A new key is generated for every client, the bearer tokens are bound to it.
+pub(crate) struct DpopKey {
+    key_pair: EcdsaKeyPair,
+    rng: SystemRandom,
+    jwk: serde_json::Value,
+}
+
+impl DpopKey {
+    pub(crate) fn generate() -> Result<Self> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .map_err(|_| anyhow!("Failed to generate a DPoP key pair"))?;
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .map_err(|_| anyhow!("Failed to generate a DPoP key pair"))?;
+
+        // The public key is an uncompressed point: 0x04 followed by the x and y coordinates
+        let public_key = key_pair.public_key().as_ref();
+        let jwk = json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+        });
+
+        Ok(DpopKey { key_pair, rng, jwk })
+    }
+
+    /// Sign a proof for a single request, `access_token` is only set for requests to the endpoints
+    fn proof(
+        &self,
+        method: &Method,
+        url: &Url,
+        nonce: Option<&str>,
+        access_token: Option<&str>,
+    ) -> Result<String> {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("The system time is before the unix epoch")?
+            .as_secs();
+
+        // The query and fragment are not part of the url in the proof
+        let mut htu = url.clone();
+        htu.set_query(None);
+        htu.set_fragment(None);
+
+        let jti: [u8; 16] = rand::random();
+        let jti: String = jti.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        let header = json!({
+            "typ": "dpop+jwt",
+            "alg": "ES256",
+            "jwk": self.jwk,
+        });
+        let mut claims = json!({
+            "jti": jti,
+            "htm": method.as_str(),
+            "htu": htu.as_str(),
+            "iat": issued_at,
+        });
+        if let Some(nonce) = nonce {
+            claims["nonce"] = nonce.into();
+        }
+        if let Some(access_token) = access_token {
+            let hash = digest(&SHA256, access_token.as_bytes());
+            claims["ath"] = URL_SAFE_NO_PAD.encode(hash.as_ref()).into();
+        }
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = self
+            .key_pair
+            .sign(&self.rng, signing_input.as_bytes())
+            .map_err(|_| anyhow!("Failed to sign DPoP proof"))?;
+
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        ))
+    }
+}
+
+/// Adds a DPoP proof to every request of `inner`
+///
+/// The last nonce of every server is sent along, when a server rejects a request because it requires a new nonce the request is retried once.
+pub(crate) struct DpopTransport {
+    inner: Arc<dyn HttpTransport>,
+    key: Arc<DpopKey>,
+    // The last nonce per origin
+    nonces: Mutex<HashMap<String, String>>,
+}
+
+impl DpopTransport {
+    pub(crate) fn new(inner: Arc<dyn HttpTransport>, key: Arc<DpopKey>) -> Self {
+        DpopTransport {
+            inner,
+            key,
+            nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn send(&self, mut request: Request, origin: &str) -> Result<(Response, bool)> {
+        let nonce = self.nonces.lock().unwrap().get(origin).cloned();
+        let access_token = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(DPOP_SCHEME))
+            .and_then(|value| value.strip_prefix(' '))
+            .map(str::to_string);
+
+        let proof = self.key.proof(
+            request.method(),
+            request.url(),
+            nonce.as_deref(),
+            access_token.as_deref(),
+        )?;
+        request.headers_mut().insert(DPOP, proof.parse()?);
+
+        let response = self.inner.execute(request).await?;
+
+        let new_nonce = response
+            .headers()
+            .get(DPOP_NONCE)
+            .and_then(|value| value.to_str().ok())
+            .filter(|new_nonce| Some(*new_nonce) != nonce.as_deref());
+        let nonce_changed = new_nonce.is_some();
+        if let Some(new_nonce) = new_nonce {
+            self.nonces
+                .lock()
+                .unwrap()
+                .insert(origin.to_string(), new_nonce.to_string());
+        }
+
+        Ok((response, nonce_changed))
+    }
+}
+
+impl HttpTransport for DpopTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            let origin = request.url().origin().ascii_serialization();
+            // Streamed bodies can't be cloned, those requests are not retried
+            let retry = request.try_clone();
+
+            let (response, nonce_changed) = self.send(request, &origin).await?;
+            match retry {
+                Some(retry) if nonce_changed && requires_nonce(&response) => {
+                    debug!("The server requires a new DPoP nonce, retrying");
+                    Ok(self.send(retry, &origin).await?.0)
+                }
+                _ => Ok(response),
+            }
+        })
+    }
+}
+
+// The token endpoint responds `400 Bad Request` and the endpoints `401 Unauthorized` with the `use_dpop_nonce` error
+fn requires_nonce(response: &Response) -> bool {
+    match response.status() {
+        StatusCode::BAD_REQUEST => true,
+        StatusCode::UNAUTHORIZED => response
+            .headers()
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains("use_dpop_nonce")),
+        _ => false,
+    }
+}
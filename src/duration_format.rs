@@ -0,0 +1,142 @@
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::convert::TryFrom;
+use std::fmt::{self, Formatter};
+use std::time::Duration;
+
+/// Deserialize a duration from a string like `"30s"`, `"1m30s"` or `"500ms"`, or from a number of seconds
+///
+/// The `{ "secs": 30, "nanos": 0 }` objects of serde's own format are accepted as well.
+/// Use it with `#[serde(with = "crate::duration_format")]`.
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+/// The same format for an optional duration, `null` is `None`
+pub(crate) mod option {
+    use super::DurationVisitor;
+    use serde::de::{self, Visitor};
+    use serde::Deserializer;
+    use std::fmt::{self, Formatter};
+    use std::time::Duration;
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<Duration>;
+
+        fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            DurationVisitor.expecting(f)?;
+            write!(f, " or null")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+struct DurationVisitor;
+
+impl<'de> Visitor<'de> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a duration like \"30s\", \"1m30s\" or \"500ms\", or a number of seconds"
+        )
+    }
+
+    fn visit_u64<E: de::Error>(self, secs: u64) -> Result<Duration, E> {
+        Ok(Duration::from_secs(secs))
+    }
+
+    fn visit_i64<E: de::Error>(self, secs: i64) -> Result<Duration, E> {
+        u64::try_from(secs)
+            .map(Duration::from_secs)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(secs), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, secs: f64) -> Result<Duration, E> {
+        Duration::try_from_secs_f64(secs)
+            .map_err(|_| E::invalid_value(de::Unexpected::Float(secs), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Duration, E> {
+        parse(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Duration, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        Duration::deserialize(de::value::MapAccessDeserializer::new(map))
+    }
+}
+
+/// Parse a sequence of numbers with a unit (`ms`, `s`, `m`, `h` or `d`), e.g. `1h30m`, a number without a unit is seconds
+fn parse(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+
+    let mut rest = value;
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, after_number) = rest.split_at(number_len);
+        let unit_len = after_number
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(after_number.len());
+        let (unit, after_unit) = after_number.split_at(unit_len);
+
+        let unit_millis: u64 = match unit.trim() {
+            "ms" => 1,
+            "s" => 1_000,
+            "m" => 60_000,
+            "h" => 3_600_000,
+            "d" => 86_400_000,
+            _ => return None,
+        };
+        // Whole numbers are exact, fractions go through floating point
+        let duration = match number.parse::<u64>() {
+            Ok(number) => Duration::from_millis(number.checked_mul(unit_millis)?),
+            Err(_) => {
+                let number = number.parse::<f64>().ok()?;
+                Duration::try_from_secs_f64(number * unit_millis as f64 / 1000.0).ok()?
+            }
+        };
+        total = total.checked_add(duration)?;
+        rest = after_unit.trim_start();
+    }
+
+    Some(total)
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The request envelope of a GraphQL query
+#[derive(Serialize)]
+pub(crate) struct GraphQlRequest<'a, V> {
+    pub(crate) query: &'a str,
+    pub(crate) variables: &'a V,
+}
+
+// The response envelope, `data` is kept as json until we know there are no errors
+#[derive(Deserialize)]
+pub(crate) struct GraphQlResponse {
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+impl GraphQlResponse {
+    /// Get the `data` of the response, the `errors` are returned as a [GraphQlErrors](GraphQlErrors)
+    pub(crate) fn into_data<R>(self) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        if !self.errors.is_empty() {
+            return Err(GraphQlErrors {
+                errors: self.errors,
+                data: self.data,
+            }
+            .into());
+        }
+
+        serde_json::from_value(self.data.context("The GraphQL response contains no data")?)
+            .context("Failed to deserialize the GraphQL data")
+    }
+}
+
+/// An entry of the `errors` of a GraphQL response
+#[derive(Clone, Debug, Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+    #[serde(default)]
+    pub locations: Vec<GraphQlErrorLocation>,
+    /// The path to the field which failed, made up of field names and list indices
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>,
+    /// Server specific details, e.g. an error code
+    #[serde(default)]
+    pub extensions: Option<serde_json::Value>,
+}
+
+/// The location in the query a [GraphQlError](GraphQlError) refers to
+#[derive(Clone, Debug, Deserialize)]
+pub struct GraphQlErrorLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The error returned by [graphql](crate::AuthorizedClient::graphql) when the response contains errors
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the errors and the partial data.
+#[derive(Clone, Debug)]
+pub struct GraphQlErrors {
+    pub errors: Vec<GraphQlError>,
+    /// The data of the fields which didn't fail, if any
+    pub data: Option<serde_json::Value>,
+}
+
+impl Display for GraphQlErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "GraphQL errors: ")?;
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for GraphQlErrors {}
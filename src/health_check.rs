@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+/// The result of [health_check](crate::AuthorizedClient::health_check)
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    /// Getting a new bearer token from the auth server
+    pub token_endpoint: HealthCheck,
+    /// Calling the [health_check_url](crate::Settings::health_check_url), `None` when it's not configured
+    pub endpoint: Option<HealthCheck>,
+}
+
+impl HealthReport {
+    /// Check if every check of the report succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.token_endpoint.is_healthy()
+            && self.endpoint.as_ref().is_none_or(HealthCheck::is_healthy)
+    }
+}
+
+/// The result of a single check of a [HealthReport](HealthReport)
+#[derive(Clone, Debug)]
+pub struct HealthCheck {
+    /// How long the check took
+    pub duration: Duration,
+    /// Why the check failed, `None` when it succeeded
+    pub error: Option<String>,
+}
+
+impl HealthCheck {
+    pub(crate) fn new<T>(duration: Duration, result: &anyhow::Result<T>) -> Self {
+        HealthCheck {
+            duration,
+            error: result.as_ref().err().map(|error| format!("{:#}", error)),
+        }
+    }
+
+    /// Check if the check succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,98 @@
+use crate::circuit_breaker::CircuitBreaker;
+use crate::clock::Clock;
+use crate::interceptor::BoxFuture;
+use crate::network_error::NetworkErrorKind;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rate_limiter::{Priority, RateLimiter};
+use crate::transport::HttpTransport;
+use anyhow::Result;
+use futures_util::future::{select, Either};
+use log::debug;
+use reqwest::{Method, Request, Response};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// The limits of the client, the hedged request is subject to them like the request it duplicates
+pub(crate) struct HedgeLimits<'a> {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) rate_limiter: Option<&'a RateLimiter>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) priority: Priority,
+    pub(crate) circuit_breaker: Option<&'a CircuitBreaker>,
+}
+
+/// Send `request`, when there's no response within `hedge_after` send it again and use the response which arrives first
+///
+/// Only reads are hedged, sending them twice is safe. The request which loses the race is cancelled.
+/// When one of the requests fails the other one is awaited, it might still succeed.
+/// The hedged request waits for the rate limiter and isn't sent while the circuit is open.
+/// Returns the response and whether a hedged request was sent.
+pub(crate) async fn execute_hedged(
+    transport: &dyn HttpTransport,
+    request: Request,
+    hedge_after: Duration,
+    clock: &dyn Clock,
+    limits: HedgeLimits<'_>,
+) -> (Result<Response>, bool) {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return (transport.execute(request).await, false);
+    }
+    // A streamed body can't be sent twice
+    let hedge = match request.try_clone() {
+        Some(hedge) => hedge,
+        None => return (transport.execute(request).await, false),
+    };
+
+    let first = transport.execute(request);
+    let first = match select(first, clock.sleep(hedge_after)).await {
+        Either::Left((response, _)) => return (response, false),
+        Either::Right(((), first)) => first,
+    };
+
+    let permit = match limits.circuit_breaker.map(CircuitBreaker::try_acquire) {
+        Some(Err(_)) => {
+            debug!("The circuit is open, not sending a hedged request");
+            return (first.await, false);
+        }
+        permit => permit.and_then(Result::ok),
+    };
+    let sent = AtomicBool::new(false);
+    let hedge: BoxFuture<'_, Result<Response>> = Box::pin(async {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(rate_limiter) = limits.rate_limiter {
+            rate_limiter.acquire(limits.priority, clock).await;
+        }
+        debug!(
+            "No response within {}ms, sending a hedged request",
+            hedge_after.as_millis()
+        );
+        sent.store(true, Ordering::Relaxed);
+        let response = transport.execute(hedge).await;
+        if let Some(permit) = permit {
+            permit.record(is_failure(&response));
+        }
+        response
+    });
+
+    let response = match select(first, hedge).await {
+        Either::Left((Ok(response), _)) | Either::Right((Ok(response), _)) => Ok(response),
+        Either::Left((Err(error), other)) | Either::Right((Err(error), other)) => {
+            debug!(
+                "Hedged request failed, waiting for the other one: {:#}",
+                error
+            );
+            other.await
+        }
+    };
+    (response, sent.load(Ordering::Relaxed))
+}
+
+// Check if the hedged request failed because the endpoint is unavailable, like circuit_breaker::is_failure
+fn is_failure(response: &Result<Response>) -> bool {
+    match response {
+        Ok(response) => response.status().is_server_error(),
+        Err(error) => error
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|error| NetworkErrorKind::of(error) != NetworkErrorKind::Other),
+    }
+}
@@ -0,0 +1,269 @@
+use crate::cassette::{Cassette, CassetteTransport};
+use crate::dpop::{DpopKey, DpopTransport};
+use crate::settings::Settings;
+use crate::transport::HttpTransport;
+use anyhow::{Context, Result};
+use log::warn;
+use oauth2::reqwest::Error;
+use oauth2::{HttpRequest, HttpResponse};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Request};
+use std::sync::Arc;
+
+/// The http clients used by an `AuthorizedClient`
+pub(crate) struct HttpClients {
+    /// Used to build the requests to the endpoints
+    pub(crate) api: Client,
+    /// Used to call the endpoints
+    pub(crate) api_transport: Arc<dyn HttpTransport>,
+    /// Used to call the token endpoint
+    pub(crate) token: Arc<dyn HttpTransport>,
+}
+
+impl HttpClients {
+    pub(crate) fn new(settings: &Settings) -> Result<Self> {
+        if settings.danger_accept_invalid_certs {
+            warn!("Invalid TLS certificates are accepted, only use this for testing");
+        }
+
+        let mut api_builder =
+            api_client_builder(settings)?.redirect(settings.redirect_policy.to_reqwest_policy());
+        if let Some(request_timeout) = settings.request_timeout {
+            api_builder = api_builder.timeout(request_timeout);
+        }
+        let api = api_builder
+            .build()
+            .context("Failed to create http client")?;
+
+        // Following redirects on the token endpoint opens the client up to SSRF vulnerabilities
+        let mut token_builder = client_builder(settings)?.redirect(Policy::none());
+        if let Some(token_exchange_timeout) = settings.token_exchange_timeout {
+            token_builder = token_builder.timeout(token_exchange_timeout);
+        }
+        let token = token_builder
+            .build()
+            .context("Failed to create token http client")?;
+
+        let mut api_transport: Arc<dyn HttpTransport> = Arc::new(api.clone());
+        let mut token: Arc<dyn HttpTransport> = Arc::new(token);
+
+        // Both transports share the cassette, so it contains the interactions in the order they happened
+        if let Some(cassette) = &settings.cassette {
+            let cassette = Cassette::load(cassette)?;
+            api_transport = Arc::new(CassetteTransport {
+                inner: api_transport,
+                cassette: cassette.clone(),
+            });
+            token = Arc::new(CassetteTransport {
+                inner: token,
+                cassette,
+            });
+        }
+
+        Ok(HttpClients {
+            api,
+            api_transport,
+            token,
+        })
+    }
+
+    /// Use a pre-built client for every request
+    pub(crate) fn from_client(client: Client) -> Self {
+        HttpClients {
+            api: client.clone(),
+            api_transport: Arc::new(client.clone()),
+            token: Arc::new(client),
+        }
+    }
+
+    /// Sign a DPoP proof for every request when it's enabled in the settings, the token and endpoint requests share the key
+    pub(crate) fn with_dpop(self, settings: &Settings) -> Result<Self> {
+        if !settings.dpop {
+            return Ok(self);
+        }
+
+        let key = Arc::new(DpopKey::generate()?);
+        Ok(HttpClients {
+            api: self.api,
+            api_transport: Arc::new(DpopTransport::new(self.api_transport, key.clone())),
+            token: Arc::new(DpopTransport::new(self.token, key)),
+        })
+    }
+
+    /// Send every request using `transport`, the client is only used to build the requests
+    pub(crate) fn from_transport(transport: Arc<dyn HttpTransport>) -> Self {
+        HttpClients {
+            api: Client::new(),
+            api_transport: transport.clone(),
+            token: transport,
+        }
+    }
+}
+
+// Apply the TLS, proxy and connect timeout settings to a client builder
+// This is a macro because the async and blocking client builders don't share a trait
+macro_rules! configure_client_builder {
+    ($builder:expr, $settings:expr) => {{
+        let settings: &crate::settings::Settings = $settings;
+        let mut builder = $builder;
+
+        if let Some(ca_bundle) = &settings.ca_bundle {
+            let certificates = reqwest::Certificate::from_pem_bundle(ca_bundle.as_bytes())
+                .context("Invalid settings: ca_bundle is not a valid PEM bundle")?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if settings.danger_accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        if let Some(min_tls_version) = settings.min_tls_version {
+            builder = builder.min_tls_version(match min_tls_version {
+                crate::settings::TlsVersion::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+                crate::settings::TlsVersion::Tls1_3 => reqwest::tls::Version::TLS_1_3,
+            });
+        }
+
+        if let Some(proxy_settings) = &settings.proxy {
+            let mut proxy = reqwest::Proxy::all(&proxy_settings.url).with_context(|| {
+                format!(
+                    "Invalid settings: proxy url '{}' is not a valid url",
+                    proxy_settings.url
+                )
+            })?;
+            if let Some(username) = &proxy_settings.username {
+                proxy = proxy.basic_auth(
+                    username,
+                    proxy_settings
+                        .password
+                        .as_ref()
+                        .map(crate::secret::SecretString::expose_secret)
+                        .unwrap_or_default(),
+                );
+            }
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(
+                &proxy_settings.no_proxy.join(","),
+            ));
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(connect_timeout) = settings.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        // Each identity format is only supported by one of the TLS backends
+        match &settings.client_identity {
+            Some(crate::settings::ClientIdentity::Pem { pem }) => {
+                let identity = reqwest::Identity::from_pem(pem.expose_secret().as_bytes())
+                    .context("Invalid settings: client_identity is not a valid PEM identity")?;
+                builder = builder.use_rustls_tls().identity(identity);
+            }
+            Some(crate::settings::ClientIdentity::Pkcs12 { der, password }) => {
+                let identity = reqwest::Identity::from_pkcs12_der(der, password.expose_secret())
+                    .context("Invalid settings: client_identity is not a valid PKCS#12 identity")?;
+                builder = builder.use_native_tls().identity(identity);
+            }
+            None => {}
+        }
+
+        builder
+    }};
+}
+#[cfg(feature = "blocking")]
+pub(crate) use configure_client_builder;
+
+// Apply the connection pool settings which are supported by both the async and blocking client builders
+macro_rules! configure_connection_pool {
+    ($builder:expr, $connection_pool:expr) => {{
+        let connection_pool: &crate::settings::ConnectionPoolSettings = $connection_pool;
+        let mut builder = $builder;
+
+        if let Some(max_idle_per_host) = connection_pool.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle_per_host);
+        }
+        if let Some(idle_timeout) = connection_pool.idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        if connection_pool.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        builder
+    }};
+}
+#[cfg(feature = "blocking")]
+pub(crate) use configure_connection_pool;
+
+// Apply the TLS settings
+fn client_builder(settings: &Settings) -> Result<ClientBuilder> {
+    Ok(configure_client_builder!(Client::builder(), settings))
+}
+
+// Apply the TLS and connection pool settings
+fn api_client_builder(settings: &Settings) -> Result<ClientBuilder> {
+    let connection_pool = &settings.connection_pool;
+    let mut builder = configure_connection_pool!(client_builder(settings)?, connection_pool);
+    if let Some(http2_keep_alive_interval) = connection_pool.http2_keep_alive_interval {
+        builder = builder.http2_keep_alive_interval(http2_keep_alive_interval);
+    }
+
+    Ok(builder)
+}
+
+/// Execute an oauth2 request using `transport`
+///
+/// This replaces `oauth2::reqwest::async_http_client` so the token requests use the same TLS settings as the other requests.
+pub(crate) async fn oauth_http_client(
+    transport: &dyn HttpTransport,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error<reqwest::Error>> {
+    let mut http_request = Request::new(request.method, request.url);
+    *http_request.headers_mut() = request.headers;
+    *http_request.body_mut() = Some(request.body.into());
+
+    let response = transport.execute(http_request).await.map_err(|error| {
+        match error.downcast::<reqwest::Error>() {
+            Ok(error) => Error::Reqwest(error),
+            Err(error) => Error::Other(format!("{:#}", error)),
+        }
+    })?;
+
+    let status_code = response.status();
+    let headers = response.headers().to_owned();
+    let body = response.bytes().await.map_err(Error::Reqwest)?;
+
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body: body.to_vec(),
+    })
+}
+
+/// Execute an oauth2 request using the blocking `client`
+#[cfg(feature = "blocking")]
+pub(crate) fn blocking_oauth_http_client(
+    client: &reqwest::blocking::Client,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error<reqwest::Error>> {
+    let mut request_builder = client
+        .request(request.method, request.url.as_str())
+        .body(request.body);
+    for (name, value) in &request.headers {
+        request_builder = request_builder.header(name.as_str(), value.as_bytes());
+    }
+    let request = request_builder.build().map_err(Error::Reqwest)?;
+
+    let response = client.execute(request).map_err(Error::Reqwest)?;
+
+    let status_code = response.status();
+    let headers = response.headers().to_owned();
+    let body = response.bytes().map_err(Error::Reqwest)?;
+
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body: body.to_vec(),
+    })
+}
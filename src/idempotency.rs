@@ -0,0 +1,43 @@
+use reqwest::Method;
+use serde::Deserialize;
+
+/// Attach an idempotency key to post and patch requests, see: [idempotency_keys](crate::Settings::idempotency_keys)
+///
+/// The key is a random UUID which stays the same for every attempt of a request, this way the server can detect retried writes.
+/// Requests which already have the header keep their own key.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyKeySettings {
+    /// The header the key is sent in
+    pub header_name: String,
+}
+
+impl Default for IdempotencyKeySettings {
+    fn default() -> Self {
+        IdempotencyKeySettings {
+            header_name: "Idempotency-Key".to_string(),
+        }
+    }
+}
+
+/// Only the methods which are not idempotent by themselves need a key
+pub(crate) fn requires_idempotency_key(method: &Method) -> bool {
+    method == Method::POST || method == Method::PATCH
+}
+
+/// A random (version 4) UUID
+pub(crate) fn new_idempotency_key() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use reqwest::{Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+
+/// A boxed future, used to allow async functions in traits
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Hooks which are called for every request made by an `AuthorizedClient`
+///
+/// Register an interceptor using [with_interceptor](crate::AuthorizedClient::with_interceptor).
+/// The hooks are called for every attempt, retries included, in the order the interceptors were registered.
+///
+/// ```
+/// use authorized_client::{BoxFuture, Interceptor};
+/// use reqwest::Request;
+///
+/// struct TenantHeader;
+///
+/// impl Interceptor for TenantHeader {
+///     fn on_request<'a>(&'a self, request: &'a mut Request) -> BoxFuture<'a, anyhow::Result<()>> {
+///         Box::pin(async move {
+///             request.headers_mut().insert("X-Tenant", "my-tenant".parse()?);
+///             Ok(())
+///         })
+///     }
+/// }
+/// ```
+pub trait Interceptor: Send + Sync {
+    /// Called right before the request is sent, the bearer token has already been added
+    fn on_request<'a>(&'a self, _request: &'a mut Request) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Called when a response has been received, before the status code is checked
+    fn on_response<'a>(&'a self, _response: &'a Response) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -0,0 +1,71 @@
+use crate::deserialize_error::body_snippet;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A response body which is read without declaring a type, see: [get_untyped](crate::AuthorizedClient::get_untyped)
+///
+/// Use this to explore an endpoint, declare a type and use [get](crate::AuthorizedClient::get) once the shape of the response is known.
+///
+/// ```
+/// use authorized_client::JsonBody;
+/// use serde_json::json;
+///
+/// let body = JsonBody::Json(json!({ "items": [{ "name": "first" }] }));
+/// assert_eq!(body.pointer("/items/0/name"), Some(&json!("first")));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonBody {
+    /// The response has no body, e.g. `204 No Content`
+    Empty,
+    /// The body is valid json
+    Json(Value),
+    /// The body is not valid json, e.g. an html error page, it's decoded as utf-8 (lossy)
+    Text(String),
+}
+
+impl JsonBody {
+    pub(crate) fn from_bytes(body: &[u8]) -> Self {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return JsonBody::Empty;
+        }
+
+        match serde_json::from_slice(body) {
+            Ok(value) => JsonBody::Json(value),
+            Err(_) => JsonBody::Text(String::from_utf8_lossy(body).into_owned()),
+        }
+    }
+
+    /// The json value, `None` when the body is empty or not json
+    pub fn as_json(&self) -> Option<&Value> {
+        match self {
+            JsonBody::Json(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Look up a value using a [JSON pointer](https://tools.ietf.org/html/rfc6901), e.g. `/items/0/name`
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        self.as_json()?.pointer(pointer)
+    }
+
+    /// The json value, an empty body is `null` and a body which is not json is an error
+    pub fn into_json(self) -> Result<Value> {
+        match self {
+            JsonBody::Empty => Ok(Value::Null),
+            JsonBody::Json(value) => Ok(value),
+            JsonBody::Text(text) => bail!(
+                "The response body is not json: {}",
+                body_snippet(text.as_bytes())
+            ),
+        }
+    }
+
+    /// Deserialize the body into a type after all, e.g. once the interesting part of the response is known
+    pub fn deserialize<R>(self) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        Ok(serde_json::from_value(self.into_json()?)?)
+    }
+}
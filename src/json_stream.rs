@@ -0,0 +1,276 @@
+use anyhow::{bail, Result};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    // A json array, every element is an item
+    Array,
+    // Newline delimited json, every line is an item
+    Lines,
+}
+
+/// Splits a json body into its items while it's received, see: [get_json_stream](crate::AuthorizedClient::get_json_stream)
+///
+/// The items are not parsed, only the strings and nesting are tracked to find where an item ends.
+/// This way only the item which is being received is buffered, not the whole body.
+pub(crate) struct JsonStreamParser {
+    // Bytes which haven't been returned as an item yet, a chunk can end in the middle of an item
+    buffer: Vec<u8>,
+    // The start of the current item in the buffer, the bytes before it are dropped when the next chunk is fed
+    start: usize,
+    // The number of bytes of the buffer which have been scanned
+    scanned: usize,
+    // Detected from the first byte of the body when it's not known up front
+    format: Option<Format>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    array_closed: bool,
+}
+
+impl JsonStreamParser {
+    /// A parser for a json array or newline delimited json, depending on the first character of the body
+    pub(crate) fn new() -> Self {
+        Self::with_format(None)
+    }
+
+    /// A parser for newline delimited json, every line is an item even when it's an array
+    pub(crate) fn lines() -> Self {
+        Self::with_format(Some(Format::Lines))
+    }
+
+    fn with_format(format: Option<Format>) -> Self {
+        JsonStreamParser {
+            buffer: Vec::new(),
+            start: 0,
+            scanned: 0,
+            format,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            array_closed: false,
+        }
+    }
+
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.drain(..self.start);
+        self.scanned -= self.start;
+        self.start = 0;
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// The next complete item, `None` when more bytes are required
+    pub(crate) fn next_item(&mut self) -> Result<Option<Vec<u8>>> {
+        let format = match self.format {
+            Some(format) => format,
+            None => match self.detect_format() {
+                Some(format) => format,
+                None => return Ok(None),
+            },
+        };
+
+        match format {
+            Format::Array => self.next_element(),
+            Format::Lines => Ok(self.next_line()),
+        }
+    }
+
+    /// The last item after the whole body has been received
+    pub(crate) fn finish(&mut self) -> Result<Option<Vec<u8>>> {
+        if let Some(item) = self.next_item()? {
+            return Ok(Some(item));
+        }
+
+        let rest = &self.buffer[self.start..];
+        match self.format {
+            // An empty body has no items
+            None => Ok(None),
+            Some(Format::Array) if !self.array_closed => {
+                bail!("The json array ended before it was closed")
+            }
+            Some(Format::Array) => Ok(None),
+            Some(Format::Lines) if is_blank(rest) => Ok(None),
+            // The last line doesn't have to end with a newline
+            Some(Format::Lines) => {
+                let item = rest.to_vec();
+                self.start = self.buffer.len();
+                self.scanned = self.buffer.len();
+                Ok(Some(item))
+            }
+        }
+    }
+
+    fn detect_format(&mut self) -> Option<Format> {
+        let first = self.buffer[self.start..]
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())?;
+        let format = if self.buffer[self.start + first] == b'[' {
+            // Skip the opening bracket, the elements are the items
+            self.start += first + 1;
+            self.scanned = self.start;
+            Format::Array
+        } else {
+            Format::Lines
+        };
+
+        self.format = Some(format);
+        Some(format)
+    }
+
+    fn next_element(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.array_closed {
+            if !is_blank(&self.buffer[self.start..]) {
+                bail!("Unexpected data after the end of the json array");
+            }
+            return Ok(None);
+        }
+
+        while self.scanned < self.buffer.len() {
+            let byte = self.buffer[self.scanned];
+            self.scanned += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => self.depth += 1,
+                b']' | b'}' if self.depth > 0 => self.depth -= 1,
+                b',' | b']' if self.depth == 0 => {
+                    let element = &self.buffer[self.start..self.scanned - 1];
+                    let blank = is_blank(element);
+                    let element = element.to_vec();
+                    self.start = self.scanned;
+
+                    if byte == b']' {
+                        self.array_closed = true;
+                        // Allow an empty array, `[]`
+                        return Ok(if blank { None } else { Some(element) });
+                    }
+                    if blank {
+                        bail!("The json array contains an empty element");
+                    }
+                    return Ok(Some(element));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn next_line(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let end = self.buffer[self.scanned..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map(|position| self.scanned + position);
+            let end = match end {
+                Some(end) => end,
+                None => {
+                    self.scanned = self.buffer.len();
+                    return None;
+                }
+            };
+
+            let line = &self.buffer[self.start..end];
+            let blank = is_blank(line);
+            let line = line.to_vec();
+            self.start = end + 1;
+            self.scanned = end + 1;
+
+            // Skip empty lines, e.g. the keep-alives of a long running export
+            if !blank {
+                return Some(line);
+            }
+        }
+    }
+}
+
+fn is_blank(bytes: &[u8]) -> bool {
+    bytes.iter().all(u8::is_ascii_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feed the body in chunks of every size, the items have to be the same for every split
+    fn items(new_parser: fn() -> JsonStreamParser, body: &str) -> Result<Vec<String>> {
+        let mut results = Vec::new();
+        for chunk_size in 1..=body.len().max(1) {
+            let mut parser = new_parser();
+            let mut items = Vec::new();
+            for chunk in body.as_bytes().chunks(chunk_size) {
+                parser.feed(chunk);
+                while let Some(item) = parser.next_item()? {
+                    items.push(String::from_utf8(item).unwrap().trim().to_string());
+                }
+            }
+            while let Some(item) = parser.finish()? {
+                items.push(String::from_utf8(item).unwrap().trim().to_string());
+            }
+            results.push(items);
+        }
+
+        let first = results[0].clone();
+        assert!(results.iter().all(|items| *items == first), "{:?}", results);
+        Ok(first)
+    }
+
+    #[test]
+    fn splits_an_array_at_every_chunk_boundary() {
+        let body = r#" [ {"a": [1, 2]}, "x,]\"}", 3 , null ] "#;
+
+        assert_eq!(
+            items(JsonStreamParser::new, body).unwrap(),
+            vec![r#"{"a": [1, 2]}"#, r#""x,]\"}""#, "3", "null"]
+        );
+    }
+
+    #[test]
+    fn an_empty_array_or_body_has_no_items() {
+        assert!(items(JsonStreamParser::new, "[ ]").unwrap().is_empty());
+        assert!(items(JsonStreamParser::new, "").unwrap().is_empty());
+    }
+
+    #[test]
+    fn fails_on_an_unclosed_array() {
+        assert!(items(JsonStreamParser::new, r#"[{"a": 1}, 2"#).is_err());
+    }
+
+    #[test]
+    fn fails_on_an_empty_element() {
+        assert!(items(JsonStreamParser::new, "[1,,2]").is_err());
+    }
+
+    #[test]
+    fn fails_on_data_after_the_array() {
+        assert!(items(JsonStreamParser::new, "[1] 2").is_err());
+    }
+
+    #[test]
+    fn splits_newline_delimited_json_at_every_chunk_boundary() {
+        let body = "{\"a\": \"caf\u{e9}\"}\r\n\n  \n[1, 2]\n{\"b\": 2}";
+
+        assert_eq!(
+            items(JsonStreamParser::lines, body).unwrap(),
+            vec!["{\"a\": \"caf\u{e9}\"}", "[1, 2]", "{\"b\": 2}"]
+        );
+    }
+
+    #[test]
+    fn detects_newline_delimited_json_from_the_first_character() {
+        assert_eq!(
+            items(JsonStreamParser::new, "{\"a\": 1}\n{\"a\": 2}\n").unwrap(),
+            vec!["{\"a\": 1}", "{\"a\": 2}"]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The claims of a JWT access token, see: [token_claims](crate::AuthorizedClient::token_claims)
+///
+/// Use this to debug a `403 Forbidden` response, e.g. a missing scope or an unexpected audience.
+/// The signature of the token is not verified, only the server which accepts the token can do that.
+#[derive(Clone, Debug)]
+pub struct TokenClaims {
+    /// The time the token expires, the `exp` claim
+    pub expires_at: Option<SystemTime>,
+    /// The scopes of the token, the `scope` claim or the `scp` claim some auth servers use instead
+    pub scopes: Vec<String>,
+    /// The audiences of the token, the `aud` claim
+    pub audiences: Vec<String>,
+    /// All claims of the token, e.g. to inspect the `sub` or `iss` claim
+    pub claims: Value,
+}
+
+impl TokenClaims {
+    /// Decode the claims of a JWT, fails when the token is not a JWT
+    ///
+    /// ```
+    /// use authorized_client::TokenClaims;
+    ///
+    /// // {"alg":"none"}.{"scope":"read write","aud":"api","exp":1700000000}
+    /// let token = "eyJhbGciOiJub25lIn0.eyJzY29wZSI6InJlYWQgd3JpdGUiLCJhdWQiOiJhcGkiLCJleHAiOjE3MDAwMDAwMDB9.";
+    /// let claims = TokenClaims::decode(token)?;
+    /// assert_eq!(claims.scopes, vec!["read", "write"]);
+    /// assert_eq!(claims.audiences, vec!["api"]);
+    ///# Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn decode(access_token: &str) -> Result<Self> {
+        let claims = decode_claims(access_token)?;
+
+        let expires_at = claims
+            .get("exp")
+            .and_then(Value::as_u64)
+            .map(|exp| UNIX_EPOCH + Duration::from_secs(exp));
+        let scopes = match claims.get("scope").or_else(|| claims.get("scp")) {
+            Some(Value::String(scopes)) => scopes.split_whitespace().map(str::to_string).collect(),
+            Some(scopes) => strings(scopes),
+            None => Vec::new(),
+        };
+        let audiences = claims.get("aud").map(strings).unwrap_or_default();
+
+        Ok(TokenClaims {
+            expires_at,
+            scopes,
+            audiences,
+            claims,
+        })
+    }
+}
+
+/// Get the remaining lifetime of a JWT access token from its `exp` claim
+///
+/// The signature is not verified, the token is only inspected to find out when it expires.
+/// Returns `None` when the token isn't a JWT or doesn't have an `exp` claim.
+pub(crate) fn expires_in(access_token: &str, now: SystemTime) -> Option<Duration> {
+    let expires_at = TokenClaims::decode(access_token).ok()?.expires_at?;
+    Some(expires_at.duration_since(now).unwrap_or_default())
+}
+
+// A claim which is either a single string or an array of strings
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(value) => vec![value.clone()],
+        Value::Array(values) => values
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Decode the payload, the second part of `header.payload.signature`
+fn decode_claims(access_token: &str) -> Result<Value> {
+    let payload = access_token
+        .split('.')
+        .nth(1)
+        .context("The access token is not a JWT")?;
+    // Some issuers pad the base64 even though JWTs shouldn't be padded
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .context("The payload of the JWT is not valid base64")?;
+
+    serde_json::from_slice(&payload).context("The payload of the JWT is not valid json")
+}
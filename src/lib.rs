@@ -1,8 +1,8 @@
 //! # Authorized Client
-//! The goal of this library is to make extremely easy to use rest endpoints which are protected by oauth 2.0 client credentials authorization.
+//! The goal of this library is to make extremely easy to use rest endpoints which are protected by oauth 2.0 client credentials (or resource owner password) authorization.
 //! The client is based on the `Reqwest` and `Oauth2` library
 //!
-//! For now this library only supports endpoints which return `json` bodies.
+//! For now this library only supports endpoints which accept `json`, form, multipart or streamed bodies, responses can be read as `json`, plain text or raw bytes.
 //!
 //! ## Usage
 //! Add this library as a dependency to your project.
@@ -17,16 +17,18 @@
 //!# use serde::Deserialize;
 //!# #[derive(Deserialize)]
 //!# struct MyResponse {}
+//!# #[derive(serde::Serialize)]
+//!# struct MyRequest { name: String }
 //! use authorized_client::{AuthorizedClient, Settings};
 //! use url::Url;
 //!
 //! // Set up the client
-//! let settings = Settings {
-//!     client_id: "xxxxxxxxxx".to_string(),
-//!     client_secret: "xxxxxxxxxx".to_string(),
-//!     token_url: "https://authorization-server.com/token".to_string(),
-//!     scopes: vec![ "profile".to_string(), "email".to_string() ]
-//! };
+//! let settings = Settings::builder()
+//!     .client_id("xxxxxxxxxx")
+//!     .client_secret("xxxxxxxxxx")
+//!     .token_url("https://authorization-server.com/token")
+//!     .scopes(vec!["profile", "email"])
+//!     .build()?;
 //!
 //! // Create a new client, this immediately tries to connect to the auth server and get a bearer token.
 //! // If this fails your settings are probably wrong.
@@ -35,11 +37,130 @@
 //! // Call your desired endpoints
 //! let repsonse: MyResponse = client.get(Url::parse("https://protected-endpoint.com/info")?).await?;
 //!
+//! // Post a json body, the body is serialized with serde_json
+//! let body = MyRequest { name: "example".to_string() };
+//! let repsonse: MyResponse = client.post(Url::parse("https://protected-endpoint.com/items")?, &body).await?;
+//!
 //!# Ok(())
 //!# }
 //! ```
+mod allowed_hosts;
 mod authorized_client;
+mod authorized_client_builder;
+mod authorized_request_builder;
+#[cfg(not(target_arch = "wasm32"))]
+mod background_refresh;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod cassette;
+mod circuit_breaker;
+mod client_assertion;
+mod client_pool;
+mod clock;
+mod conditional;
+mod content_type;
+mod credentials_cell;
+mod deserialize_error;
+#[cfg(not(target_arch = "wasm32"))]
+mod device_code_flow;
+mod discovery;
+#[cfg(not(target_arch = "wasm32"))]
+mod download;
+mod dpop;
+mod duration_format;
+mod graphql;
+mod health_check;
+mod hedging;
+mod http_client;
+mod idempotency;
+mod interceptor;
+mod json_body;
+mod json_stream;
+#[cfg(feature = "jwt")]
+mod jwt;
+mod metrics;
+#[cfg(feature = "mock")]
+mod mock_transport;
+mod multipart_form;
+mod network_error;
+mod pagination;
+mod rate_limiter;
+mod redirect_policy;
+mod request_signer;
+mod response_cache;
+mod response_meta;
+mod response_size;
+mod retry_policy;
+mod secret;
 mod settings;
+mod single_flight;
+mod sse;
+mod status_error;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod token_exchange;
+mod token_introspection;
+mod token_observer;
+mod token_provider;
+mod token_store;
+mod trace_context;
+mod transport;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod wire_log;
+mod www_authenticate;
 
-pub use crate::authorized_client::{AuthorizedClient, RequestBuilder};
-pub use crate::settings::Settings;
+pub use crate::allowed_hosts::HostNotAllowedError;
+pub use crate::authorized_client::{optional_json, AuthorizedClient, RequestBuilder};
+pub use crate::authorized_client_builder::AuthorizedClientBuilder;
+pub use crate::authorized_request_builder::AuthorizedRequestBuilder;
+pub use crate::cassette::{CassetteMode, CassetteSettings};
+pub use crate::circuit_breaker::{CircuitBreakerSettings, CircuitOpenError};
+pub use crate::client_assertion::JwtAlgorithm;
+pub use crate::client_pool::AuthorizedClientPool;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::clock::TokioClock;
+pub use crate::clock::{Clock, ManualClock, SystemClock};
+pub use crate::conditional::Conditional;
+pub use crate::content_type::UnexpectedContentTypeError;
+pub use crate::deserialize_error::DeserializeError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::device_code_flow::{DeviceCodeFlow, DeviceUserCode};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::download::{DownloadProgress, IncompleteDownloadError};
+pub use crate::graphql::{GraphQlError, GraphQlErrorLocation, GraphQlErrors};
+pub use crate::health_check::{HealthCheck, HealthReport};
+pub use crate::idempotency::IdempotencyKeySettings;
+pub use crate::interceptor::{BoxFuture, Interceptor};
+pub use crate::json_body::JsonBody;
+#[cfg(feature = "jwt")]
+pub use crate::jwt::TokenClaims;
+pub use crate::metrics::{Metrics, RequestMetrics, TokenRefreshMetrics};
+#[cfg(feature = "mock")]
+pub use crate::mock_transport::{CapturedRequest, MockResponse, MockTransport};
+pub use crate::multipart_form::MultipartForm;
+pub use crate::network_error::{NetworkError, NetworkErrorKind};
+pub use crate::rate_limiter::{Priority, RateLimit};
+pub use crate::redirect_policy::{RedirectPolicy, DEFAULT_MAX_REDIRECTS};
+pub use crate::request_signer::RequestSigner;
+pub use crate::response_cache::ResponseCacheSettings;
+pub use crate::response_meta::ResponseMeta;
+pub use crate::response_size::ResponseTooLargeError;
+pub use crate::retry_policy::{Backoff, RetryPolicy};
+pub use crate::secret::SecretString;
+pub use crate::settings::{
+    AuthType, ClientAuthMethod, ClientIdentity, ConnectionPoolSettings, GrantType, ProxySettings,
+    Settings, SettingsBuilder, TlsVersion, DEFAULT_REFRESH_COOLDOWN, DEFAULT_REFRESH_LEEWAY,
+};
+pub use crate::sse::Event;
+pub use crate::status_error::{ApiError, StatusError, UnauthorizedError};
+pub use crate::token_introspection::TokenIntrospection;
+pub use crate::token_observer::{TokenObserver, TokenRefreshEvent};
+pub use crate::token_provider::{OAuthTokenProvider, Token, TokenProvider};
+pub use crate::token_store::{FileTokenStore, MemoryTokenStore, StoredToken, TokenStore};
+pub use crate::trace_context::{TraceContext, TraceContextPropagator};
+pub use crate::transport::HttpTransport;
+#[cfg(feature = "websocket")]
+pub use crate::websocket::WebSocketAuth;
+pub use crate::wire_log::WireLogSettings;
+pub use crate::www_authenticate::BearerChallenge;
@@ -0,0 +1,92 @@
+use crate::token_observer::{TokenObserver, TokenRefreshEvent};
+use reqwest::{Method, StatusCode};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use url::Url;
+
+/// Callbacks to collect metrics about the requests made by an `AuthorizedClient`
+///
+/// Register the metrics using [with_metrics](crate::AuthorizedClient::with_metrics).
+/// The callbacks are called synchronously, keep them fast, e.g. by only updating counters and histograms.
+///
+/// ```
+/// use authorized_client::{Metrics, RequestMetrics};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// #[derive(Default)]
+/// struct RequestCounter {
+///     failed: AtomicU64,
+/// }
+///
+/// impl Metrics for RequestCounter {
+///     fn on_request(&self, request: &RequestMetrics) {
+///         if !request.status.is_some_and(|status| status.is_success()) {
+///             self.failed.fetch_add(1, Ordering::Relaxed);
+///         }
+///     }
+/// }
+/// ```
+pub trait Metrics: Send + Sync {
+    /// Called when a request finished, once for all attempts combined
+    fn on_request(&self, _request: &RequestMetrics) {}
+
+    /// Called when a bearer token has been requested from the auth server
+    fn on_token_refresh(&self, _refresh: &TokenRefreshMetrics) {}
+}
+
+/// The outcome of a request
+#[derive(Clone, Debug)]
+pub struct RequestMetrics {
+    pub method: Method,
+    pub url: Url,
+    /// The status code of the last response, `None` when no response was received
+    pub status: Option<StatusCode>,
+    /// The duration of all attempts combined, including the time spent waiting between them
+    pub duration: Duration,
+    /// The number of retries, both after a `401 Unauthorized` and according to the retry policy
+    pub retries: u32,
+    /// A hedged request was sent because the response was slow, see: [hedge_after](crate::Settings::hedge_after)
+    pub hedged: bool,
+}
+
+/// The outcome of a bearer token request
+#[derive(Clone, Debug)]
+pub struct TokenRefreshMetrics {
+    pub duration: Duration,
+    pub success: bool,
+}
+
+/// The registered metrics and token observers, shared by the clones of an `AuthorizedClient` and its background refresh
+#[derive(Clone, Default)]
+pub(crate) struct MetricsRegistry {
+    metrics: Arc<RwLock<Vec<Arc<dyn Metrics>>>>,
+    token_observers: Arc<RwLock<Vec<Arc<dyn TokenObserver>>>>,
+}
+
+impl MetricsRegistry {
+    pub(crate) fn add(&self, metrics: Arc<dyn Metrics>) {
+        self.metrics.write().unwrap().push(metrics);
+    }
+
+    pub(crate) fn add_token_observer(&self, token_observer: Arc<dyn TokenObserver>) {
+        self.token_observers.write().unwrap().push(token_observer);
+    }
+
+    pub(crate) fn on_request(&self, request: &RequestMetrics) {
+        for metrics in self.metrics.read().unwrap().iter() {
+            metrics.on_request(request);
+        }
+    }
+
+    pub(crate) fn on_token_refresh(&self, refresh: &TokenRefreshMetrics) {
+        for metrics in self.metrics.read().unwrap().iter() {
+            metrics.on_token_refresh(refresh);
+        }
+    }
+
+    pub(crate) fn on_new_token(&self, event: &TokenRefreshEvent) {
+        for token_observer in self.token_observers.read().unwrap().iter() {
+            token_observer.on_token_refresh(event);
+        }
+    }
+}
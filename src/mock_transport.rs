@@ -0,0 +1,192 @@
+use crate::interceptor::BoxFuture;
+use crate::transport::HttpTransport;
+use anyhow::{Context, Result};
+use oauth2::http;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, Request, Response, ResponseBuilderExt, StatusCode};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+/// A transport which returns queued responses instead of sending the requests, to test code which uses an `AuthorizedClient`
+///
+/// The responses are queued per url and returned in order, the query string is not part of the match.
+/// The clones of a `MockTransport` share the queued responses and captured requests.
+///
+/// ```
+///# async fn doc_test() -> anyhow::Result<()> {
+/// use authorized_client::{AuthorizedClient, MockResponse, MockTransport, Settings};
+/// use reqwest::StatusCode;
+/// use std::time::Duration;
+///
+/// let transport = MockTransport::new();
+/// transport.push_token("https://authorization-server.com/token", "my-token", Duration::from_secs(3600));
+/// transport.push_response(
+///     "https://protected-endpoint.com/info",
+///     MockResponse::json(StatusCode::OK, &serde_json::json!({ "name": "example" }))?,
+/// );
+///
+/// let settings = Settings::builder()
+///     .client_id("xxxxxxxxxx")
+///     .client_secret("xxxxxxxxxx")
+///     .token_url("https://authorization-server.com/token")
+///     .build()?;
+/// let client = AuthorizedClient::connect_with_transport(settings, transport.clone()).await?;
+/// let info: serde_json::Value = client.get("https://protected-endpoint.com/info".parse()?).await?;
+///
+/// let requests = transport.requests();
+/// assert_eq!(requests[1].headers["Authorization"], "Bearer my-token");
+///# Ok(())
+///# }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: HashMap<String, VecDeque<MockResponse>>,
+    requests: Vec<CapturedRequest>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    /// Queue a response for the next request to `url`
+    pub fn push_response(&self, url: &str, response: MockResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .responses
+            .entry(response_key(url))
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Queue a successful token response for the next request to the token endpoint `token_url`
+    pub fn push_token(&self, token_url: &str, access_token: &str, expires_in: Duration) {
+        let body = serde_json::json!({
+            "access_token": access_token,
+            "token_type": "bearer",
+            "expires_in": expires_in.as_secs(),
+        });
+        self.push_response(
+            token_url,
+            MockResponse::new(StatusCode::OK)
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .body(body.to_string()),
+        );
+    }
+
+    /// The requests which were sent, in order, including the requests to the token endpoint
+    pub fn requests(&self) -> Vec<CapturedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(CapturedRequest {
+                method: request.method().clone(),
+                url: request.url().clone(),
+                headers: request.headers().clone(),
+                body: request
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .map(<[u8]>::to_vec),
+            });
+
+            let response = state
+                .responses
+                .get_mut(&response_key(request.url().as_str()))
+                .and_then(VecDeque::pop_front)
+                .with_context(|| {
+                    format!(
+                        "MockTransport has no response queued for {} {}",
+                        request.method(),
+                        request.url()
+                    )
+                })?;
+
+            let mut builder = http::Response::builder()
+                .status(response.status)
+                .url(request.url().clone());
+            if let Some(headers) = builder.headers_mut() {
+                *headers = response.headers;
+            }
+            Ok(builder
+                .body(response.body)
+                .context("Failed to build the mock response")?
+                .into())
+        })
+    }
+}
+
+// The responses are matched on the url without the query string
+fn response_key(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            url.set_query(None);
+            url.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// A canned response returned by a [MockTransport](MockTransport)
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// Create a response with an empty body
+    pub fn new(status: StatusCode) -> Self {
+        MockResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Create a response with `body` serialized as json
+    pub fn json<T>(status: StatusCode, body: &T) -> Result<Self>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(MockResponse::new(status)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(serde_json::to_vec(body).context("Failed to serialize the mock response")?))
+    }
+
+    /// Add a header to the response
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Set the body of the response
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// A request sent to a [MockTransport](MockTransport)
+#[derive(Clone, Debug)]
+pub struct CapturedRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// `None` when the request has no body or the body is streamed
+    pub body: Option<Vec<u8>>,
+}
@@ -0,0 +1,105 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// A `multipart/form-data` body
+///
+/// The parts are kept in memory so the body can be rebuilt when a request has to be retried.
+#[derive(Clone)]
+pub struct MultipartForm {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Clone)]
+struct Part {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    content: Bytes,
+}
+
+impl MultipartForm {
+    /// Create a new empty form
+    pub fn new() -> Self {
+        // RandomState is randomly seeded, which makes it a cheap source for a unique boundary
+        let random = RandomState::new().build_hasher().finish();
+
+        MultipartForm {
+            boundary: format!("authorized-client-{:016x}", random),
+            parts: Vec::new(),
+        }
+    }
+
+    /// Add a text field to the form
+    pub fn text(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.parts.push(Part {
+            name: name.into(),
+            file_name: None,
+            content_type: None,
+            content: Bytes::from(value.into()),
+        });
+        self
+    }
+
+    /// Add a file to the form
+    pub fn file(
+        mut self,
+        name: impl Into<String>,
+        file_name: impl Into<String>,
+        content_type: impl Into<String>,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        self.parts.push(Part {
+            name: name.into(),
+            file_name: Some(file_name.into()),
+            content_type: Some(content_type.into()),
+            content: content.into(),
+        });
+        self
+    }
+
+    /// The value of the `Content-Type` header for this form
+    pub(crate) fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Encode the form into a request body
+    pub(crate) fn to_bytes(&self) -> Bytes {
+        let mut body = BytesMut::new();
+
+        for part in &self.parts {
+            body.put_slice(format!("--{}\r\n", self.boundary).as_bytes());
+            body.put_slice(
+                format!(
+                    "Content-Disposition: form-data; name=\"{}\"",
+                    escape_quotes(&part.name)
+                )
+                .as_bytes(),
+            );
+            if let Some(file_name) = &part.file_name {
+                body.put_slice(format!("; filename=\"{}\"", escape_quotes(file_name)).as_bytes());
+            }
+            body.put_slice(b"\r\n");
+            if let Some(content_type) = &part.content_type {
+                body.put_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+            }
+            body.put_slice(b"\r\n");
+            body.put_slice(&part.content);
+            body.put_slice(b"\r\n");
+        }
+        body.put_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+
+        body.freeze()
+    }
+}
+
+impl Default for MultipartForm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn escape_quotes(value: &str) -> String {
+    value.replace('"', "%22")
+}
@@ -0,0 +1,121 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use url::Url;
+
+/// The error returned when a request failed before the server sent a response, after the retries of the [retry_policy](crate::Settings::retry_policy)
+///
+/// Use `anyhow::Error::downcast_ref` to find out why the request failed, e.g. to tell an unreachable server apart from a rejected bearer token.
+#[derive(Debug)]
+pub struct NetworkError {
+    pub kind: NetworkErrorKind,
+    /// The url of the request, `None` when the request couldn't be built
+    pub url: Option<Url>,
+    /// The number of attempts of the retry policy, including the first one
+    pub attempts: u32,
+    source: reqwest::Error,
+}
+
+/// The reason a request failed before the server sent a response, see: [NetworkError](NetworkError)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkErrorKind {
+    /// The host name couldn't be resolved
+    Dns,
+    /// The connection couldn't be established, e.g. it was refused or the TLS handshake failed
+    Connect,
+    /// The request or the connection timed out
+    Timeout,
+    /// The connection broke while the request was sent or the response was received, e.g. it was reset or closed by the server
+    ConnectionReset,
+    /// Any other failure, e.g. a redirect loop or an invalid request
+    Other,
+}
+
+impl NetworkError {
+    pub(crate) fn new(source: reqwest::Error, attempts: u32) -> Self {
+        NetworkError {
+            kind: NetworkErrorKind::of(&source),
+            url: source.url().cloned(),
+            attempts,
+            source,
+        }
+    }
+}
+
+impl NetworkErrorKind {
+    /// Classify a failed request
+    pub(crate) fn of(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return NetworkErrorKind::Timeout;
+        }
+        // The resolver error of hyper is private, only its message tells it apart from other connect errors
+        if is_dns_error(error) {
+            return NetworkErrorKind::Dns;
+        }
+        if error.is_connect() {
+            return NetworkErrorKind::Connect;
+        }
+        // reqwest reports every failure of an established connection as a request error
+        if error.is_request() || is_reset(error) {
+            return NetworkErrorKind::ConnectionReset;
+        }
+        NetworkErrorKind::Other
+    }
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.url {
+            Some(url) => write!(f, "The request to {} failed", url)?,
+            None => write!(f, "The request failed")?,
+        }
+        write!(
+            f,
+            " ({}, attempts = {}): {}",
+            self.kind, self.attempts, self.source
+        )
+    }
+}
+
+impl Error for NetworkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl Display for NetworkErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            NetworkErrorKind::Dns => "dns error",
+            NetworkErrorKind::Connect => "connect error",
+            NetworkErrorKind::Timeout => "timeout",
+            NetworkErrorKind::ConnectionReset => "connection reset",
+            NetworkErrorKind::Other => "network error",
+        };
+        f.write_str(kind)
+    }
+}
+
+fn causes(error: &reqwest::Error) -> impl Iterator<Item = &(dyn Error + 'static)> {
+    std::iter::successors(error.source(), |&cause| cause.source())
+}
+
+fn is_dns_error(error: &reqwest::Error) -> bool {
+    error.is_connect() && causes(error).any(|cause| cause.to_string().starts_with("dns error"))
+}
+
+fn is_reset(error: &reqwest::Error) -> bool {
+    causes(error).any(|cause| {
+        cause.downcast_ref::<io::Error>().is_some_and(|error| {
+            matches!(
+                error.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            )
+        }) || cause
+            .downcast_ref::<hyper::Error>()
+            .is_some_and(|error| error.is_incomplete_message() || error.is_closed())
+    })
+}
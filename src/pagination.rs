@@ -0,0 +1,81 @@
+use reqwest::header::{HeaderMap, LINK};
+use serde_json::Value;
+use url::Url;
+
+/// Get the `rel="next"` url from the `Link` headers ([RFC 8288](https://tools.ietf.org/html/rfc8288)), relative urls are resolved against `current`
+pub(crate) fn next_link(headers: &HeaderMap, current: &Url) -> Option<Url> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_links)
+        .find(|(_, rels)| {
+            rels.split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("next"))
+        })
+        .and_then(|(target, _)| current.join(&target).ok())
+}
+
+// Parse a `Link` header into (target, rel) pairs
+fn parse_links(value: &str) -> Vec<(String, String)> {
+    let mut links = Vec::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let target = rest[start + 1..end].trim().to_owned();
+
+        // The parameters run until the next link, targets can contain commas so look for the next '<'
+        rest = &rest[end + 1..];
+        let params_end = rest.find('<').unwrap_or(rest.len());
+        let rel = rest[..params_end]
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .map(|(_, rel)| {
+                rel.trim()
+                    .trim_end_matches(',')
+                    .trim()
+                    .trim_matches('"')
+                    .to_owned()
+            })
+            .unwrap_or_default();
+
+        links.push((target, rel));
+        rest = &rest[params_end..];
+    }
+
+    links
+}
+
+/// Build the url of the next page by setting `cursor_param` to the cursor found at `cursor_pointer` in `page`
+///
+/// Returns `None` when the cursor is missing, null or empty.
+pub(crate) fn next_cursor_url(
+    page: &Value,
+    url: &Url,
+    cursor_pointer: &str,
+    cursor_param: &str,
+) -> Option<Url> {
+    let cursor = match page.pointer(cursor_pointer)? {
+        Value::String(cursor) if !cursor.is_empty() => cursor.clone(),
+        Value::Number(cursor) => cursor.to_string(),
+        _ => return None,
+    };
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| name != cursor_param)
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+
+    let mut next = url.clone();
+    next.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(cursor_param, &cursor);
+    Some(next)
+}
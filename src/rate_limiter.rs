@@ -0,0 +1,271 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::clock::Clock;
+use serde::Deserialize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeSet;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+/// Limit the number of requests made by a client, see: [rate_limit](crate::Settings::rate_limit)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// The number of requests per second which are allowed on average
+    pub requests_per_second: f64,
+    /// The number of requests which can be made at once after the client has been idle
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        RateLimit {
+            requests_per_second,
+            burst,
+        }
+    }
+}
+
+/// The priority of a request when it waits for the [rate_limit](crate::Settings::rate_limit), see: [priority](crate::AuthorizedRequestBuilder::priority)
+///
+/// Requests with a higher priority get the next token first, e.g. to keep interactive calls fast while background traffic is throttled.
+/// Requests with the same priority are served in order. Without a rate limit the priority has no effect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    // The index of the waiting queue, high priority first
+    #[cfg(not(target_arch = "wasm32"))]
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+/// A token bucket, shared by all clones of an `AuthorizedClient`
+///
+/// It needs a timer which doesn't return immediately, so it's not available on wasm32, see: [Clock::sleep](crate::Clock::sleep)
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct RateLimiter {
+    rate_limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    next_ticket: u64,
+    // The tickets of the waiting requests for every priority, a ticket is removed when its request got a token or was cancelled
+    waiting: [BTreeSet<u64>; 3],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RateLimiter {
+    pub(crate) fn new(rate_limit: RateLimit) -> Self {
+        let tokens = rate_limit.burst as f64;
+        RateLimiter {
+            rate_limit,
+            bucket: Mutex::new(Bucket {
+                tokens,
+                last_refill: Instant::now(),
+                next_ticket: 0,
+                waiting: Default::default(),
+            }),
+        }
+    }
+
+    /// Wait until a request is allowed, the requests which are ahead of it get a token first
+    pub(crate) async fn acquire(&self, priority: Priority, clock: &dyn Clock) {
+        let index = priority.index();
+        let mut waiter: Option<Waiter> = None;
+        loop {
+            let delay = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refilled =
+                    (now - bucket.last_refill).as_secs_f64() * self.rate_limit.requests_per_second;
+                bucket.tokens = (bucket.tokens + refilled).min(self.rate_limit.burst as f64);
+                bucket.last_refill = now;
+
+                let ticket = match &waiter {
+                    Some(waiter) => waiter.ticket,
+                    None => bucket.next_ticket,
+                };
+                // The tokens for the requests ahead are kept for them
+                let ahead = bucket.waiting[..index]
+                    .iter()
+                    .map(BTreeSet::len)
+                    .sum::<usize>()
+                    + bucket.waiting[index].range(..ticket).count();
+                let missing = ahead as f64 + 1.0 - bucket.tokens;
+                if missing <= 0.0 {
+                    bucket.tokens -= 1.0;
+                    None
+                } else {
+                    if waiter.is_none() {
+                        bucket.next_ticket += 1;
+                        bucket.waiting[index].insert(ticket);
+                        waiter = Some(Waiter {
+                            rate_limiter: self,
+                            index,
+                            ticket,
+                        });
+                    }
+                    Some(Duration::from_secs_f64(
+                        missing / self.rate_limit.requests_per_second,
+                    ))
+                }
+            };
+
+            match delay {
+                Some(delay) => clock.sleep(delay).await,
+                // Dropping the waiter removes its ticket
+                None => return,
+            }
+        }
+    }
+}
+
+// A request which is waiting for a token, its ticket is removed when it's dropped
+#[cfg(not(target_arch = "wasm32"))]
+struct Waiter<'a> {
+    rate_limiter: &'a RateLimiter,
+    index: usize,
+    ticket: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.rate_limiter.bucket.lock().unwrap().waiting[self.index].remove(&self.ticket);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::interceptor::BoxFuture;
+    use std::future::Future;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    // The tokio timer, the delays are recorded
+    #[derive(Default)]
+    struct RecordingClock {
+        sleeps: Mutex<Vec<Duration>>,
+    }
+
+    impl Clock for RecordingClock {
+        fn now(&self) -> Instant {
+            Instant::now()
+        }
+
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            self.sleeps.lock().unwrap().push(duration);
+            Box::pin(tokio::time::sleep(duration))
+        }
+    }
+
+    #[test]
+    fn the_burst_is_available_immediately() {
+        block_on(async {
+            let rate_limiter = RateLimiter::new(RateLimit::new(1.0, 3));
+            let clock = RecordingClock::default();
+
+            for _ in 0..3 {
+                rate_limiter.acquire(Priority::Normal, &clock).await;
+            }
+
+            assert!(clock.sleeps.lock().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn tokens_are_refilled_at_the_rate() {
+        block_on(async {
+            let rate_limiter = RateLimiter::new(RateLimit::new(50.0, 1));
+            let clock = RecordingClock::default();
+
+            rate_limiter.acquire(Priority::Normal, &clock).await;
+            rate_limiter.acquire(Priority::Normal, &clock).await;
+
+            let sleeps = clock.sleeps.lock().unwrap();
+            assert!(!sleeps.is_empty());
+            assert!(sleeps[0] > Duration::ZERO && sleeps[0] <= Duration::from_millis(20));
+        });
+    }
+
+    #[test]
+    fn the_refill_is_capped_at_the_burst() {
+        block_on(async {
+            let rate_limiter = RateLimiter::new(RateLimit::new(100.0, 2));
+            rate_limiter.bucket.lock().unwrap().last_refill -= Duration::from_secs(60);
+
+            rate_limiter
+                .acquire(Priority::Normal, &RecordingClock::default())
+                .await;
+
+            assert!(rate_limiter.bucket.lock().unwrap().tokens < 1.5);
+        });
+    }
+
+    #[test]
+    fn a_higher_priority_gets_the_next_token_first() {
+        block_on(async {
+            let rate_limiter = RateLimiter::new(RateLimit::new(20.0, 1));
+            let clock = RecordingClock::default();
+            rate_limiter.acquire(Priority::Normal, &clock).await;
+
+            let order = Mutex::new(Vec::new());
+            let acquire = |priority| {
+                let (rate_limiter, clock, order) = (&rate_limiter, &clock, &order);
+                async move {
+                    rate_limiter.acquire(priority, clock).await;
+                    order.lock().unwrap().push(priority);
+                }
+            };
+            // The normal request starts waiting first
+            futures_util::future::join(acquire(Priority::Normal), acquire(Priority::High)).await;
+
+            assert_eq!(
+                *order.lock().unwrap(),
+                vec![Priority::High, Priority::Normal]
+            );
+        });
+    }
+
+    #[test]
+    fn a_cancelled_request_gives_up_its_place() {
+        block_on(async {
+            let rate_limiter = RateLimiter::new(RateLimit::new(1.0, 1));
+            let clock = RecordingClock::default();
+            rate_limiter.acquire(Priority::Normal, &clock).await;
+
+            let waiting = tokio::time::timeout(
+                Duration::from_millis(10),
+                rate_limiter.acquire(Priority::Normal, &clock),
+            )
+            .await;
+
+            assert!(waiting.is_err());
+            let bucket = rate_limiter.bucket.lock().unwrap();
+            assert!(bucket.waiting.iter().all(BTreeSet::is_empty));
+        });
+    }
+}
@@ -0,0 +1,77 @@
+use reqwest::redirect::{Action, Attempt, Policy};
+use serde::Deserialize;
+
+/// The number of redirects which are followed by default
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// How redirects of the endpoints are followed, redirects of the token endpoint are never followed
+///
+/// The bearer token is never sent to another origin, `reqwest` removes the `Authorization` header when a redirect changes the host or port
+/// and a redirect from https to http is refused because the token would be sent in plain text.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RedirectPolicy {
+    /// Follow up to `max_redirects` redirects to any origin
+    Limited { max_redirects: usize },
+    /// Follow up to `max_redirects` redirects to the origin of the request, a redirect to another origin fails
+    SameOrigin { max_redirects: usize },
+    /// Don't follow redirects, the redirect response fails with a [StatusError](crate::StatusError)
+    None,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        RedirectPolicy::Limited {
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// The `reqwest` policy, shared by the async and blocking client
+    pub(crate) fn to_reqwest_policy(self) -> Policy {
+        match self {
+            RedirectPolicy::Limited { max_redirects } => {
+                Policy::custom(move |attempt| follow_redirect(attempt, max_redirects, false))
+            }
+            RedirectPolicy::SameOrigin { max_redirects } => {
+                Policy::custom(move |attempt| follow_redirect(attempt, max_redirects, true))
+            }
+            RedirectPolicy::None => Policy::none(),
+        }
+    }
+}
+
+fn follow_redirect(attempt: Attempt, max_redirects: usize, same_origin: bool) -> Action {
+    // The first url is the one of the request itself
+    let previous = attempt.previous();
+    if previous.len() > max_redirects {
+        return attempt.error(format!(
+            "Too many redirects, at most {} are followed",
+            max_redirects
+        ));
+    }
+
+    let (first, last) = match (previous.first(), previous.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return attempt.follow(),
+    };
+    if last.scheme() == "https" && attempt.url().scheme() != "https" {
+        let error = format!(
+            "Refusing to follow the redirect from {} to {}, it downgrades https",
+            last,
+            attempt.url()
+        );
+        return attempt.error(error);
+    }
+    if same_origin && attempt.url().origin() != first.origin() {
+        let error = format!(
+            "Refusing to follow the redirect from {} to {}, it leaves the origin",
+            last,
+            attempt.url()
+        );
+        return attempt.error(error);
+    }
+
+    attempt.follow()
+}
@@ -0,0 +1,47 @@
+use crate::interceptor::BoxFuture;
+use anyhow::Result;
+use reqwest::Request;
+
+/// Signs every request made by an `AuthorizedClient`, e.g. with an HMAC or AWS SigV4-style signature header
+///
+/// Register a signer using [with_signer](crate::AuthorizedClient::with_signer).
+/// The signer is called for every attempt, retries included, after the bearer token is added and the interceptors are called,
+/// right before the request is sent. This way the signature covers the final headers and a fresh timestamp.
+///
+/// ```
+/// use authorized_client::{BoxFuture, RequestSigner};
+/// use reqwest::Request;
+/// use ring::hmac;
+/// use std::time::{SystemTime, UNIX_EPOCH};
+///
+/// struct HmacSigner {
+///     key: hmac::Key,
+/// }
+///
+/// impl RequestSigner for HmacSigner {
+///     fn sign<'a>(&'a self, request: &'a mut Request, body: &'a [u8]) -> BoxFuture<'a, anyhow::Result<()>> {
+///         Box::pin(async move {
+///             let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs().to_string();
+///
+///             let mut context = hmac::Context::with_key(&self.key);
+///             for part in [request.method().as_str(), request.url().path(), &timestamp] {
+///                 context.update(part.as_bytes());
+///                 context.update(b"\n");
+///             }
+///             context.update(body);
+///             let signature: String = context.sign().as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+///
+///             let headers = request.headers_mut();
+///             headers.insert("X-Timestamp", timestamp.parse()?);
+///             headers.insert("X-Signature", signature.parse()?);
+///             Ok(())
+///         })
+///     }
+/// }
+/// ```
+pub trait RequestSigner: Send + Sync {
+    /// Add the signature to the request, `body` contains the bytes of the body and is empty when the request has no body
+    ///
+    /// Streamed bodies are not available up front, those requests fail before `sign` is called.
+    fn sign<'a>(&'a self, request: &'a mut Request, body: &'a [u8]) -> BoxFuture<'a, Result<()>>;
+}
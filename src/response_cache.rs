@@ -0,0 +1,143 @@
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderValue, CACHE_CONTROL, ETAG};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Cache the responses of get requests, see: [response_cache](crate::Settings::response_cache)
+///
+/// Responses are cached when they have an `ETag` or a `Cache-Control: max-age`.
+/// A response is reused without a request until its `max-age` has passed,
+/// after that it's revalidated by sending its `ETag` in an `If-None-Match` header.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseCacheSettings {
+    /// The maximum number of cached responses, the response which expires first is removed when the cache is full
+    pub max_entries: usize,
+}
+
+impl Default for ResponseCacheSettings {
+    fn default() -> Self {
+        ResponseCacheSettings { max_entries: 1000 }
+    }
+}
+
+/// A successful response which is shared with other get requests, by the cache or by deduplicating them
+///
+/// The status and headers are kept so the body can be validated like the body of a response which isn't shared.
+#[derive(Clone)]
+pub(crate) struct SharedResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Bytes,
+}
+
+/// A cached response
+#[derive(Clone)]
+pub(crate) struct CachedResponse {
+    pub(crate) etag: Option<HeaderValue>,
+    pub(crate) response: SharedResponse,
+    fresh_until: Instant,
+}
+
+impl CachedResponse {
+    /// Check if the response can be used without revalidating it
+    pub(crate) fn is_fresh(&self) -> bool {
+        Instant::now() < self.fresh_until
+    }
+}
+
+/// The cached responses keyed by url, shared by all clones of an `AuthorizedClient`
+pub(crate) struct ResponseCache {
+    settings: ResponseCacheSettings,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(settings: ResponseCacheSettings) -> Self {
+        ResponseCache {
+            settings,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(url).cloned()
+    }
+
+    /// Store the response if its headers allow it, otherwise the cached response is removed
+    pub(crate) fn put(&self, url: &str, response: SharedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+
+        let (cacheable, max_age) = cache_control(&response.headers);
+        let max_age = max_age.unwrap_or(Duration::ZERO);
+        let etag = response.headers.get(ETAG).cloned();
+        // Without an etag a response which isn't fresh can't be reused
+        if !cacheable || self.settings.max_entries == 0 || (etag.is_none() && max_age.is_zero()) {
+            entries.remove(url);
+            return;
+        }
+
+        if entries.len() >= self.settings.max_entries && !entries.contains_key(url) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fresh_until)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            url.to_owned(),
+            CachedResponse {
+                etag,
+                response,
+                fresh_until: Instant::now() + max_age,
+            },
+        );
+    }
+
+    /// The server confirmed the cached response is still valid, update its freshness
+    pub(crate) fn refresh(&self, url: &str, headers: &HeaderMap) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(url) {
+            let (_, max_age) = cache_control(headers);
+            entry.fresh_until = Instant::now() + max_age.unwrap_or(Duration::ZERO);
+        }
+    }
+}
+
+// Parse the `Cache-Control` header, returns if the response may be stored and how long it's fresh
+fn cache_control(headers: &HeaderMap) -> (bool, Option<Duration>) {
+    let mut cacheable = true;
+    let mut max_age = None;
+
+    for value in headers.get_all(CACHE_CONTROL) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        for directive in value.split(',').map(str::trim) {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cacheable = false,
+                // The response may be stored but has to be revalidated every time
+                "no-cache" => max_age = Some(Duration::ZERO),
+                "max-age" if max_age.is_none() => {
+                    // Larger values are capped, like recommended by RFC 9111
+                    max_age = argument
+                        .and_then(|argument| argument.parse::<u64>().ok())
+                        .map(|seconds| Duration::from_secs(seconds.min(i32::MAX as u64)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    (cacheable, max_age)
+}
@@ -0,0 +1,32 @@
+use crate::deserialize_error::deserialize_json;
+use anyhow::Result;
+use reqwest::header::HeaderMap;
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+
+/// A deserialized response body together with the status code and headers of the response
+#[derive(Clone, Debug)]
+pub struct ResponseMeta<R> {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: R,
+}
+
+/// Deserialize the json body of the response, keeping the status code and headers, see: [deserialize_json](deserialize_json)
+pub(crate) async fn json_with_meta<R>(
+    response: Response,
+    accept_any_content_type: bool,
+) -> Result<ResponseMeta<R>>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = deserialize_json(response, accept_any_content_type).await?;
+
+    Ok(ResponseMeta {
+        status,
+        headers,
+        body,
+    })
+}
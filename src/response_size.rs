@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use futures_util::stream;
+use oauth2::http;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::{Response, ResponseBuilderExt};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use url::Url;
+
+/// The error returned when a response body is larger than the [max_response_size](crate::Settings::max_response_size)
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the limit and url.
+#[derive(Clone, Debug)]
+pub struct ResponseTooLargeError {
+    /// The maximum number of bytes
+    pub limit: usize,
+    pub url: Url,
+}
+
+impl Display for ResponseTooLargeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The response of {} is larger than {} bytes",
+            self.url, self.limit
+        )
+    }
+}
+
+impl Error for ResponseTooLargeError {}
+
+/// Fail when the body of `response` is larger than `limit`
+///
+/// A `Content-Length` above the limit fails immediately, a body of a known size within the limit is returned as is.
+/// Otherwise the body fails while it's read, before the rest is buffered.
+pub(crate) fn limit_response_size(mut response: Response, limit: usize) -> Result<Response> {
+    let too_large = ResponseTooLargeError {
+        limit,
+        url: response.url().clone(),
+    };
+    // The header is checked as well, the size of a logged body is unknown, see: WireLogSettings
+    let content_length = response.content_length().or_else(|| {
+        response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse().ok())
+    });
+    match content_length {
+        Some(content_length) if content_length > limit as u64 => return Err(too_large.into()),
+        // hyper doesn't read past the announced size
+        _ if response.content_length().is_some() => return Ok(response),
+        _ => {}
+    }
+
+    // The extensions, e.g. the remote address, are moved to the rebuilt response before the url is added to them
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    if let Some(extensions) = builder.extensions_mut() {
+        *extensions = mem::take(response.extensions_mut());
+    }
+    let builder = builder.url(response.url().clone());
+
+    let body = stream::unfold(Some((response, 0)), move |state| {
+        let too_large = too_large.clone();
+        async move {
+            let (mut response, read) = state?;
+            match response.chunk().await {
+                Ok(Some(chunk)) if read + chunk.len() > limit => Some((
+                    Err::<Bytes, Box<dyn Error + Send + Sync>>(too_large.into()),
+                    None,
+                )),
+                Ok(Some(chunk)) => {
+                    let read = read + chunk.len();
+                    Some((Ok(chunk), Some((response, read))))
+                }
+                Ok(None) => None,
+                Err(error) => Some((Err(error.into()), None)),
+            }
+        }
+    });
+
+    Ok(builder
+        .body(hyper::Body::wrap_stream(body))
+        .context("Failed to rebuild the limited response")?
+        .into())
+}
+
+/// The body errors are wrapped by `reqwest`, return the [ResponseTooLargeError](ResponseTooLargeError) itself so it can be downcast
+pub(crate) fn unwrap_response_too_large(error: anyhow::Error) -> anyhow::Error {
+    match error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ResponseTooLargeError>())
+    {
+        Some(too_large) => too_large.clone().into(),
+        None => error,
+    }
+}
+
+/// Fail when the body of a blocking `response` is larger than `limit`
+///
+/// The same checks as [limit_response_size](limit_response_size), a blocking body can't be wrapped
+/// so a body of an unknown size is read up to the limit and buffered.
+#[cfg(feature = "blocking")]
+pub(crate) fn blocking_limit_response_size(
+    mut response: reqwest::blocking::Response,
+    limit: usize,
+) -> Result<reqwest::blocking::Response> {
+    use std::io::Read;
+
+    let too_large = ResponseTooLargeError {
+        limit,
+        url: response.url().clone(),
+    };
+    match response.content_length() {
+        Some(content_length) if content_length > limit as u64 => return Err(too_large.into()),
+        // hyper doesn't read past the announced size
+        Some(_) => return Ok(response),
+        None => {}
+    }
+
+    // One byte more than the limit is read to know whether the body is larger
+    let mut body = Vec::new();
+    (&mut response)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .context("Failed to read the response body")?;
+    if body.len() > limit {
+        return Err(too_large.into());
+    }
+
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    if let Some(extensions) = builder.extensions_mut() {
+        *extensions = mem::take(response.extensions_mut());
+    }
+    let builder = builder.url(response.url().clone());
+
+    Ok(builder
+        .body(body)
+        .context("Failed to rebuild the limited response")?
+        .into())
+}
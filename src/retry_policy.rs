@@ -0,0 +1,167 @@
+use crate::clock::Clock;
+use crate::network_error::NetworkErrorKind;
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Retry requests which failed because of a transient error
+///
+/// This is independent of the retries after a `401 Unauthorized`, those are always done.
+/// Retries are disabled by default, only enable them for endpoints where sending a request twice is safe,
+/// or let the server detect retried writes using [idempotency_keys](crate::Settings::idempotency_keys).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one. `1` disables retries
+    pub max_attempts: u32,
+    /// The time to wait between attempts
+    pub backoff: Backoff,
+    /// Wait a random duration between zero and the backoff delay, this spreads the retries of many clients
+    pub jitter: bool,
+    /// Status codes which are retried
+    pub retryable_status_codes: Vec<u16>,
+    /// Retry when the host name couldn't be resolved or the connection could not be established or was reset
+    pub retry_connection_errors: bool,
+    /// Retry when the request timed out
+    pub retry_timeouts: bool,
+    /// The total time a request may wait because of `Retry-After` headers on `429` and `503` responses
+    ///
+    /// These retries are done even when `max_attempts` is `1`, the server indicated the request wasn't processed.
+    /// Set this to zero to disable them.
+    #[serde(with = "crate::duration_format")]
+    pub retry_after_budget: Duration,
+    /// The maximum number of retries because of `Retry-After` headers within the `retry_after_budget`, defaults to 3
+    pub max_retry_after_retries: u32,
+}
+
+/// The time to wait between attempts
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Backoff {
+    /// Always wait the same duration
+    Fixed {
+        #[serde(with = "crate::duration_format")]
+        delay: Duration,
+    },
+    /// Multiply the delay by `multiplier` after every attempt, up to `max_delay`
+    Exponential {
+        #[serde(with = "crate::duration_format")]
+        initial_delay: Duration,
+        #[serde(with = "crate::duration_format")]
+        max_delay: Duration,
+        multiplier: f64,
+    },
+}
+
+impl RetryPolicy {
+    /// Create a policy which retries up to `max_attempts` attempts using the default backoff and status codes
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Check if a response with `status` should be retried, `attempt` starts at 1
+    pub(crate) fn should_retry_status(&self, status: StatusCode, attempt: u32) -> bool {
+        attempt < self.max_attempts && self.retryable_status_codes.contains(&status.as_u16())
+    }
+
+    /// Check if a request which failed with `error` should be retried, `attempt` starts at 1
+    pub(crate) fn should_retry_error(&self, error: &reqwest::Error, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+
+        match NetworkErrorKind::of(error) {
+            NetworkErrorKind::Timeout => self.retry_timeouts,
+            NetworkErrorKind::Dns
+            | NetworkErrorKind::Connect
+            | NetworkErrorKind::ConnectionReset => self.retry_connection_errors,
+            NetworkErrorKind::Other => false,
+        }
+    }
+
+    /// Check if a token exchange which failed with `error` should be retried, `attempt` starts at 1
+    ///
+    /// Only connection errors and timeouts are retried, an error response of the auth server won't change by retrying.
+    pub(crate) fn should_retry_token_error(&self, error: &anyhow::Error, attempt: u32) -> bool {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .is_some_and(|error| self.should_retry_error(error, attempt))
+    }
+
+    /// The time to wait after `attempt`, `attempt` starts at 1
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff.delay(attempt);
+
+        if self.jitter {
+            delay.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+        } else {
+            delay
+        }
+    }
+}
+
+impl Backoff {
+    /// The time to wait after `attempt`, `attempt` starts at 1
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        match self {
+            Backoff::Fixed { delay } => *delay,
+            Backoff::Exponential {
+                initial_delay,
+                max_delay,
+                multiplier,
+            } => {
+                let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+                let delay = initial_delay.as_secs_f64() * multiplier.powi(exponent);
+                // Invalid multipliers (negative, NaN) fall back to the maximum delay
+                Duration::try_from_secs_f64(delay).map_or(*max_delay, |delay| delay.min(*max_delay))
+            }
+        }
+    }
+}
+
+/// Parse the `Retry-After` header, it contains either a number of seconds or a HTTP-date which is compared with the time of `clock`
+pub(crate) fn retry_after(headers: &HeaderMap, clock: &dyn Clock) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(
+                date.duration_since(clock.system_time())
+                    .unwrap_or(Duration::ZERO),
+            )
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            backoff: Backoff::default(),
+            jitter: true,
+            retryable_status_codes: vec![429, 502, 503, 504],
+            retry_connection_errors: true,
+            retry_timeouts: true,
+            retry_after_budget: Duration::from_secs(30),
+            max_retry_after_retries: 3,
+        }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::Exponential {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt::{self, Debug, Formatter};
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+
+/// A string which is redacted when it's printed, e.g. a bearer token or client secret
+///
+/// The memory of the secret is overwritten with zeros when it's dropped.
+/// Use [expose_secret](SecretString::expose_secret) to get the value.
+#[derive(Clone, Default)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: String) -> Self {
+        SecretString(secret)
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        SecretString(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        SecretString(secret.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(SecretString)
+    }
+}
+
+impl Debug for SecretString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString([redacted])")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        // Zeros are valid UTF-8, the volatile writes prevent the compiler from removing the "unused" writes
+        let bytes = unsafe { self.0.as_mut_vec() };
+        for byte in bytes.iter_mut() {
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
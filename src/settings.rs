@@ -1,9 +1,1236 @@
-use serde::Deserialize;
+use crate::cassette::CassetteSettings;
+use crate::circuit_breaker::CircuitBreakerSettings;
+use crate::client_assertion::{JwtAlgorithm, SigningKey};
+use crate::idempotency::IdempotencyKeySettings;
+use crate::rate_limiter::RateLimit;
+use crate::redirect_policy::RedirectPolicy;
+use crate::response_cache::ResponseCacheSettings;
+use crate::retry_policy::{Backoff, RetryPolicy};
+use crate::secret::SecretString;
+use crate::token_exchange::ACCESS_TOKEN_TYPE;
+use crate::wire_log::WireLogSettings;
+use anyhow::{bail, Context, Result};
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::env::{self, VarError};
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
 
-#[derive(Clone, Deserialize)]
+/// The settings of an `AuthorizedClient`
+///
+/// Create them using [new](Settings::new), the [builder](Settings::builder) or one of the loaders, e.g. [from_env](Settings::from_env).
+/// The fields can be read and changed, but new fields are added without a major release so the struct can't be constructed directly.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct Settings {
     pub client_id: String,
-    pub client_secret: String,
+    pub client_secret: SecretString,
+    /// Can be left empty when the `issuer_url` is set
+    #[serde(default)]
     pub token_url: String,
     pub scopes: Vec<String>,
+    /// Discover the token, introspection and revocation endpoints from the metadata of this issuer when they are not set
+    ///
+    /// Both OpenID Connect discovery (`/.well-known/openid-configuration`) and [RFC 8414](https://tools.ietf.org/html/rfc8414) are supported.
+    #[serde(default)]
+    pub issuer_url: Option<String>,
+    /// The token introspection endpoint ([RFC 7662](https://tools.ietf.org/html/rfc7662)), used by [introspect_token](crate::AuthorizedClient::introspect_token)
+    #[serde(default)]
+    pub introspection_url: Option<String>,
+    /// The token revocation endpoint ([RFC 7009](https://tools.ietf.org/html/rfc7009)), used by [revoke_token](crate::AuthorizedClient::revoke_token)
+    #[serde(default)]
+    pub revocation_url: Option<String>,
+    /// The url the paths passed to the `*_path` methods are relative to, e.g. `https://api.example.com/v1`
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// An endpoint which is called by [health_check](crate::AuthorizedClient::health_check), any `2xx` response is healthy
+    #[serde(default)]
+    pub health_check_url: Option<String>,
+    /// Only send the bearer token to these hosts, e.g. `api.example.com` or `*.example.com` for every subdomain
+    ///
+    /// A request to another host fails with a [HostNotAllowedError](crate::HostNotAllowedError), by default every host is allowed.
+    #[serde(default)]
+    pub allowed_hosts: Option<Vec<String>>,
+    /// Headers added to every request to an endpoint, e.g. `User-Agent`, headers set on the request itself take precedence
+    #[serde(default)]
+    pub default_headers: HashMap<String, String>,
+    /// The OAuth 2.0 grant used to get a bearer token, defaults to client credentials
+    #[serde(default)]
+    pub grant_type: GrantType,
+    /// How the client authenticates itself at the token endpoint, defaults to the client secret
+    #[serde(default)]
+    pub client_auth_method: ClientAuthMethod,
+    /// How the client id and client secret are sent to the token endpoint, defaults to HTTP basic auth
+    #[serde(default)]
+    pub auth_type: AuthType,
+    /// Bind the bearer tokens to a key pair of the client using DPoP ([RFC 9449](https://tools.ietf.org/html/rfc9449))
+    ///
+    /// A new key pair is generated for every client, every request carries a proof signed with it.
+    /// The blocking client, WebSocket connections and token providers don't support DPoP.
+    #[serde(default)]
+    pub dpop: bool,
+    /// Extra parameters sent with every token request, e.g. `audience` for Auth0 or `resource` for Azure
+    #[serde(default)]
+    pub extra_token_params: HashMap<String, String>,
+    /// The TLS client identity used for mutual TLS, for both the token endpoint and the other endpoints
+    #[serde(default)]
+    pub client_identity: Option<ClientIdentity>,
+    /// PEM encoded root certificates which are trusted on top of the system root certificates
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+    /// Accept invalid TLS certificates, only use this for testing
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+    /// The minimum TLS version which is accepted
+    #[serde(default)]
+    pub min_tls_version: Option<TlsVersion>,
+    /// Send all requests through this proxy, when empty the proxy environment variables are used
+    #[serde(default)]
+    pub proxy: Option<ProxySettings>,
+    /// How the connections to the endpoints are pooled and kept alive
+    #[serde(default)]
+    pub connection_pool: ConnectionPoolSettings,
+    /// The maximum duration of a request to an endpoint, from connecting until the response body has been read
+    #[serde(default, with = "crate::duration_format::option")]
+    pub request_timeout: Option<Duration>,
+    /// The maximum duration to establish a connection, for both the token endpoint and the other endpoints
+    #[serde(default, with = "crate::duration_format::option")]
+    pub connect_timeout: Option<Duration>,
+    /// The maximum duration of a request to the token endpoint
+    #[serde(default, with = "crate::duration_format::option")]
+    pub token_exchange_timeout: Option<Duration>,
+    /// How redirects of the endpoints are followed, by default up to 10 redirects are followed
+    #[serde(default)]
+    pub redirect_policy: RedirectPolicy,
+    /// How token exchanges which failed because of a connection error or timeout are retried, by default they are not retried
+    #[serde(default)]
+    pub token_retry_policy: RetryPolicy,
+    /// How requests which failed because of a transient error are retried, by default they are not retried
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    /// The number of times the bearer token is refreshed and the request retried when the server responds with `401 Unauthorized`, defaults to 3
+    #[serde(default = "default_max_auth_retries")]
+    pub max_auth_retries: u8,
+    /// The time to wait before retrying a request after a `401 Unauthorized` response, the first retry is done immediately
+    ///
+    /// `attempt` 1 of the backoff is the second retry, this way the token endpoint isn't flooded when the server keeps rejecting the token.
+    /// Defaults to an exponential backoff of 1 second, multiplied by 1.5 every retry, up to 10 seconds.
+    #[serde(default = "default_auth_retry_backoff")]
+    pub auth_retry_backoff: Backoff,
+    /// The number of times an interrupted download is resumed, defaults to 3
+    #[serde(default = "default_max_download_resumes")]
+    pub max_download_resumes: u8,
+    /// The time to wait before resuming an interrupted download
+    ///
+    /// Defaults to an exponential backoff of 500 milliseconds, multiplied by 2 every resume, up to 5 seconds.
+    #[serde(default = "default_download_resume_backoff")]
+    pub download_resume_backoff: Backoff,
+    /// Send a get or head request again when there's no response within this duration, the response which arrives first is used
+    ///
+    /// This cuts the tail latency of reads from a backend which is sometimes slow, at the cost of more requests.
+    /// Every attempt of the [retry_policy](Settings::retry_policy) is hedged. By default requests are not hedged.
+    #[serde(default, with = "crate::duration_format::option")]
+    pub hedge_after: Option<Duration>,
+    /// Limit the number of requests to the endpoints, the limit is shared by all clones of the client
+    ///
+    /// Not supported on wasm32, the rate limiter needs a timer.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// Fail immediately while the endpoints keep failing, the state is shared by all clones of the client
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerSettings>,
+    /// Coalesce concurrent get requests to the same url into one request, the callers share the response
+    #[serde(default)]
+    pub deduplicate_gets: bool,
+    /// Cache the responses of get requests using their `ETag` and `Cache-Control` headers
+    #[serde(default)]
+    pub response_cache: Option<ResponseCacheSettings>,
+    /// Log the requests to the endpoints and their responses, by default nothing is logged
+    #[serde(default)]
+    pub wire_log: Option<WireLogSettings>,
+    /// Record the requests and responses to a file or replay them, for tests
+    #[serde(default)]
+    pub cassette: Option<CassetteSettings>,
+    /// Deserialize json bodies regardless of their `Content-Type`, by default a body which isn't json fails with an [UnexpectedContentTypeError](crate::UnexpectedContentTypeError)
+    #[serde(default)]
+    pub accept_any_content_type: bool,
+    /// The maximum size of a response body in bytes, a larger body fails with a [ResponseTooLargeError](crate::ResponseTooLargeError)
+    ///
+    /// The limit applies to the streams of `get_stream` and `sse` as well, it doesn't apply to WebSocket connections.
+    /// The blocking client only checks the `Content-Length` header.
+    #[serde(default)]
+    pub max_response_size: Option<usize>,
+    /// Attach an idempotency key to post and patch requests, so the server can detect retried writes
+    #[serde(default)]
+    pub idempotency_keys: Option<IdempotencyKeySettings>,
+    /// Refresh the bearer token when it expires within this duration, this avoids using a token which expires while the request is in flight
+    #[serde(default = "default_refresh_leeway", with = "crate::duration_format")]
+    pub refresh_leeway: Duration,
+    /// The lifetime of a bearer token when the token response doesn't contain `expires_in`
+    ///
+    /// With the `jwt` feature the `exp` claim of a JWT access token is used instead, when it has one.
+    /// By default a token response without `expires_in` fails.
+    #[serde(default, with = "crate::duration_format::option")]
+    pub default_token_lifetime: Option<Duration>,
+    /// Refresh the bearer token in a background task instead of when a request is made, this requires a tokio runtime
+    #[serde(default)]
+    pub background_refresh: bool,
+    /// Keep using the current bearer token for up to this duration after it expired when a new one can't be fetched
+    ///
+    /// This keeps requests working during a short outage of the token endpoint, the auth server decides if the expired token is still accepted.
+    /// By default a failed refresh fails the request.
+    #[serde(default, with = "crate::duration_format")]
+    pub serve_stale: Duration,
+    /// After a failed refresh of the bearer token no new token is requested for this duration, the requests which need one fail immediately
+    ///
+    /// This avoids hammering the token endpoint with a token exchange for every request while it's failing.
+    /// Zero disables the cooldown.
+    #[serde(default = "default_refresh_cooldown", with = "crate::duration_format")]
+    pub refresh_cooldown: Duration,
+    /// Retry a request once with a bearer token for the scopes a `403 Forbidden` response with an `insufficient_scope` error asks for
+    ///
+    /// The auth server decides if the client may get a token for those scopes, by default the request fails.
+    #[serde(default)]
+    pub retry_insufficient_scope: bool,
+}
+
+/// The OAuth 2.0 grant used to get a bearer token from the auth server
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum GrantType {
+    /// Exchange the client id and client secret for a bearer token
+    #[default]
+    ClientCredentials,
+    /// Exchange the username and password of a resource owner for a bearer token
+    ///
+    /// The client secret is optional for this grant, leave it empty for public clients.
+    Password {
+        username: String,
+        password: SecretString,
+    },
+    /// Let the user authorize the client on a second device, see: [DeviceCodeFlow](crate::DeviceCodeFlow)
+    ///
+    /// The client secret is optional for this grant, leave it empty for public clients.
+    DeviceCode { device_authorization_url: String },
+    /// Exchange a token issued by another party for a bearer token ([RFC 8693](https://tools.ietf.org/html/rfc8693))
+    ///
+    /// The same subject token is exchanged again when the bearer token expires.
+    /// The client secret is optional for this grant, leave it empty for public clients.
+    TokenExchange {
+        subject_token: SecretString,
+        /// The type of the subject token, defaults to `urn:ietf:params:oauth:token-type:access_token`
+        #[serde(default = "default_subject_token_type")]
+        subject_token_type: String,
+        /// The type of the requested token, by default the auth server decides
+        #[serde(default)]
+        requested_token_type: Option<String>,
+        /// The logical name of the service the bearer token is used for
+        #[serde(default)]
+        audience: Option<String>,
+    },
+}
+
+fn default_subject_token_type() -> String {
+    ACCESS_TOKEN_TYPE.to_string()
+}
+
+/// How the client authenticates itself at the token endpoint
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ClientAuthMethod {
+    /// Authenticate using the client secret
+    #[default]
+    ClientSecret,
+    /// Authenticate using a client assertion signed with a private key (`private_key_jwt`)
+    ///
+    /// The client secret is not used for this method, leave it empty.
+    PrivateKeyJwt {
+        /// The PEM encoded private key
+        key: SecretString,
+        algorithm: JwtAlgorithm,
+        /// The audience of the assertion, defaults to the token url
+        #[serde(default)]
+        audience: Option<String>,
+    },
+}
+
+/// How the client id and client secret are sent to the token endpoint
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthType {
+    /// Send the client id and client secret using HTTP basic auth
+    #[default]
+    BasicAuth,
+    /// Send the client id and client secret in the request body
+    RequestBody,
+}
+
+/// A TLS client identity used for mutual TLS
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ClientIdentity {
+    /// A PEM encoded private key and certificate chain
+    Pem { pem: SecretString },
+    /// A DER encoded PKCS#12 archive
+    Pkcs12 {
+        der: Vec<u8>,
+        password: SecretString,
+    },
+}
+
+impl Debug for ClientIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Both variants contain a private key
+        match self {
+            ClientIdentity::Pem { .. } => write!(f, "Pem([redacted])"),
+            ClientIdentity::Pkcs12 { .. } => write!(f, "Pkcs12([redacted])"),
+        }
+    }
+}
+
+/// A TLS protocol version
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls1_2,
+    #[serde(rename = "1.3")]
+    Tls1_3,
+}
+
+/// The proxy which is used for the token endpoint and the other endpoints
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxySettings {
+    pub url: String,
+    /// Authenticate at the proxy using basic auth
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<SecretString>,
+    /// Hosts which are reached without the proxy, e.g. `localhost` or `.internal.example.com`
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    pub fn new(url: impl Into<String>) -> Self {
+        ProxySettings {
+            url: url.into(),
+            username: None,
+            password: None,
+            no_proxy: Vec::new(),
+        }
+    }
+
+    /// Authenticate at the proxy using basic auth
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(SecretString::new(password.into()));
+        self
+    }
+
+    /// Reach `host` without the proxy
+    pub fn no_proxy(mut self, host: impl Into<String>) -> Self {
+        self.no_proxy.push(host.into());
+        self
+    }
+}
+
+/// How the connections to the endpoints are pooled and kept alive, the token endpoint always uses the defaults
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionPoolSettings {
+    /// The maximum number of idle connections which are kept per host, by default there is no limit
+    pub max_idle_per_host: Option<usize>,
+    /// Close connections which have been idle for this duration, defaults to 90 seconds
+    #[serde(with = "crate::duration_format::option")]
+    pub idle_timeout: Option<Duration>,
+    /// Use HTTP/2 without negotiating it first, only enable this when every endpoint supports HTTP/2
+    pub http2_prior_knowledge: bool,
+    /// Send HTTP/2 keep-alive pings at this interval to detect broken connections, not supported by the blocking client
+    #[serde(with = "crate::duration_format::option")]
+    pub http2_keep_alive_interval: Option<Duration>,
+}
+
+/// The default [refresh_leeway](Settings::refresh_leeway)
+pub const DEFAULT_REFRESH_LEEWAY: Duration = Duration::from_secs(30);
+
+fn default_refresh_leeway() -> Duration {
+    DEFAULT_REFRESH_LEEWAY
+}
+
+fn default_max_auth_retries() -> u8 {
+    3
+}
+
+fn default_auth_retry_backoff() -> Backoff {
+    Backoff::Exponential {
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(10),
+        multiplier: 1.5,
+    }
+}
+
+fn default_max_download_resumes() -> u8 {
+    3
+}
+
+fn default_download_resume_backoff() -> Backoff {
+    Backoff::Exponential {
+        initial_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(5),
+        multiplier: 2.0,
+    }
+}
+
+/// The default [refresh_cooldown](Settings::refresh_cooldown)
+pub const DEFAULT_REFRESH_COOLDOWN: Duration = Duration::from_secs(5);
+
+fn default_refresh_cooldown() -> Duration {
+    DEFAULT_REFRESH_COOLDOWN
+}
+
+impl Settings {
+    /// Create validated settings for the client credentials grant with the defaults for the other settings
+    ///
+    /// ```
+    ///# fn doc_test() -> anyhow::Result<()> {
+    /// use authorized_client::Settings;
+    ///
+    /// let mut settings = Settings::new("xxxxxxxxxx", "xxxxxxxxxx", "https://authorization-server.com/token")?;
+    /// settings.scopes.push("profile".to_string());
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn new(
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+        token_url: impl Into<String>,
+    ) -> Result<Self> {
+        Settings::builder()
+            .client_id(client_id)
+            .client_secret(client_secret)
+            .token_url(token_url)
+            .build()
+    }
+
+    /// Create a builder to construct validated `Settings`
+    pub fn builder() -> SettingsBuilder {
+        SettingsBuilder::default()
+    }
+
+    /// Load the settings from the `CLIENT_ID`, `CLIENT_SECRET`, `TOKEN_URL` and `SCOPES` environment variables
+    ///
+    /// `SCOPES` is an optional comma separated list of scopes.
+    pub fn from_env() -> Result<Self> {
+        Self::from_env_with_prefix("")
+    }
+
+    /// Load the settings from environment variables starting with `prefix`
+    ///
+    /// E.g. with prefix `MYAPP_` the variables `MYAPP_CLIENT_ID`, `MYAPP_CLIENT_SECRET`, `MYAPP_TOKEN_URL` and `MYAPP_SCOPES` are used.
+    /// See: [from_env](Settings::from_env) for more info
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self> {
+        let scopes = match env::var(format!("{}SCOPES", prefix)) {
+            Ok(scopes) => scopes
+                .split(',')
+                .map(str::trim)
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(VarError::NotPresent) => Vec::new(),
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("Failed to read environment variable {}SCOPES", prefix)
+                })
+            }
+        };
+
+        Settings::builder()
+            .client_id(required_env_var(prefix, "CLIENT_ID")?)
+            .client_secret(required_env_var(prefix, "CLIENT_SECRET")?)
+            .token_url(required_env_var(prefix, "TOKEN_URL")?)
+            .scopes(scopes)
+            .build()
+    }
+
+    /// Load the settings from a json file, the loaded settings are validated
+    ///
+    /// The field names match the fields of `Settings`.
+    /// Durations are strings of numbers with a unit, `ms`, `s`, `m`, `h` or `d`, e.g. `"30s"`, `"1m30s"` or `"500ms"`,
+    /// or a number of seconds, e.g. `30` or `0.5`. Objects with `secs` and `nanos` are accepted as well.
+    /// Unknown fields are rejected, this way a typo fails loudly instead of being ignored.
+    /// The error contains the path of the file, the field which is invalid and its line and column.
+    ///
+    /// ```no_run
+    ///# fn doc_test() -> anyhow::Result<()> {
+    /// use authorized_client::Settings;
+    ///
+    /// let settings = Settings::from_json_file("config/authorized_client.json")?;
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the settings file {}", path.display()))?;
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let settings = Self::from_deserializer(&mut deserializer)
+            .and_then(|settings| {
+                deserializer.end()?;
+                Ok(settings)
+            })
+            .with_context(|| format!("Invalid settings file {}", path.display()))?;
+
+        Ok(settings)
+    }
+
+    /// Deserialize the settings using any serde `deserializer`, the deserialized settings are validated
+    ///
+    /// Use this to load the settings from another format than json, e.g. with the deserializer of the `toml` or `serde_yaml` crate.
+    /// Unknown fields are rejected and the error contains the path of the field which is invalid.
+    /// There are no `from_toml_file` and `from_yaml_file` loaders yet, load those files like this:
+    ///
+    /// ```ignore
+    /// let toml = std::fs::read_to_string("config/authorized_client.toml")?;
+    /// let settings = Settings::from_deserializer(toml::Deserializer::new(&toml))
+    ///     .context("Invalid settings file config/authorized_client.toml")?;
+    /// ```
+    pub fn from_deserializer<'de, D>(deserializer: D) -> Result<Self>
+    where
+        D: Deserializer<'de>,
+        D::Error: Send + Sync + 'static,
+    {
+        let settings: Settings =
+            serde_path_to_error::deserialize(deserializer).map_err(|error| {
+                let path = error.path().to_string();
+                anyhow::Error::new(error.into_inner())
+                    .context(format!("Failed to deserialize the settings at '{}'", path))
+            })?;
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    // Settings for a client which gets its bearer token from elsewhere, the auth server fields are left empty
+    pub(crate) fn without_auth_server() -> Self {
+        Settings {
+            client_id: String::new(),
+            client_secret: SecretString::default(),
+            token_url: String::new(),
+            scopes: Vec::new(),
+            issuer_url: None,
+            introspection_url: None,
+            revocation_url: None,
+            base_url: None,
+            health_check_url: None,
+            allowed_hosts: None,
+            default_headers: HashMap::new(),
+            grant_type: GrantType::default(),
+            client_auth_method: ClientAuthMethod::default(),
+            auth_type: AuthType::default(),
+            dpop: false,
+            extra_token_params: HashMap::new(),
+            client_identity: None,
+            ca_bundle: None,
+            danger_accept_invalid_certs: false,
+            min_tls_version: None,
+            proxy: None,
+            connection_pool: ConnectionPoolSettings::default(),
+            request_timeout: None,
+            connect_timeout: None,
+            token_exchange_timeout: None,
+            redirect_policy: RedirectPolicy::default(),
+            token_retry_policy: RetryPolicy::default(),
+            retry_policy: RetryPolicy::default(),
+            max_auth_retries: default_max_auth_retries(),
+            auth_retry_backoff: default_auth_retry_backoff(),
+            max_download_resumes: default_max_download_resumes(),
+            download_resume_backoff: default_download_resume_backoff(),
+            hedge_after: None,
+            rate_limit: None,
+            circuit_breaker: None,
+            deduplicate_gets: false,
+            response_cache: None,
+            wire_log: None,
+            cassette: None,
+            accept_any_content_type: false,
+            max_response_size: None,
+            idempotency_keys: None,
+            refresh_leeway: DEFAULT_REFRESH_LEEWAY,
+            default_token_lifetime: None,
+            background_refresh: false,
+            serve_stale: Duration::ZERO,
+            refresh_cooldown: DEFAULT_REFRESH_COOLDOWN,
+            retry_insufficient_scope: false,
+        }
+    }
+
+    /// Check that the settings are usable, every problem is reported with the name of the field
+    pub fn validate(&self) -> Result<()> {
+        if self.client_id.trim().is_empty() {
+            bail!("Invalid settings: client_id must not be empty");
+        }
+        // Public clients using the password, device code or token exchange grant don't need a client secret
+        let requires_client_secret = matches!(self.grant_type, GrantType::ClientCredentials);
+        match &self.client_auth_method {
+            ClientAuthMethod::ClientSecret => {
+                if requires_client_secret && self.client_secret.expose_secret().trim().is_empty() {
+                    bail!("Invalid settings: client_secret must not be empty");
+                }
+            }
+            ClientAuthMethod::PrivateKeyJwt { key, algorithm, .. } => {
+                SigningKey::from_pem(key.expose_secret(), *algorithm).context(
+                    "Invalid settings: client_auth_method.key is not a valid private key",
+                )?;
+            }
+        }
+        match &self.grant_type {
+            GrantType::ClientCredentials => {}
+            GrantType::Password { username, .. } => {
+                if username.trim().is_empty() {
+                    bail!("Invalid settings: grant_type.username must not be empty");
+                }
+            }
+            GrantType::TokenExchange {
+                subject_token,
+                subject_token_type,
+                ..
+            } => {
+                if subject_token.expose_secret().trim().is_empty() {
+                    bail!("Invalid settings: grant_type.subject_token must not be empty");
+                }
+                if subject_token_type.trim().is_empty() {
+                    bail!("Invalid settings: grant_type.subject_token_type must not be empty");
+                }
+            }
+            GrantType::DeviceCode {
+                device_authorization_url,
+            } => {
+                Url::parse(device_authorization_url).with_context(|| {
+                    format!(
+                        "Invalid settings: grant_type.device_authorization_url '{}' is not a valid url",
+                        device_authorization_url
+                    )
+                })?;
+            }
+        }
+        // The rate limiter needs a timer, see: Clock::sleep
+        #[cfg(target_arch = "wasm32")]
+        if self.rate_limit.is_some() {
+            bail!("Invalid settings: rate_limit is not supported on wasm32");
+        }
+        // An empty token url is discovered using the issuer url
+        if let Some(issuer_url) = &self.issuer_url {
+            let issuer_url = Url::parse(issuer_url).with_context(|| {
+                format!(
+                    "Invalid settings: issuer_url '{}' is not a valid url",
+                    issuer_url
+                )
+            })?;
+            if !matches!(issuer_url.scheme(), "http" | "https") {
+                bail!(
+                    "Invalid settings: issuer_url '{}' must be an http or https url",
+                    issuer_url
+                );
+            }
+        }
+        if !self.token_url.is_empty() || self.issuer_url.is_none() {
+            let token_url = Url::parse(&self.token_url).with_context(|| {
+                format!(
+                    "Invalid settings: token_url '{}' is not a valid url",
+                    self.token_url
+                )
+            })?;
+            if !matches!(token_url.scheme(), "http" | "https") {
+                bail!(
+                    "Invalid settings: token_url '{}' must be an http or https url",
+                    self.token_url
+                );
+            }
+        }
+        if self.scopes.iter().any(|scope| scope.trim().is_empty()) {
+            bail!("Invalid settings: scopes must not contain empty scopes");
+        }
+        for (name, value) in &self.default_headers {
+            HeaderName::from_bytes(name.as_bytes()).with_context(|| {
+                format!(
+                    "Invalid settings: default_headers contains an invalid header name '{}'",
+                    name
+                )
+            })?;
+            HeaderValue::from_str(value).with_context(|| {
+                format!(
+                    "Invalid settings: default_headers.{} is not a valid header value",
+                    name
+                )
+            })?;
+        }
+        if let Some(idempotency_keys) = &self.idempotency_keys {
+            HeaderName::from_bytes(idempotency_keys.header_name.as_bytes()).with_context(|| {
+                format!(
+                    "Invalid settings: idempotency_keys.header_name '{}' is not a valid header name",
+                    idempotency_keys.header_name
+                )
+            })?;
+        }
+        if let Some(introspection_url) = &self.introspection_url {
+            Url::parse(introspection_url).with_context(|| {
+                format!(
+                    "Invalid settings: introspection_url '{}' is not a valid url",
+                    introspection_url
+                )
+            })?;
+        }
+        if let Some(revocation_url) = &self.revocation_url {
+            let parsed = Url::parse(revocation_url).with_context(|| {
+                format!(
+                    "Invalid settings: revocation_url '{}' is not a valid url",
+                    revocation_url
+                )
+            })?;
+            // Required by RFC 7009
+            if parsed.scheme() != "https" {
+                bail!(
+                    "Invalid settings: revocation_url '{}' must be an https url",
+                    revocation_url
+                );
+            }
+        }
+        if let Some(base_url) = &self.base_url {
+            let parsed = Url::parse(base_url).with_context(|| {
+                format!(
+                    "Invalid settings: base_url '{}' is not a valid url",
+                    base_url
+                )
+            })?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                bail!(
+                    "Invalid settings: base_url '{}' must be an http or https url",
+                    base_url
+                );
+            }
+        }
+        if let Some(health_check_url) = &self.health_check_url {
+            let parsed = Url::parse(health_check_url).with_context(|| {
+                format!(
+                    "Invalid settings: health_check_url '{}' is not a valid url",
+                    health_check_url
+                )
+            })?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                bail!(
+                    "Invalid settings: health_check_url '{}' must be an http or https url",
+                    health_check_url
+                );
+            }
+        }
+        if let Some(allowed_hosts) = &self.allowed_hosts {
+            if allowed_hosts.iter().any(|host| host.trim().is_empty()) {
+                bail!("Invalid settings: allowed_hosts must not contain an empty host");
+            }
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            if !rate_limit.requests_per_second.is_finite() || rate_limit.requests_per_second <= 0.0
+            {
+                bail!("Invalid settings: rate_limit.requests_per_second must be a positive number");
+            }
+            if rate_limit.burst == 0 {
+                bail!("Invalid settings: rate_limit.burst must be at least 1");
+            }
+        }
+        if self.default_token_lifetime == Some(Duration::ZERO) {
+            bail!("Invalid settings: default_token_lifetime must be longer than zero");
+        }
+        if self.hedge_after == Some(Duration::ZERO) {
+            bail!("Invalid settings: hedge_after must be longer than zero");
+        }
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            if circuit_breaker.failure_threshold == 0 {
+                bail!("Invalid settings: circuit_breaker.failure_threshold must be at least 1");
+            }
+            if circuit_breaker.half_open_probes == 0 {
+                bail!("Invalid settings: circuit_breaker.half_open_probes must be at least 1");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn required_env_var(prefix: &str, name: &str) -> Result<String> {
+    let key = format!("{}{}", prefix, name);
+    env::var(&key).with_context(|| format!("Failed to read environment variable {}", key))
+}
+
+/// Builder for [Settings](Settings)
+///
+/// ```
+///# fn doc_test() -> anyhow::Result<()> {
+/// use authorized_client::Settings;
+///
+/// let settings = Settings::builder()
+///     .client_id("xxxxxxxxxx")
+///     .client_secret("xxxxxxxxxx")
+///     .token_url("https://authorization-server.com/token")
+///     .scope("profile")
+///     .scope("email")
+///     .build()?;
+///# Ok(())
+///# }
+/// ```
+#[derive(Clone, Default)]
+pub struct SettingsBuilder {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    token_url: Option<String>,
+    scopes: Vec<String>,
+    issuer_url: Option<String>,
+    introspection_url: Option<String>,
+    revocation_url: Option<String>,
+    base_url: Option<String>,
+    health_check_url: Option<String>,
+    allowed_hosts: Option<Vec<String>>,
+    default_headers: HashMap<String, String>,
+    grant_type: GrantType,
+    client_auth_method: ClientAuthMethod,
+    auth_type: AuthType,
+    dpop: bool,
+    extra_token_params: HashMap<String, String>,
+    client_identity: Option<ClientIdentity>,
+    ca_bundle: Option<String>,
+    danger_accept_invalid_certs: bool,
+    min_tls_version: Option<TlsVersion>,
+    proxy: Option<ProxySettings>,
+    connection_pool: ConnectionPoolSettings,
+    request_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    token_exchange_timeout: Option<Duration>,
+    redirect_policy: RedirectPolicy,
+    token_retry_policy: RetryPolicy,
+    retry_policy: RetryPolicy,
+    max_auth_retries: Option<u8>,
+    auth_retry_backoff: Option<Backoff>,
+    max_download_resumes: Option<u8>,
+    download_resume_backoff: Option<Backoff>,
+    hedge_after: Option<Duration>,
+    rate_limit: Option<RateLimit>,
+    circuit_breaker: Option<CircuitBreakerSettings>,
+    deduplicate_gets: bool,
+    response_cache: Option<ResponseCacheSettings>,
+    wire_log: Option<WireLogSettings>,
+    cassette: Option<CassetteSettings>,
+    accept_any_content_type: bool,
+    max_response_size: Option<usize>,
+    idempotency_keys: Option<IdempotencyKeySettings>,
+    refresh_leeway: Option<Duration>,
+    default_token_lifetime: Option<Duration>,
+    background_refresh: bool,
+    serve_stale: Duration,
+    refresh_cooldown: Option<Duration>,
+    retry_insufficient_scope: bool,
+}
+
+impl SettingsBuilder {
+    pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    pub fn client_secret(mut self, client_secret: impl Into<String>) -> Self {
+        self.client_secret = Some(client_secret.into());
+        self
+    }
+
+    pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    /// Add a single scope
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Add multiple scopes
+    pub fn scopes<S>(mut self, scopes: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.scopes.extend(scopes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Discover the token, introspection and revocation endpoints from the metadata of this issuer when they are not set
+    pub fn issuer_url(mut self, issuer_url: impl Into<String>) -> Self {
+        self.issuer_url = Some(issuer_url.into());
+        self
+    }
+
+    /// The token introspection endpoint, used by [introspect_token](crate::AuthorizedClient::introspect_token)
+    pub fn introspection_url(mut self, introspection_url: impl Into<String>) -> Self {
+        self.introspection_url = Some(introspection_url.into());
+        self
+    }
+
+    /// The token revocation endpoint, used by [revoke_token](crate::AuthorizedClient::revoke_token)
+    pub fn revocation_url(mut self, revocation_url: impl Into<String>) -> Self {
+        self.revocation_url = Some(revocation_url.into());
+        self
+    }
+
+    /// The url the paths passed to the `*_path` methods are relative to, e.g. `https://api.example.com/v1`
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// An endpoint which is called by [health_check](crate::AuthorizedClient::health_check), any `2xx` response is healthy
+    pub fn health_check_url(mut self, health_check_url: impl Into<String>) -> Self {
+        self.health_check_url = Some(health_check_url.into());
+        self
+    }
+
+    /// Only send the bearer token to these hosts, e.g. `api.example.com` or `*.example.com` for every subdomain
+    pub fn allowed_hosts<S>(mut self, allowed_hosts: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.allowed_hosts
+            .get_or_insert_with(Vec::new)
+            .extend(allowed_hosts.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a header to every request to an endpoint, e.g. `User-Agent`, headers set on the request itself take precedence
+    pub fn default_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// The OAuth 2.0 grant used to get a bearer token, defaults to [ClientCredentials](GrantType::ClientCredentials)
+    pub fn grant_type(mut self, grant_type: GrantType) -> Self {
+        self.grant_type = grant_type;
+        self
+    }
+
+    /// How the client authenticates itself at the token endpoint, defaults to [ClientSecret](ClientAuthMethod::ClientSecret)
+    pub fn client_auth_method(mut self, client_auth_method: ClientAuthMethod) -> Self {
+        self.client_auth_method = client_auth_method;
+        self
+    }
+
+    /// How the client id and client secret are sent to the token endpoint, defaults to [BasicAuth](AuthType::BasicAuth)
+    pub fn auth_type(mut self, auth_type: AuthType) -> Self {
+        self.auth_type = auth_type;
+        self
+    }
+
+    /// Bind the bearer tokens to a key pair of the client using DPoP, see: [dpop](Settings::dpop)
+    pub fn dpop(mut self, dpop: bool) -> Self {
+        self.dpop = dpop;
+        self
+    }
+
+    /// Add an extra parameter to every token request, e.g. `audience` for Auth0 or `resource` for Azure
+    pub fn extra_token_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_token_params.insert(name.into(), value.into());
+        self
+    }
+
+    /// The TLS client identity used for mutual TLS
+    pub fn client_identity(mut self, client_identity: ClientIdentity) -> Self {
+        self.client_identity = Some(client_identity);
+        self
+    }
+
+    /// PEM encoded root certificates which are trusted on top of the system root certificates
+    pub fn ca_bundle(mut self, ca_bundle: impl Into<String>) -> Self {
+        self.ca_bundle = Some(ca_bundle.into());
+        self
+    }
+
+    /// Accept invalid TLS certificates, only use this for testing
+    pub fn danger_accept_invalid_certs(mut self, danger_accept_invalid_certs: bool) -> Self {
+        self.danger_accept_invalid_certs = danger_accept_invalid_certs;
+        self
+    }
+
+    /// The minimum TLS version which is accepted
+    pub fn min_tls_version(mut self, min_tls_version: TlsVersion) -> Self {
+        self.min_tls_version = Some(min_tls_version);
+        self
+    }
+
+    /// Send all requests through this proxy, by default the proxy environment variables are used
+    pub fn proxy(mut self, proxy: ProxySettings) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// How the connections to the endpoints are pooled and kept alive
+    pub fn connection_pool(mut self, connection_pool: ConnectionPoolSettings) -> Self {
+        self.connection_pool = connection_pool;
+        self
+    }
+
+    /// The maximum duration of a request to an endpoint, from connecting until the response body has been read
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// The maximum duration to establish a connection
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// The maximum duration of a request to the token endpoint
+    pub fn token_exchange_timeout(mut self, token_exchange_timeout: Duration) -> Self {
+        self.token_exchange_timeout = Some(token_exchange_timeout);
+        self
+    }
+
+    /// How redirects of the endpoints are followed, see: [RedirectPolicy](RedirectPolicy)
+    pub fn redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// How token exchanges which failed because of a connection error or timeout are retried, by default they are not retried
+    pub fn token_retry_policy(mut self, token_retry_policy: RetryPolicy) -> Self {
+        self.token_retry_policy = token_retry_policy;
+        self
+    }
+
+    /// How requests which failed because of a transient error are retried, by default they are not retried
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// The number of times the request is retried with a new bearer token after a `401 Unauthorized` response, defaults to 3
+    pub fn max_auth_retries(mut self, max_auth_retries: u8) -> Self {
+        self.max_auth_retries = Some(max_auth_retries);
+        self
+    }
+
+    /// The time to wait before retrying a request after a `401 Unauthorized` response, see: [auth_retry_backoff](Settings::auth_retry_backoff)
+    pub fn auth_retry_backoff(mut self, auth_retry_backoff: Backoff) -> Self {
+        self.auth_retry_backoff = Some(auth_retry_backoff);
+        self
+    }
+
+    /// The number of times an interrupted download is resumed, defaults to 3
+    pub fn max_download_resumes(mut self, max_download_resumes: u8) -> Self {
+        self.max_download_resumes = Some(max_download_resumes);
+        self
+    }
+
+    /// The time to wait before resuming an interrupted download, see: [download_resume_backoff](Settings::download_resume_backoff)
+    pub fn download_resume_backoff(mut self, download_resume_backoff: Backoff) -> Self {
+        self.download_resume_backoff = Some(download_resume_backoff);
+        self
+    }
+
+    /// Send a get or head request again when there's no response within `hedge_after`, by default requests are not hedged
+    pub fn hedge_after(mut self, hedge_after: Duration) -> Self {
+        self.hedge_after = Some(hedge_after);
+        self
+    }
+
+    /// Limit the number of requests to the endpoints, every attempt counts as a request
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Fail immediately while the endpoints keep failing, see: [CircuitBreakerSettings](CircuitBreakerSettings)
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreakerSettings) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Coalesce concurrent get requests to the same url into one request, the callers share the response
+    ///
+    /// This applies to [get](crate::AuthorizedClient::get), [get_plain_text](crate::AuthorizedClient::get_plain_text) and [get_bytes](crate::AuthorizedClient::get_bytes).
+    pub fn deduplicate_gets(mut self, deduplicate_gets: bool) -> Self {
+        self.deduplicate_gets = deduplicate_gets;
+        self
+    }
+
+    /// Cache the responses of get requests using their `ETag` and `Cache-Control` headers, see: [ResponseCacheSettings](ResponseCacheSettings)
+    ///
+    /// This applies to [get](crate::AuthorizedClient::get), [get_plain_text](crate::AuthorizedClient::get_plain_text) and [get_bytes](crate::AuthorizedClient::get_bytes).
+    pub fn response_cache(mut self, response_cache: ResponseCacheSettings) -> Self {
+        self.response_cache = Some(response_cache);
+        self
+    }
+
+    /// Log the requests to the endpoints and their responses at `debug` level, see: [WireLogSettings](WireLogSettings)
+    pub fn wire_log(mut self, wire_log: WireLogSettings) -> Self {
+        self.wire_log = Some(wire_log);
+        self
+    }
+
+    /// Record the requests and responses to a file or replay them, see: [CassetteSettings](CassetteSettings)
+    pub fn cassette(mut self, cassette: CassetteSettings) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Deserialize json bodies regardless of their `Content-Type`, for servers which send json as e.g. `text/plain`
+    pub fn accept_any_content_type(mut self, accept_any_content_type: bool) -> Self {
+        self.accept_any_content_type = accept_any_content_type;
+        self
+    }
+
+    /// The maximum size of a response body in bytes, a larger body fails with a [ResponseTooLargeError](crate::ResponseTooLargeError)
+    pub fn max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = Some(max_response_size);
+        self
+    }
+
+    /// Attach an idempotency key to post and patch requests, see: [IdempotencyKeySettings](IdempotencyKeySettings)
+    pub fn idempotency_keys(mut self, idempotency_keys: IdempotencyKeySettings) -> Self {
+        self.idempotency_keys = Some(idempotency_keys);
+        self
+    }
+
+    /// Refresh the bearer token when it expires within `refresh_leeway`, defaults to [DEFAULT_REFRESH_LEEWAY](DEFAULT_REFRESH_LEEWAY)
+    pub fn refresh_leeway(mut self, refresh_leeway: Duration) -> Self {
+        self.refresh_leeway = Some(refresh_leeway);
+        self
+    }
+
+    /// The lifetime of a bearer token when the token response doesn't contain `expires_in`, by default such a response fails
+    pub fn default_token_lifetime(mut self, default_token_lifetime: Duration) -> Self {
+        self.default_token_lifetime = Some(default_token_lifetime);
+        self
+    }
+
+    /// Refresh the bearer token in a background task, this way request latency never includes a token exchange
+    ///
+    /// The task is stopped when the last clone of the `AuthorizedClient` is dropped.
+    pub fn background_refresh(mut self, background_refresh: bool) -> Self {
+        self.background_refresh = background_refresh;
+        self
+    }
+
+    /// Keep using the current bearer token for up to `serve_stale` after it expired when a new one can't be fetched
+    pub fn serve_stale(mut self, serve_stale: Duration) -> Self {
+        self.serve_stale = serve_stale;
+        self
+    }
+
+    /// Don't request a new bearer token for `refresh_cooldown` after a failed refresh, defaults to [DEFAULT_REFRESH_COOLDOWN](DEFAULT_REFRESH_COOLDOWN)
+    pub fn refresh_cooldown(mut self, refresh_cooldown: Duration) -> Self {
+        self.refresh_cooldown = Some(refresh_cooldown);
+        self
+    }
+
+    /// Retry a request once with a bearer token for the scopes a `403 Forbidden` response with an `insufficient_scope` error asks for
+    pub fn retry_insufficient_scope(mut self, retry_insufficient_scope: bool) -> Self {
+        self.retry_insufficient_scope = retry_insufficient_scope;
+        self
+    }
+
+    /// Build and validate the `Settings`
+    pub fn build(self) -> Result<Settings> {
+        let settings = Settings {
+            client_id: self
+                .client_id
+                .context("Invalid settings: client_id is missing")?,
+            client_secret: match (
+                self.client_secret,
+                &self.grant_type,
+                &self.client_auth_method,
+            ) {
+                (Some(client_secret), _, _) => SecretString::new(client_secret),
+                // Public clients don't have a client secret and a client assertion replaces the client secret
+                (
+                    None,
+                    GrantType::Password { .. }
+                    | GrantType::DeviceCode { .. }
+                    | GrantType::TokenExchange { .. },
+                    _,
+                )
+                | (None, _, ClientAuthMethod::PrivateKeyJwt { .. }) => SecretString::default(),
+                (None, GrantType::ClientCredentials, ClientAuthMethod::ClientSecret) => {
+                    bail!("Invalid settings: client_secret is missing")
+                }
+            },
+            token_url: match (self.token_url, &self.issuer_url) {
+                (Some(token_url), _) => token_url,
+                // The token url is discovered when connecting
+                (None, Some(_)) => String::new(),
+                (None, None) => bail!("Invalid settings: token_url is missing"),
+            },
+            scopes: self.scopes,
+            issuer_url: self.issuer_url,
+            introspection_url: self.introspection_url,
+            revocation_url: self.revocation_url,
+            base_url: self.base_url,
+            health_check_url: self.health_check_url,
+            allowed_hosts: self.allowed_hosts,
+            default_headers: self.default_headers,
+            grant_type: self.grant_type,
+            client_auth_method: self.client_auth_method,
+            auth_type: self.auth_type,
+            dpop: self.dpop,
+            extra_token_params: self.extra_token_params,
+            client_identity: self.client_identity,
+            ca_bundle: self.ca_bundle,
+            danger_accept_invalid_certs: self.danger_accept_invalid_certs,
+            min_tls_version: self.min_tls_version,
+            proxy: self.proxy,
+            connection_pool: self.connection_pool,
+            request_timeout: self.request_timeout,
+            connect_timeout: self.connect_timeout,
+            token_exchange_timeout: self.token_exchange_timeout,
+            redirect_policy: self.redirect_policy,
+            token_retry_policy: self.token_retry_policy,
+            retry_policy: self.retry_policy,
+            max_auth_retries: self
+                .max_auth_retries
+                .unwrap_or_else(default_max_auth_retries),
+            auth_retry_backoff: self
+                .auth_retry_backoff
+                .unwrap_or_else(default_auth_retry_backoff),
+            max_download_resumes: self
+                .max_download_resumes
+                .unwrap_or_else(default_max_download_resumes),
+            download_resume_backoff: self
+                .download_resume_backoff
+                .unwrap_or_else(default_download_resume_backoff),
+            hedge_after: self.hedge_after,
+            rate_limit: self.rate_limit,
+            circuit_breaker: self.circuit_breaker,
+            deduplicate_gets: self.deduplicate_gets,
+            response_cache: self.response_cache,
+            wire_log: self.wire_log,
+            cassette: self.cassette,
+            accept_any_content_type: self.accept_any_content_type,
+            max_response_size: self.max_response_size,
+            idempotency_keys: self.idempotency_keys,
+            refresh_leeway: self.refresh_leeway.unwrap_or(DEFAULT_REFRESH_LEEWAY),
+            default_token_lifetime: self.default_token_lifetime,
+            background_refresh: self.background_refresh,
+            serve_stale: self.serve_stale,
+            refresh_cooldown: self.refresh_cooldown.unwrap_or(DEFAULT_REFRESH_COOLDOWN),
+            retry_insufficient_scope: self.retry_insufficient_scope,
+        };
+
+        settings.validate()?;
+
+        Ok(settings)
+    }
 }
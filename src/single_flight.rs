@@ -0,0 +1,59 @@
+use crate::response_cache::SharedResponse;
+use crate::status_error::StatusError;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+type SharedResult = Result<SharedResponse, Arc<anyhow::Error>>;
+
+/// Coalesces concurrent identical requests into one request, shared by all clones of an `AuthorizedClient`
+#[derive(Default)]
+pub(crate) struct SingleFlight {
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<SharedResult>>>>,
+}
+
+impl SingleFlight {
+    pub(crate) fn new() -> Self {
+        SingleFlight::default()
+    }
+
+    /// Run `fetch` unless a request with the same `key` is already in flight, in that case wait for its result
+    ///
+    /// When the caller running `fetch` is cancelled one of the waiting callers takes over.
+    pub(crate) async fn run<F, Fut>(&self, key: String, fetch: F) -> Result<SharedResponse>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<SharedResponse>>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let result = cell
+            .get_or_init(|| async { fetch().await.map_err(Arc::new) })
+            .await
+            .clone();
+
+        // The first caller to finish removes the request, later calls make a new request
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(&key)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(&key);
+        }
+        drop(in_flight);
+
+        // Errors can't be cloned, keep the status error so callers can still inspect the response
+        result.map_err(|error| match error.downcast_ref::<StatusError>() {
+            Some(status_error) => status_error.clone().into(),
+            None => anyhow!("{:#}", error),
+        })
+    }
+}
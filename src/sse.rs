@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The delay before reconnecting when the server didn't send a `retry` field
+pub(crate) const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// An event received from a server-sent events stream, see: [sse](crate::AuthorizedClient::sse)
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// The id of the last event which had one, it's sent as `Last-Event-ID` when reconnecting
+    pub id: Option<String>,
+    /// The event type, `message` when the server didn't send one
+    pub event: String,
+    pub data: String,
+}
+
+/// Parses a `text/event-stream` body, see: [the specification](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation)
+#[derive(Default)]
+pub(crate) struct EventParser {
+    // Bytes of a line which hasn't been completed yet, a chunk can end in the middle of a character
+    line: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+    pub(crate) last_event_id: Option<String>,
+    pub(crate) retry: Option<Duration>,
+    events: VecDeque<Event>,
+}
+
+impl EventParser {
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' {
+                let mut line = std::mem::take(&mut self.line);
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                self.process_line(&String::from_utf8_lossy(&line));
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    pub(crate) fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// Discard the incomplete event after the connection dropped, the last event id and retry are kept
+    pub(crate) fn reset(&mut self) {
+        self.line.clear();
+        self.event = None;
+        self.data = None;
+        self.events.clear();
+    }
+
+    fn process_line(&mut self, line: &str) {
+        // An empty line dispatches the event
+        if line.is_empty() {
+            let event = self.event.take();
+            if let Some(mut data) = self.data.take() {
+                if data.ends_with('\n') {
+                    data.pop();
+                }
+                self.events.push_back(Event {
+                    id: self.last_event_id.clone(),
+                    event: event.unwrap_or_else(|| "message".to_string()),
+                    data,
+                });
+            }
+            return;
+        }
+
+        // Lines starting with a colon are comments, e.g. keep-alives
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                let data = self.data.get_or_insert_with(String::new);
+                data.push_str(value);
+                data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(Duration::from_millis(retry));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: &str) -> (EventParser, Vec<Event>) {
+        let mut parser = EventParser::default();
+        parser.feed(body.as_bytes());
+        let events = std::iter::from_fn(|| parser.next_event()).collect();
+        (parser, events)
+    }
+
+    #[test]
+    fn parses_the_fields_of_an_event() {
+        let (parser, events) = parse("id: 7\nevent: update\nretry: 1500\ndata: {\"a\":1}\n\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+        assert_eq!(events[0].event, "update");
+        assert_eq!(events[0].data, "{\"a\":1}");
+        assert_eq!(parser.retry, Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn joins_multiline_data_with_newlines() {
+        let (_, events) = parse("data: first\ndata:second\r\ndata\n\n");
+
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].data, "first\nsecond\n");
+    }
+
+    #[test]
+    fn ignores_comments_and_unknown_fields() {
+        let (parser, events) = parse(": keep-alive\nunknown: value\nretry: soon\n\n:\ndata: x\n\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "x");
+        assert_eq!(parser.retry, None);
+    }
+
+    #[test]
+    fn the_last_event_id_is_kept_for_the_following_events() {
+        let (parser, events) = parse("id: 1\ndata: a\n\ndata: b\n\nid: bad\0id\ndata: c\n\n");
+
+        let ids: Vec<_> = events.iter().map(|event| event.id.as_deref()).collect();
+        assert_eq!(ids, vec![Some("1"), Some("1"), Some("1")]);
+        assert_eq!(parser.last_event_id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn an_event_without_data_is_not_dispatched() {
+        let (_, events) = parse("event: ping\n\ndata: x\n\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "message");
+    }
+
+    #[test]
+    fn lines_and_characters_can_be_split_across_chunks() {
+        let body = "data: caf\u{e9}\r\n\r\n".as_bytes();
+        let mut parser = EventParser::default();
+        for byte in body {
+            parser.feed(&[*byte]);
+        }
+
+        assert_eq!(parser.next_event().unwrap().data, "caf\u{e9}");
+    }
+
+    #[test]
+    fn reset_discards_the_incomplete_event() {
+        let mut parser = EventParser::default();
+        parser.feed(b"id: 3\ndata: partial\nda");
+        parser.reset();
+        parser.feed(b"data: next\n\n");
+
+        let event = parser.next_event().unwrap();
+        assert_eq!(event.data, "next");
+        assert_eq!(event.id.as_deref(), Some("3"));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::deserialize_error::body_snippet;
+use crate::www_authenticate::BearerChallenge;
+use oauth2::http::StatusCode;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+
+/// The error returned when the server responds with an unsupported status code
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the response the server sent back.
+#[derive(Clone, Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// The complete body, the error message only contains the first 512 bytes
+    pub body: String,
+}
+
+impl StatusError {
+    /// Deserialize the json body of the error response
+    pub fn json<E>(&self) -> serde_json::Result<E>
+    where
+        E: for<'de> Deserialize<'de>,
+    {
+        serde_json::from_str(&self.body)
+    }
+}
+
+impl Display for StatusError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Unsupported status code (CODE={})", self.status.as_u16())?;
+        if !self.body.is_empty() {
+            let snippet = body_snippet(self.body.as_bytes());
+            write!(f, ": {}", snippet)?;
+            if snippet.len() < self.body.len() {
+                write!(f, "... ({} bytes)", self.body.len())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for StatusError {}
+
+/// The error returned when the server keeps rejecting the bearer token with `401 Unauthorized`
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the reason the server gave in the `WWW-Authenticate` header.
+#[derive(Clone, Debug)]
+pub struct UnauthorizedError {
+    /// The number of times the bearer token was refreshed
+    pub retries: u8,
+    /// The challenge of the last response, `None` when the server didn't send one
+    pub challenge: Option<BearerChallenge>,
+}
+
+impl Display for UnauthorizedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to authenticate, retries = {}", self.retries)?;
+        if let Some(challenge) = &self.challenge {
+            write!(f, ": {}", challenge)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for UnauthorizedError {}
+
+/// The error returned by the `*_with_error` methods when the error response could be deserialized
+///
+/// Use `anyhow::Error::downcast_ref` to get access to the deserialized error.
+#[derive(Debug)]
+pub struct ApiError<E> {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub error: E,
+}
+
+impl<E: Debug> Display for ApiError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unsupported status code (CODE={}): {:?}",
+            self.status.as_u16(),
+            self.error
+        )
+    }
+}
+
+impl<E: Debug> Error for ApiError<E> {}
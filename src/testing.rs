@@ -0,0 +1,301 @@
+use anyhow::{Context, Result};
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::warn;
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use url::form_urlencoded;
+
+/// A local auth server for integration tests, both of this library and of code which uses an `AuthorizedClient`
+///
+/// The token endpoint is served at [token_url](MockTokenServer::token_url), it accepts the client credentials, password and refresh token grants.
+/// Every other path is a protected endpoint which returns `{}` for a valid bearer token and `401 Unauthorized` otherwise.
+/// The server is stopped when the `MockTokenServer` is dropped.
+///
+/// ```
+///# async fn doc_test() -> anyhow::Result<()> {
+/// use authorized_client::testing::MockTokenServer;
+/// use authorized_client::{AuthorizedClient, Settings};
+/// use std::time::Duration;
+///
+/// let server = MockTokenServer::start().await?;
+/// server.set_expires_in(Duration::from_secs(60));
+///
+/// let settings = Settings::builder()
+///     .client_id("xxxxxxxxxx")
+///     .client_secret("xxxxxxxxxx")
+///     .token_url(server.token_url())
+///     .build()?;
+/// let client = AuthorizedClient::connect(settings).await?;
+///
+/// // The server rejects the current token, the client has to get a new one
+/// server.revoke_tokens();
+/// let _: serde_json::Value = client.get(server.url("/info")?).await?;
+/// assert_eq!(server.token_requests(), 2);
+///# Ok(())
+///# }
+/// ```
+pub struct MockTokenServer {
+    address: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+    handle: JoinHandle<()>,
+}
+
+struct ServerState {
+    expires_in: Duration,
+    allowed_scopes: Option<Vec<String>>,
+    issue_refresh_tokens: bool,
+    reject_tokens: bool,
+    failures: VecDeque<StatusCode>,
+    access_tokens: Vec<IssuedToken>,
+    refresh_tokens: HashSet<String>,
+    token_requests: usize,
+}
+
+struct IssuedToken {
+    access_token: String,
+    expires_at: Instant,
+    revoked: bool,
+}
+
+impl MockTokenServer {
+    /// Start the server on a random local port, this requires a tokio runtime
+    pub async fn start() -> Result<Self> {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").context("Failed to bind the mock token server")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(ServerState {
+            expires_in: Duration::from_secs(3600),
+            allowed_scopes: None,
+            issue_refresh_tokens: false,
+            reject_tokens: false,
+            failures: VecDeque::new(),
+            access_tokens: Vec::new(),
+            refresh_tokens: HashSet::new(),
+            token_requests: 0,
+        }));
+
+        let service_state = state.clone();
+        let server = Server::from_tcp(listener)?.serve(make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_request(state.clone(), request)
+                }))
+            }
+        }));
+        let handle = tokio::spawn(async move {
+            if let Err(error) = server.await {
+                warn!("The mock token server stopped: {}", error);
+            }
+        });
+
+        Ok(MockTokenServer {
+            address,
+            state,
+            handle,
+        })
+    }
+
+    /// The url of the token endpoint
+    pub fn token_url(&self) -> String {
+        format!("http://{}/token", self.address)
+    }
+
+    /// The url of a protected endpoint
+    pub fn url(&self, path: &str) -> Result<url::Url> {
+        url::Url::parse(&format!("http://{}", self.address))?
+            .join(path)
+            .context("Invalid path")
+    }
+
+    /// The lifetime of the tokens which are issued from now on, defaults to an hour
+    pub fn set_expires_in(&self, expires_in: Duration) {
+        self.state.lock().unwrap().expires_in = expires_in;
+    }
+
+    /// Only allow these scopes, a token request with another scope fails with `invalid_scope`
+    pub fn set_allowed_scopes<S>(&self, scopes: impl IntoIterator<Item = S>)
+    where
+        S: Into<String>,
+    {
+        self.state.lock().unwrap().allowed_scopes =
+            Some(scopes.into_iter().map(Into::into).collect());
+    }
+
+    /// Issue a refresh token with every access token, a refresh token can be used once
+    pub fn set_issue_refresh_tokens(&self, issue_refresh_tokens: bool) {
+        self.state.lock().unwrap().issue_refresh_tokens = issue_refresh_tokens;
+    }
+
+    /// Fail the next `count` token requests with `status`
+    pub fn fail_next_token_requests(&self, count: usize, status: StatusCode) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .extend(std::iter::repeat_n(status, count));
+    }
+
+    /// Reject every token on the protected endpoints, including the tokens which are issued from now on
+    pub fn set_reject_tokens(&self, reject_tokens: bool) {
+        self.state.lock().unwrap().reject_tokens = reject_tokens;
+    }
+
+    /// Reject every token which has been issued so far, as if they were revoked on the server side
+    pub fn revoke_tokens(&self) {
+        for token in &mut self.state.lock().unwrap().access_tokens {
+            token.revoked = true;
+        }
+    }
+
+    /// The number of requests to the token endpoint, including the failed ones
+    pub fn token_requests(&self) -> usize {
+        self.state.lock().unwrap().token_requests
+    }
+
+    /// The access tokens which have been issued, in order
+    pub fn issued_tokens(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .access_tokens
+            .iter()
+            .map(|token| token.access_token.clone())
+            .collect()
+    }
+}
+
+impl Drop for MockTokenServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_request(
+    state: Arc<Mutex<ServerState>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if request.uri().path() == "/token" && request.method() == Method::POST {
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .unwrap_or_default();
+        let params: Vec<(String, String)> = form_urlencoded::parse(&body).into_owned().collect();
+        return Ok(state.lock().unwrap().token_response(&params));
+    }
+
+    let access_token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let authorized = access_token.is_some_and(|access_token| {
+        let state = state.lock().unwrap();
+        !state.reject_tokens
+            && state.access_tokens.iter().any(|token| {
+                token.access_token == access_token
+                    && !token.revoked
+                    && token.expires_at > Instant::now()
+            })
+    });
+
+    if authorized {
+        Ok(json_response(StatusCode::OK, serde_json::json!({})))
+    } else {
+        let mut response = json_response(
+            StatusCode::UNAUTHORIZED,
+            serde_json::json!({ "error": "invalid_token" }),
+        );
+        response.headers_mut().insert(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static("Bearer error=\"invalid_token\""),
+        );
+        Ok(response)
+    }
+}
+
+impl ServerState {
+    fn token_response(&mut self, params: &[(String, String)]) -> Response<Body> {
+        self.token_requests += 1;
+
+        if let Some(status) = self.failures.pop_front() {
+            return json_response(status, serde_json::json!({ "error": "server_error" }));
+        }
+
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        match param("grant_type") {
+            Some("client_credentials") | Some("password") => {}
+            Some("refresh_token") => {
+                // Refresh tokens are rotated, every refresh token can only be used once
+                let refresh_token = param("refresh_token").unwrap_or_default();
+                if !self.refresh_tokens.remove(refresh_token) {
+                    return error_response("invalid_grant");
+                }
+            }
+            _ => return error_response("unsupported_grant_type"),
+        }
+
+        let scope = param("scope");
+        if let (Some(allowed_scopes), Some(scope)) = (&self.allowed_scopes, scope) {
+            if scope
+                .split_whitespace()
+                .any(|scope| !allowed_scopes.iter().any(|allowed| allowed == scope))
+            {
+                return error_response("invalid_scope");
+            }
+        }
+
+        let count = self.access_tokens.len() + 1;
+        let access_token = format!("access-token-{}", count);
+        self.access_tokens.push(IssuedToken {
+            access_token: access_token.clone(),
+            expires_at: Instant::now() + self.expires_in,
+            revoked: false,
+        });
+
+        let mut body = serde_json::json!({
+            "access_token": access_token,
+            "token_type": "bearer",
+            "expires_in": self.expires_in.as_secs(),
+        });
+        if let Some(scope) = scope {
+            body["scope"] = scope.into();
+        }
+        if self.issue_refresh_tokens {
+            let refresh_token = format!("refresh-token-{}", count);
+            self.refresh_tokens.insert(refresh_token.clone());
+            body["refresh_token"] = refresh_token.into();
+        }
+
+        json_response(StatusCode::OK, body)
+    }
+}
+
+fn error_response(error: &str) -> Response<Body> {
+    json_response(
+        StatusCode::BAD_REQUEST,
+        serde_json::json!({ "error": error }),
+    )
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
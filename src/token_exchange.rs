@@ -0,0 +1,110 @@
+use crate::settings::{AuthType, ClientAuthMethod, GrantType, Settings};
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use oauth2::basic::BasicTokenResponse;
+use oauth2::http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use oauth2::http::{HeaderMap, HeaderValue, Method};
+use oauth2::{HttpRequest, HttpResponse};
+use url::{form_urlencoded, Url};
+
+/// The grant type of a token exchange ([RFC 8693](https://tools.ietf.org/html/rfc8693))
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+/// The token type of an OAuth 2.0 access token
+pub(crate) const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Build the token exchange request of a [TokenExchange](GrantType::TokenExchange) grant
+///
+/// The `oauth2` crate doesn't support this grant, so the request is built here and sent using the same http client as the other grants.
+pub(crate) fn token_exchange_request(
+    settings: &Settings,
+    extra_params: Vec<(String, String)>,
+) -> Result<HttpRequest> {
+    let (subject_token, subject_token_type, requested_token_type, audience) =
+        match &settings.grant_type {
+            GrantType::TokenExchange {
+                subject_token,
+                subject_token_type,
+                requested_token_type,
+                audience,
+            } => (
+                subject_token,
+                subject_token_type,
+                requested_token_type,
+                audience,
+            ),
+            _ => bail!("The settings don't use the token exchange grant"),
+        };
+
+    let mut params = form_urlencoded::Serializer::new(String::new());
+    params
+        .append_pair("grant_type", TOKEN_EXCHANGE_GRANT_TYPE)
+        .append_pair("subject_token", subject_token.expose_secret())
+        .append_pair("subject_token_type", subject_token_type);
+    if let Some(requested_token_type) = requested_token_type {
+        params.append_pair("requested_token_type", requested_token_type);
+    }
+    if let Some(audience) = audience {
+        params.append_pair("audience", audience);
+    }
+    if !settings.scopes.is_empty() {
+        params.append_pair("scope", &settings.scopes.join(" "));
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+
+    // Authenticate the same way the `oauth2` crate does for the other grants
+    let client_secret = Some(settings.client_secret.expose_secret())
+        .filter(|client_secret| !client_secret.is_empty())
+        .filter(|_| matches!(settings.client_auth_method, ClientAuthMethod::ClientSecret));
+    match (client_secret, settings.auth_type) {
+        (Some(client_secret), AuthType::BasicAuth) => {
+            let credentials = format!(
+                "{}:{}",
+                form_urlencoded::byte_serialize(settings.client_id.as_bytes()).collect::<String>(),
+                form_urlencoded::byte_serialize(client_secret.as_bytes()).collect::<String>()
+            );
+            headers.insert(
+                AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode(credentials)).parse()?,
+            );
+        }
+        (Some(client_secret), AuthType::RequestBody) => {
+            params
+                .append_pair("client_id", &settings.client_id)
+                .append_pair("client_secret", client_secret);
+        }
+        // Public clients and client assertions only send the client id
+        (None, _) => {
+            params.append_pair("client_id", &settings.client_id);
+        }
+    }
+    params.extend_pairs(extra_params);
+
+    Ok(HttpRequest {
+        url: Url::parse(&settings.token_url)
+            .with_context(|| format!("Invalid token url '{}'", settings.token_url))?,
+        method: Method::POST,
+        headers,
+        body: params.finish().into_bytes(),
+    })
+}
+
+/// Parse the response of a token exchange, the response has the same format as the one of the other grants
+pub(crate) fn token_exchange_response(response: HttpResponse) -> Result<BasicTokenResponse> {
+    if !response.status_code.is_success() {
+        bail!(
+            "The token exchange failed (CODE={}): {}",
+            response.status_code.as_u16(),
+            String::from_utf8_lossy(&response.body)
+        );
+    }
+
+    serde_json::from_slice(&response.body).context("Failed to parse the token exchange response")
+}
@@ -0,0 +1,39 @@
+use oauth2::basic::BasicTokenIntrospectionResponse;
+use oauth2::TokenIntrospectionResponse;
+use std::time::SystemTime;
+
+/// The information the auth server has about the bearer token ([RFC 7662](https://tools.ietf.org/html/rfc7662))
+///
+/// Only `active` is required, the auth server decides which other fields it returns.
+#[derive(Clone, Debug)]
+pub struct TokenIntrospection {
+    /// Whether the bearer token is currently active, the other fields are usually empty when it's not
+    pub active: bool,
+    pub scopes: Vec<String>,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Vec<String>,
+    pub expires_at: Option<SystemTime>,
+    pub issued_at: Option<SystemTime>,
+}
+
+impl TokenIntrospection {
+    pub(crate) fn from_response(response: &BasicTokenIntrospectionResponse) -> Self {
+        TokenIntrospection {
+            active: response.active(),
+            scopes: response
+                .scopes()
+                .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect())
+                .unwrap_or_default(),
+            client_id: response.client_id().map(|client_id| client_id.to_string()),
+            username: response.username().map(str::to_owned),
+            subject: response.sub().map(str::to_owned),
+            issuer: response.iss().map(str::to_owned),
+            audience: response.aud().cloned().unwrap_or_default(),
+            expires_at: response.exp().map(SystemTime::from),
+            issued_at: response.iat().map(SystemTime::from),
+        }
+    }
+}
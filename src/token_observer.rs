@@ -0,0 +1,33 @@
+use crate::secret::SecretString;
+use std::time::SystemTime;
+
+/// Callback which is called every time an `AuthorizedClient` got a new bearer token, e.g. for an audit log
+///
+/// Register an observer using [with_token_observer](crate::AuthorizedClient::with_token_observer).
+/// The observer is called for every bearer token which is requested after it's registered, including the refreshes in the background.
+/// It's called synchronously, keep it fast.
+///
+/// ```
+/// use authorized_client::{TokenObserver, TokenRefreshEvent};
+///
+/// struct AuditLog;
+///
+/// impl TokenObserver for AuditLog {
+///     fn on_token_refresh(&self, event: &TokenRefreshEvent) {
+///         println!("New bearer token for {:?}, expires at {:?}", event.scopes, event.expires_at);
+///     }
+/// }
+/// ```
+pub trait TokenObserver: Send + Sync {
+    fn on_token_refresh(&self, event: &TokenRefreshEvent);
+}
+
+/// A new bearer token, see: [TokenObserver](TokenObserver)
+#[derive(Clone, Debug)]
+pub struct TokenRefreshEvent {
+    pub expires_at: SystemTime,
+    /// The scopes the auth server granted, or the requested scopes when the token response doesn't mention them
+    pub scopes: Vec<String>,
+    /// The new bearer token, it's redacted unless it's exposed explicitly using [expose_secret](SecretString::expose_secret)
+    pub access_token: SecretString,
+}
@@ -0,0 +1,93 @@
+use crate::authorized_client::AuthorizedClient;
+use crate::clock::SystemClock;
+use crate::http_client::HttpClients;
+use crate::interceptor::BoxFuture;
+use crate::settings::Settings;
+use crate::transport::HttpTransport;
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A bearer token returned by a [TokenProvider](TokenProvider)
+#[derive(Clone)]
+pub struct Token {
+    pub access_token: String,
+    /// How long the bearer token stays valid
+    pub expires_in: Duration,
+}
+
+/// A source of bearer tokens, e.g. AWS Cognito, the GCP metadata server or a custom STS flow
+///
+/// Use it with [connect_with_token_provider](crate::AuthorizedClient::connect_with_token_provider).
+/// `token` is called when connecting and whenever the bearer token is (almost) expired or rejected by the server.
+///
+/// ```
+/// use authorized_client::{BoxFuture, Token, TokenProvider};
+/// use std::time::Duration;
+///
+/// struct MetadataServer {
+///     http_client: reqwest::Client,
+/// }
+///
+/// impl TokenProvider for MetadataServer {
+///     fn token(&self) -> BoxFuture<'_, anyhow::Result<Token>> {
+///         Box::pin(async move {
+///             let access_token = self
+///                 .http_client
+///                 .get("http://metadata.internal/token")
+///                 .send()
+///                 .await?
+///                 .text()
+///                 .await?;
+///             Ok(Token {
+///                 access_token,
+///                 expires_in: Duration::from_secs(300),
+///             })
+///         })
+///     }
+/// }
+/// ```
+pub trait TokenProvider: Send + Sync {
+    /// Get a new bearer token
+    fn token(&self) -> BoxFuture<'_, Result<Token>>;
+}
+
+/// Exchanges the client credentials (or username and password, or subject token) of the settings for a bearer token at the token endpoint
+///
+/// This is the exchange [connect](crate::AuthorizedClient::connect) uses, without the refresh tokens and token store.
+/// Use it to build a provider which falls back to or wraps the default exchange.
+pub struct OAuthTokenProvider {
+    settings: Settings,
+    transport: Arc<dyn HttpTransport>,
+}
+
+impl OAuthTokenProvider {
+    pub fn new(settings: Settings) -> Result<Self> {
+        settings.validate()?;
+        if settings.token_url.is_empty() {
+            bail!("Invalid settings: token_url is required, the endpoints of the issuer_url are only discovered when connecting");
+        }
+
+        let transport = HttpClients::new(&settings)?.token;
+        Ok(OAuthTokenProvider {
+            settings,
+            transport,
+        })
+    }
+}
+
+impl TokenProvider for OAuthTokenProvider {
+    fn token(&self) -> BoxFuture<'_, Result<Token>> {
+        Box::pin(async move {
+            let credentials =
+                AuthorizedClient::get_bearer_token(&self.settings, &*self.transport, &SystemClock)
+                    .await?;
+            Ok(Token {
+                access_token: credentials.access_token.expose_secret().to_string(),
+                expires_in: credentials
+                    .expires_at
+                    .saturating_duration_since(Instant::now()),
+            })
+        })
+    }
+}
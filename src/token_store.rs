@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A bearer token which can be saved in a [TokenStore](TokenStore)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredToken {
+    pub access_token: String,
+    pub expires_at: SystemTime,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+impl Debug for StoredToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoredToken")
+            .field("access_token", &"[redacted]")
+            .field("expires_at", &self.expires_at)
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "[redacted]"),
+            )
+            .finish()
+    }
+}
+
+/// Storage for the bearer token
+///
+/// Every new bearer token is saved in the store, when connecting a still valid token from the store is reused.
+/// This avoids a token exchange on every process start, e.g. for short-lived CLI invocations.
+pub trait TokenStore: Send + Sync {
+    /// Get the saved token, `None` when no token has been saved yet
+    fn get(&self) -> Result<Option<StoredToken>>;
+
+    /// Save a new token, this replaces the previous one
+    fn put(&self, token: &StoredToken) -> Result<()>;
+}
+
+/// Keeps the bearer token in memory, this is the default store
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    token: Mutex<Option<StoredToken>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn get(&self) -> Result<Option<StoredToken>> {
+        Ok(self.token.lock().unwrap().clone())
+    }
+
+    fn put(&self, token: &StoredToken) -> Result<()> {
+        *self.token.lock().unwrap() = Some(token.clone());
+        Ok(())
+    }
+}
+
+/// Saves the bearer token as json in a file
+///
+/// The file contains a secret, make sure it's stored in a location only the current user can read.
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileTokenStore { path: path.into() }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn get(&self) -> Result<Option<StoredToken>> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Failed to read token file {}", self.path.display()))
+            }
+        };
+
+        let token = serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse token file {}", self.path.display()))?;
+
+        Ok(Some(token))
+    }
+
+    fn put(&self, token: &StoredToken) -> Result<()> {
+        let content = serde_json::to_vec(token).context("Failed to serialize token")?;
+
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write token file {}", self.path.display()))
+    }
+}
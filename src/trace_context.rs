@@ -0,0 +1,93 @@
+use crate::interceptor::{BoxFuture, Interceptor};
+use anyhow::Result;
+use reqwest::header::HeaderValue;
+use reqwest::Request;
+
+/// The context of the current distributed trace, see: [W3C Trace Context](https://www.w3.org/TR/trace-context/)
+#[derive(Clone, Debug)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    /// The id of the span which makes the request, the server uses it as parent
+    pub span_id: u64,
+    pub sampled: bool,
+    /// Vendor specific trace information, sent as the `tracestate` header
+    pub trace_state: Option<String>,
+}
+
+impl TraceContext {
+    /// The value of the `traceparent` header
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            if self.sampled { 1 } else { 0 }
+        )
+    }
+}
+
+/// An interceptor which adds the `traceparent` and `tracestate` headers to every attempt
+///
+/// The trace context is read by `current_context` right before a request is sent,
+/// this is where the context of your tracing library, e.g. OpenTelemetry, is converted into a [TraceContext](TraceContext).
+/// When it returns `None` or an invalid context, no headers are added.
+///
+/// ```
+/// use authorized_client::{TraceContext, TraceContextPropagator};
+///
+/// let propagator = TraceContextPropagator::new(|| {
+///     Some(TraceContext {
+///         trace_id: 0x4bf92f3577b34da6a3ce929d0e0e4736,
+///         span_id: 0x00f067aa0ba902b7,
+///         sampled: true,
+///         trace_state: None,
+///     })
+/// });
+/// ```
+pub struct TraceContextPropagator<F> {
+    current_context: F,
+}
+
+impl<F> TraceContextPropagator<F>
+where
+    F: Fn() -> Option<TraceContext> + Send + Sync,
+{
+    pub fn new(current_context: F) -> Self {
+        TraceContextPropagator { current_context }
+    }
+}
+
+impl<F> Interceptor for TraceContextPropagator<F>
+where
+    F: Fn() -> Option<TraceContext> + Send + Sync,
+{
+    fn on_request<'a>(&'a self, request: &'a mut Request) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let context = match (self.current_context)() {
+                // All zero ids are invalid according to the specification
+                Some(context) if context.trace_id != 0 && context.span_id != 0 => context,
+                _ => return Ok(()),
+            };
+
+            let headers = request.headers_mut();
+            headers.insert(
+                "traceparent",
+                HeaderValue::from_str(&context.traceparent())?,
+            );
+            match context
+                .trace_state
+                .as_deref()
+                .filter(|trace_state| !trace_state.is_empty())
+            {
+                Some(trace_state) => {
+                    headers.insert("tracestate", HeaderValue::from_str(trace_state)?);
+                }
+                None => {
+                    headers.remove("tracestate");
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
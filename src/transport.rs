@@ -0,0 +1,20 @@
+use crate::interceptor::BoxFuture;
+use anyhow::Result;
+use reqwest::{Client, Request, Response};
+
+/// Sends the http requests of an `AuthorizedClient`, both to the token endpoint and the other endpoints
+///
+/// `reqwest::Client` is the default transport, replace it using [connect_with_transport](crate::AuthorizedClient::connect_with_transport),
+/// e.g. with a `MockTransport` in unit tests (requires the `mock` feature).
+pub trait HttpTransport: Send + Sync {
+    /// Send `request` and return the response
+    ///
+    /// Return the `reqwest::Error` as is, the [retry_policy](crate::Settings::retry_policy) uses it to decide if the request is retried.
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response>>;
+}
+
+impl HttpTransport for Client {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move { Ok(Client::execute(self, request).await?) })
+    }
+}
@@ -0,0 +1,86 @@
+use crate::authorized_client::Credentials;
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::{HeaderValue, CONNECTION, UPGRADE};
+use reqwest::{Method, Request, Response};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use url::Url;
+
+// Appended to the key by the server to prove it understood the handshake, see: RFC 6455 section 1.3
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How the bearer token is sent when upgrading to a WebSocket, see: [upgrade](crate::AuthorizedClient::upgrade)
+///
+/// Only the opening handshake is implemented: the token is sent and the `Sec-WebSocket-Accept` answer is verified.
+/// The connection is handed over as a raw upgraded stream, framing, pings and closing are up to a WebSocket library.
+#[derive(Clone, Debug, Default)]
+pub enum WebSocketAuth {
+    /// Send the bearer token in the `Authorization` header
+    #[default]
+    Header,
+    /// Send the bearer token in this query parameter, for servers which follow the browser WebSocket API
+    ///
+    /// The url usually ends up in access logs, only use this when the server doesn't accept the header.
+    QueryParameter(String),
+}
+
+/// Build the opening handshake, the returned key is needed to verify the response
+pub(crate) fn handshake_request(
+    url: &Url,
+    credentials: &Credentials,
+    auth: &WebSocketAuth,
+) -> Result<(Request, String)> {
+    // The handshake is a regular http request
+    let mut url = url.clone();
+    let scheme = match url.scheme() {
+        "ws" | "http" => "http",
+        "wss" | "https" => "https",
+        scheme => bail!("Unsupported WebSocket scheme '{}'", scheme),
+    };
+    url.set_scheme(scheme)
+        .ok()
+        .context("Failed to convert the WebSocket url")?;
+
+    if let WebSocketAuth::QueryParameter(name) = auth {
+        url.query_pairs_mut()
+            .append_pair(name, credentials.access_token.expose_secret());
+    }
+
+    let key = STANDARD.encode(rand::random::<[u8; 16]>());
+
+    let mut request = Request::new(Method::GET, url);
+    let headers = request.headers_mut();
+    headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert("Sec-WebSocket-Version", HeaderValue::from_static("13"));
+    headers.insert("Sec-WebSocket-Key", key.parse()?);
+    if let WebSocketAuth::Header = auth {
+        headers.insert(
+            "Authorization",
+            credentials
+                .authorization
+                .clone()
+                .context("The bearer token is not a valid header value")?,
+        );
+    }
+
+    Ok((request, key))
+}
+
+/// Check that the server accepted the upgrade to a WebSocket
+pub(crate) fn verify_handshake(response: &Response, key: &str) -> Result<()> {
+    let expected = STANDARD.encode(digest(
+        &SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key, HANDSHAKE_GUID).as_bytes(),
+    ));
+
+    let accept = response.headers().get("Sec-WebSocket-Accept").context(
+        "The server didn't accept the WebSocket upgrade: Sec-WebSocket-Accept is missing",
+    )?;
+    if accept.as_bytes() != expected.as_bytes() {
+        bail!("The server didn't accept the WebSocket upgrade: Sec-WebSocket-Accept is invalid");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use futures_util::stream;
+use log::debug;
+use oauth2::http;
+use reqwest::header::{
+    HeaderMap, HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use serde::Deserialize;
+use std::mem;
+
+/// Log the requests to the endpoints and their responses at `debug` level, see: [wire_log](crate::Settings::wire_log)
+///
+/// The method, url and headers are logged, the `Authorization` and cookie headers are redacted.
+/// Bodies are only logged when `log_bodies` is enabled, they can contain personal data so think twice before enabling it in production.
+/// While bodies are logged the size of a response body is unknown upfront, `Response::content_length` returns `None`, read the `Content-Length` header instead.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WireLogSettings {
+    pub log_bodies: bool,
+    /// The maximum number of bytes of a body which are logged, the rest is truncated
+    pub max_body_size: usize,
+}
+
+impl Default for WireLogSettings {
+    fn default() -> Self {
+        WireLogSettings {
+            log_bodies: false,
+            max_body_size: 4096,
+        }
+    }
+}
+
+pub(crate) fn log_request(settings: &WireLogSettings, request: &Request) {
+    debug!("--> {} {}", request.method(), request.url());
+    log_headers(request.headers());
+
+    if let (true, Some(body)) = (settings.log_bodies, request.body()) {
+        match body.as_bytes() {
+            Some(bytes) => debug!("--> {}", truncate(bytes, settings.max_body_size)),
+            None => debug!("--> [streamed body]"),
+        }
+    }
+}
+
+/// Log the response, the body is logged while it's being read so streamed responses are not buffered
+pub(crate) fn log_response(settings: &WireLogSettings, mut response: Response) -> Result<Response> {
+    debug!("<-- {} {}", response.status().as_u16(), response.url());
+    log_headers(response.headers());
+
+    if !settings.log_bodies {
+        return Ok(response);
+    }
+
+    // The extensions, e.g. the remote address, are moved to the rebuilt response before the url is added to them
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    if let Some(extensions) = builder.extensions_mut() {
+        *extensions = mem::take(response.extensions_mut());
+    }
+    let builder = builder.url(response.url().clone());
+
+    let body_log = BodyLog {
+        response,
+        captured: Vec::new(),
+        max_body_size: settings.max_body_size,
+        logged: false,
+    };
+    let body = stream::unfold(Some(body_log), |body_log| async move {
+        let mut body_log = body_log?;
+        match body_log.response.chunk().await {
+            Ok(Some(chunk)) => {
+                body_log.capture(&chunk);
+                Some((Ok::<Bytes, reqwest::Error>(chunk), Some(body_log)))
+            }
+            Ok(None) => {
+                body_log.finish();
+                None
+            }
+            Err(error) => {
+                body_log.finish();
+                Some((Err(error), None))
+            }
+        }
+    });
+
+    Ok(builder
+        .body(hyper::Body::wrap_stream(body))
+        .context("Failed to rebuild the logged response")?
+        .into())
+}
+
+fn log_headers(headers: &HeaderMap) {
+    for (name, value) in headers {
+        if is_sensitive_header(name) {
+            debug!("    {}: [redacted]", name);
+        } else {
+            debug!(
+                "    {}: {}",
+                name,
+                String::from_utf8_lossy(value.as_bytes())
+            );
+        }
+    }
+}
+
+/// The headers which contain credentials or session cookies
+pub(crate) fn is_sensitive_header(name: &HeaderName) -> bool {
+    name == AUTHORIZATION
+        || name == PROXY_AUTHORIZATION
+        || name == COOKIE
+        || name == SET_COOKIE
+        || name == "dpop"
+}
+
+fn truncate(body: &[u8], max_body_size: usize) -> String {
+    if body.len() > max_body_size {
+        format!(
+            "{}... ({} bytes truncated)",
+            String::from_utf8_lossy(&body[..max_body_size]),
+            body.len() - max_body_size
+        )
+    } else {
+        String::from_utf8_lossy(body).into_owned()
+    }
+}
+
+// Captures the start of a response body while it's being read
+struct BodyLog {
+    response: Response,
+    captured: Vec<u8>,
+    max_body_size: usize,
+    logged: bool,
+}
+
+impl BodyLog {
+    fn capture(&mut self, chunk: &[u8]) {
+        if self.logged {
+            return;
+        }
+
+        let remaining = self.max_body_size - self.captured.len();
+        self.captured
+            .extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+
+        // Log as soon as the limit is reached, the rest of the body might never be read
+        if chunk.len() > remaining {
+            debug!(
+                "<-- {}... (truncated)",
+                String::from_utf8_lossy(&self.captured)
+            );
+            self.logged = true;
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.logged {
+            debug!("<-- {}", String::from_utf8_lossy(&self.captured));
+            self.logged = true;
+        }
+    }
+}
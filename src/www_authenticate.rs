@@ -0,0 +1,213 @@
+use reqwest::header::{HeaderMap, WWW_AUTHENTICATE};
+use std::fmt::{self, Display, Formatter};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The `Bearer` (or `DPoP`) challenge of a `WWW-Authenticate` header ([RFC 6750](https://tools.ietf.org/html/rfc6750#section-3))
+///
+/// The server uses it to explain why the bearer token was rejected, see: [UnauthorizedError](crate::UnauthorizedError)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BearerChallenge {
+    pub realm: Option<String>,
+    /// E.g. `invalid_token` or `insufficient_scope`
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+    /// The scopes which are required to access the resource, separated by spaces
+    pub scope: Option<String>,
+}
+
+impl BearerChallenge {
+    /// Find the bearer challenge in the `WWW-Authenticate` headers of a response
+    ///
+    /// ```
+    /// use authorized_client::BearerChallenge;
+    /// use reqwest::header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert(
+    ///     WWW_AUTHENTICATE,
+    ///     HeaderValue::from_static(r#"Bearer realm="api", error="invalid_token", error_description="The token expired""#),
+    /// );
+    /// let challenge = BearerChallenge::from_headers(&headers).unwrap();
+    /// assert_eq!(challenge.error.as_deref(), Some("invalid_token"));
+    /// assert_eq!(challenge.error_description.as_deref(), Some("The token expired"));
+    /// ```
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_challenges)
+            .find(|(scheme, _)| {
+                scheme.eq_ignore_ascii_case("Bearer") || scheme.eq_ignore_ascii_case("DPoP")
+            })
+            .map(|(_, params)| {
+                let param = |name: &str| {
+                    params
+                        .iter()
+                        .find(|(key, _)| key == name)
+                        .map(|(_, value)| value.clone())
+                };
+                BearerChallenge {
+                    realm: param("realm"),
+                    error: param("error"),
+                    error_description: param("error_description"),
+                    scope: param("scope"),
+                }
+            })
+    }
+
+    /// The token doesn't have the scopes the resource requires, a new token won't help unless it's requested with more scopes
+    pub fn is_insufficient_scope(&self) -> bool {
+        self.error.as_deref() == Some("insufficient_scope")
+    }
+}
+
+impl Display for BearerChallenge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error.as_deref().unwrap_or("no error"))?;
+        if let Some(error_description) = &self.error_description {
+            write!(f, " ({})", error_description)?;
+        }
+        if let Some(realm) = &self.realm {
+            write!(f, ", realm = {}", realm)?;
+        }
+        Ok(())
+    }
+}
+
+type Challenge = (String, Vec<(String, String)>);
+
+// A header can contain multiple challenges: `Basic realm="api", Bearer error="invalid_token"`
+// The parameters of a challenge follow its scheme, the parameter names are lowercased
+fn parse_challenges(header: &str) -> Vec<Challenge> {
+    let mut challenges: Vec<Challenge> = Vec::new();
+    let mut chars = header.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace() || *c == ',') {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let token = read_token(&mut chars);
+        if token.is_empty() {
+            // A stray `=`, e.g. the padding of a token68
+            chars.next();
+            continue;
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            let value = if chars.peek() == Some(&'"') {
+                read_quoted(&mut chars)
+            } else {
+                read_token(&mut chars)
+            };
+            if let Some((_, params)) = challenges.last_mut() {
+                params.push((token.to_ascii_lowercase(), value));
+            }
+        } else {
+            challenges.push((token, Vec::new()));
+        }
+    }
+
+    challenges
+}
+
+fn read_token(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut token = String::new();
+    while let Some(c) = chars.peek() {
+        if c.is_whitespace() || *c == ',' || *c == '=' {
+            break;
+        }
+        token.push(*c);
+        chars.next();
+    }
+    token
+}
+
+fn read_quoted(chars: &mut Peekable<Chars<'_>>) -> String {
+    // Skip the opening quote
+    chars.next();
+
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => value.extend(chars.next()),
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn challenge(header: &'static str) -> Option<BearerChallenge> {
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static(header));
+        BearerChallenge::from_headers(&headers)
+    }
+
+    #[test]
+    fn quoted_values_can_contain_commas_and_escapes() {
+        let challenge = challenge(
+            r#"Bearer error="invalid_token", error_description="Expired, \"renew\" it \\ now""#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            challenge.error_description.as_deref(),
+            Some(r#"Expired, "renew" it \ now"#)
+        );
+    }
+
+    #[test]
+    fn values_can_be_tokens_and_names_are_case_insensitive() {
+        let challenge =
+            challenge("bearer ERROR = insufficient_scope, Scope=\"read write\"").unwrap();
+
+        assert!(challenge.is_insufficient_scope());
+        assert_eq!(challenge.scope.as_deref(), Some("read write"));
+    }
+
+    #[test]
+    fn finds_the_bearer_challenge_among_other_schemes() {
+        let challenge =
+            challenge(r#"Basic realm="basic", Negotiate YWJjZA==, Bearer realm="api""#).unwrap();
+
+        assert_eq!(challenge.realm.as_deref(), Some("api"));
+        assert_eq!(challenge.error, None);
+    }
+
+    #[test]
+    fn accepts_dpop_challenges() {
+        let challenge = challenge(r#"DPoP algs="ES256", error="invalid_token""#).unwrap();
+
+        assert_eq!(challenge.error.as_deref(), Some("invalid_token"));
+    }
+
+    #[test]
+    fn an_unterminated_quoted_string_ends_at_the_end_of_the_header() {
+        let challenge = challenge(r#"Bearer error="invalid_token"#).unwrap();
+
+        assert_eq!(challenge.error.as_deref(), Some("invalid_token"));
+    }
+
+    #[test]
+    fn no_challenge_without_a_bearer_scheme() {
+        assert_eq!(challenge(r#"Basic realm="api""#), None);
+    }
+}
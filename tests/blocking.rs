@@ -0,0 +1,85 @@
+#![cfg(feature = "blocking")]
+
+use authorized_client::blocking::AuthorizedClient;
+use authorized_client::{ResponseTooLargeError, Settings};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+const TOKEN: &str = r#"{"access_token":"token","token_type":"bearer","expires_in":3600}"#;
+
+// Serve the token endpoint and answer every other request with a chunked body without a Content-Length
+fn start_server(body: &'static str) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            std::io::Read::read_exact(&mut reader, &mut vec![0; content_length]).unwrap();
+
+            let body = if request_line.starts_with("POST /token") {
+                TOKEN
+            } else {
+                body
+            };
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+    address
+}
+
+fn client(address: SocketAddr, max_response_size: usize) -> AuthorizedClient {
+    let settings = Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(format!("http://{}/token", address))
+        .max_response_size(max_response_size)
+        .build()
+        .unwrap();
+    AuthorizedClient::connect(settings).unwrap()
+}
+
+#[test]
+fn a_chunked_body_within_max_response_size_is_returned() {
+    let address = start_server(r#"{"name":"small"}"#);
+    let client = client(address, 64);
+
+    let body: serde_json::Value = client
+        .get(format!("http://{}/info", address).parse().unwrap())
+        .unwrap();
+
+    assert_eq!(body, serde_json::json!({ "name": "small" }));
+}
+
+#[test]
+fn a_chunked_body_over_max_response_size_fails() {
+    let address = start_server(r#"{"name":"a name which is too long"}"#);
+    let client = client(address, 16);
+
+    let error = client
+        .get::<serde_json::Value>(format!("http://{}/info", address).parse().unwrap())
+        .unwrap_err();
+
+    let too_large = error.downcast_ref::<ResponseTooLargeError>().unwrap();
+    assert_eq!(too_large.limit, 16);
+}
@@ -0,0 +1,173 @@
+use authorized_client::{AuthorizedClient, Backoff, MockResponse, MockTransport, Settings};
+use reqwest::header::{HeaderValue, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/report.csv";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// A path in a directory of its own, so the tests don't share files
+fn download_path(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "authorized_client-download-{}-{}",
+        std::process::id(),
+        test
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("report.csv")
+}
+
+async fn client(transport: &MockTransport) -> AuthorizedClient {
+    let settings = Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(TOKEN_URL)
+        .download_resume_backoff(Backoff::Fixed {
+            delay: Duration::ZERO,
+        })
+        .build()
+        .unwrap();
+    transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+    AuthorizedClient::connect_with_transport(settings, transport.clone())
+        .await
+        .unwrap()
+}
+
+// The connection drops after `body`, the server announced 10 bytes
+fn interrupted(body: &'static str, etag: Option<&'static str>) -> MockResponse {
+    let response = MockResponse::new(StatusCode::OK)
+        .header(CONTENT_LENGTH, HeaderValue::from_static("10"))
+        .body(body);
+    match etag {
+        Some(etag) => response.header(ETAG, HeaderValue::from_static(etag)),
+        None => response,
+    }
+}
+
+fn download_requests(transport: &MockTransport) -> Vec<reqwest::header::HeaderMap> {
+    transport
+        .requests()
+        .into_iter()
+        .filter(|request| request.url.as_str() == URL)
+        .map(|request| request.headers)
+        .collect()
+}
+
+#[test]
+fn an_existing_file_is_replaced_instead_of_appended_to() {
+    block_on(async {
+        let path = download_path("existing");
+        std::fs::write(&path, "stale").unwrap();
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        transport.push_response(URL, MockResponse::new(StatusCode::OK).body("helloworld"));
+
+        let size = client
+            .download(Url::parse(URL).unwrap(), &path)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 10);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "helloworld");
+        assert!(download_requests(&transport)[0].get(RANGE).is_none());
+    });
+}
+
+#[test]
+fn an_interrupted_download_is_resumed_when_the_file_is_unchanged() {
+    block_on(async {
+        let path = download_path("resumed");
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        transport.push_response(URL, interrupted("hello", Some("\"v1\"")));
+        transport.push_response(
+            URL,
+            MockResponse::new(StatusCode::PARTIAL_CONTENT)
+                .header(CONTENT_RANGE, HeaderValue::from_static("bytes 5-9/10"))
+                .body("world"),
+        );
+
+        client
+            .download(Url::parse(URL).unwrap(), &path)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "helloworld");
+        let requests = download_requests(&transport);
+        assert_eq!(requests[1][RANGE], "bytes=5-");
+        assert_eq!(requests[1][IF_RANGE], "\"v1\"");
+    });
+}
+
+#[test]
+fn a_full_response_to_a_resumed_download_replaces_the_partial_file() {
+    block_on(async {
+        let path = download_path("changed");
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        transport.push_response(URL, interrupted("hello", Some("\"v1\"")));
+        // The file changed, so the server ignores the range
+        transport.push_response(
+            URL,
+            MockResponse::new(StatusCode::OK)
+                .header(ETAG, HeaderValue::from_static("\"v2\""))
+                .body("HELLOWORLD"),
+        );
+
+        client
+            .download(Url::parse(URL).unwrap(), &path)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "HELLOWORLD");
+    });
+}
+
+#[test]
+fn a_download_without_a_validator_starts_over() {
+    block_on(async {
+        let path = download_path("no-validator");
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        transport.push_response(URL, interrupted("hello", None));
+        transport.push_response(URL, MockResponse::new(StatusCode::OK).body("helloworld"));
+
+        client
+            .download(Url::parse(URL).unwrap(), &path)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "helloworld");
+        assert!(download_requests(&transport)[1].get(RANGE).is_none());
+    });
+}
+
+#[test]
+fn a_failed_download_leaves_the_existing_file_alone() {
+    block_on(async {
+        let path = download_path("failed");
+        std::fs::write(&path, "previous").unwrap();
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        transport.push_response(URL, MockResponse::new(StatusCode::NOT_FOUND));
+
+        assert!(client
+            .download(Url::parse(URL).unwrap(), &path)
+            .await
+            .is_err());
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous");
+        assert!(!path.with_file_name("report.csv.part").exists());
+    });
+}
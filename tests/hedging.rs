@@ -0,0 +1,97 @@
+use authorized_client::{
+    AuthorizedClient, BoxFuture, HttpTransport, Metrics, RateLimit, RequestMetrics, Settings,
+    SettingsBuilder,
+};
+use reqwest::{Request, Response};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/info";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// Issues tokens and answers the first api request after 300ms, the others immediately
+#[derive(Clone, Default)]
+struct SlowTransport {
+    api_requests: Arc<AtomicUsize>,
+}
+
+impl HttpTransport for SlowTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, anyhow::Result<Response>> {
+        Box::pin(async move {
+            let body = if request.url().as_str() == TOKEN_URL {
+                r#"{"access_token":"token","token_type":"bearer","expires_in":3600}"#
+            } else {
+                if self.api_requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                }
+                "{}"
+            };
+            let response = hyper::Response::builder()
+                .header("Content-Type", "application/json")
+                .body(body)?;
+            Ok(Response::from(response))
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+struct Hedged(Arc<AtomicBool>);
+
+impl Metrics for Hedged {
+    fn on_request(&self, request: &RequestMetrics) {
+        self.0.store(request.hedged, Ordering::SeqCst);
+    }
+}
+
+async fn get(configure: impl FnOnce(SettingsBuilder) -> SettingsBuilder) -> (usize, bool) {
+    let transport = SlowTransport::default();
+    let hedged = Hedged::default();
+    let settings = Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(TOKEN_URL)
+        .hedge_after(Duration::from_millis(50));
+    let client = AuthorizedClient::connect_with_transport(
+        configure(settings).build().unwrap(),
+        transport.clone(),
+    )
+    .await
+    .unwrap()
+    .with_metrics(hedged.clone());
+
+    let _: serde_json::Value = client.get(Url::parse(URL).unwrap()).await.unwrap();
+    (
+        transport.api_requests.load(Ordering::SeqCst),
+        hedged.0.load(Ordering::SeqCst),
+    )
+}
+
+#[test]
+fn sends_a_hedged_request_when_the_response_is_slow() {
+    block_on(async {
+        assert_eq!(get(|settings| settings).await, (2, true));
+    });
+}
+
+#[test]
+fn the_hedged_request_waits_for_the_rate_limiter() {
+    block_on(async {
+        // The hedged request would have to wait 2 seconds, the first response arrives before that
+        let rate_limit = RateLimit::new(0.5, 1);
+        assert_eq!(
+            get(|settings| settings.rate_limit(rate_limit)).await,
+            (1, false)
+        );
+    });
+}
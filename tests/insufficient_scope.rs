@@ -0,0 +1,101 @@
+use authorized_client::{AuthorizedClient, MockResponse, MockTransport, Settings, StatusError};
+use reqwest::header::{HeaderValue, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/info";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn insufficient_scope(scope: &'static str) -> MockResponse {
+    MockResponse::new(StatusCode::FORBIDDEN).header(
+        WWW_AUTHENTICATE,
+        HeaderValue::from_str(&format!(
+            r#"Bearer error="insufficient_scope", scope="{}""#,
+            scope
+        ))
+        .unwrap(),
+    )
+}
+
+async fn client(transport: &MockTransport) -> AuthorizedClient {
+    let settings = Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(TOKEN_URL)
+        .scopes(vec!["a"])
+        .retry_insufficient_scope(true)
+        .build()
+        .unwrap();
+    transport.push_token(TOKEN_URL, "token-a", Duration::from_secs(3600));
+    AuthorizedClient::connect_with_transport(settings, transport.clone())
+        .await
+        .unwrap()
+}
+
+#[test]
+fn retries_once_with_the_scope_the_server_asks_for() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        transport.push_response(URL, insufficient_scope("b"));
+        transport.push_token(TOKEN_URL, "token-b", Duration::from_secs(3600));
+        transport.push_response(URL, MockResponse::new(StatusCode::OK).body("{}"));
+
+        let _: serde_json::Value = client.get(Url::parse(URL).unwrap()).await.unwrap();
+
+        let authorizations: Vec<_> = transport
+            .requests()
+            .into_iter()
+            .filter(|request| request.url.as_str() == URL)
+            .map(|request| request.headers["authorization"].clone())
+            .collect();
+        assert_eq!(authorizations, ["Bearer token-a", "Bearer token-b"]);
+    });
+}
+
+#[test]
+fn does_not_follow_a_server_which_keeps_asking_for_other_scopes() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(&transport).await;
+        // The server asks for scope b with a token for a, and for scope a with a token for b
+        for _ in 0..3 {
+            transport.push_response(URL, insufficient_scope("b"));
+            transport.push_response(URL, insufficient_scope("a"));
+            transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+        }
+
+        let error = client
+            .get::<serde_json::Value>(Url::parse(URL).unwrap())
+            .await
+            .unwrap_err();
+
+        let error = error.downcast_ref::<StatusError>().unwrap();
+        assert_eq!(error.status, StatusCode::FORBIDDEN);
+        let requests = transport.requests();
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.url.as_str() == URL)
+                .count(),
+            2
+        );
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.url.as_str() == TOKEN_URL)
+                .count(),
+            2
+        );
+    });
+}
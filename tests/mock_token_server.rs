@@ -0,0 +1,138 @@
+use authorized_client::testing::MockTokenServer;
+use authorized_client::{
+    AuthorizedClient, Backoff, ManualClock, Settings, SettingsBuilder, UnauthorizedError,
+};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn settings(server: &MockTokenServer) -> SettingsBuilder {
+    Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(server.token_url())
+        .refresh_cooldown(Duration::ZERO)
+        .auth_retry_backoff(Backoff::Fixed {
+            delay: Duration::ZERO,
+        })
+}
+
+async fn get(client: &AuthorizedClient, server: &MockTokenServer) -> anyhow::Result<()> {
+    client
+        .get::<serde_json::Value>(server.url("/info")?)
+        .await
+        .map(drop)
+}
+
+#[test]
+fn refreshes_the_token_when_it_expires() {
+    block_on(async {
+        let server = MockTokenServer::start().await.unwrap();
+        let clock = ManualClock::new();
+        let client = AuthorizedClient::connect(settings(&server).build().unwrap())
+            .await
+            .unwrap()
+            .with_clock(clock.clone());
+
+        get(&client, &server).await.unwrap();
+        assert_eq!(server.token_requests(), 1);
+
+        // The token is valid for an hour, the refresh leeway is 30 seconds
+        clock.advance(Duration::from_secs(3600 - 60));
+        get(&client, &server).await.unwrap();
+        assert_eq!(server.token_requests(), 1);
+
+        clock.advance(Duration::from_secs(40));
+        get(&client, &server).await.unwrap();
+        assert_eq!(server.token_requests(), 2);
+    });
+}
+
+#[test]
+fn retries_a_rejected_token_up_to_max_auth_retries() {
+    block_on(async {
+        let server = MockTokenServer::start().await.unwrap();
+        let client =
+            AuthorizedClient::connect(settings(&server).max_auth_retries(2).build().unwrap())
+                .await
+                .unwrap();
+
+        // Revoked tokens are replaced by a new one
+        server.revoke_tokens();
+        get(&client, &server).await.unwrap();
+        assert_eq!(server.token_requests(), 2);
+
+        // When every token is rejected the request fails after refreshing max_auth_retries times
+        server.set_reject_tokens(true);
+        let error = get(&client, &server).await.unwrap_err();
+        let error = error.downcast_ref::<UnauthorizedError>().unwrap();
+        assert_eq!(error.retries, 2);
+        assert_eq!(
+            error.challenge.as_ref().unwrap().error.as_deref(),
+            Some("invalid_token")
+        );
+        assert_eq!(server.token_requests(), 4);
+    });
+}
+
+#[test]
+fn reports_failed_token_requests_and_recovers() {
+    block_on(async {
+        let server = MockTokenServer::start().await.unwrap();
+
+        server.fail_next_token_requests(1, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(
+            AuthorizedClient::connect(settings(&server).build().unwrap())
+                .await
+                .is_err()
+        );
+
+        let clock = ManualClock::new();
+        let client = AuthorizedClient::connect(settings(&server).build().unwrap())
+            .await
+            .unwrap()
+            .with_clock(clock.clone());
+
+        // A failed refresh fails the request, the next request tries again
+        server.fail_next_token_requests(1, StatusCode::SERVICE_UNAVAILABLE);
+        clock.advance(Duration::from_secs(3700));
+        assert!(get(&client, &server).await.is_err());
+        get(&client, &server).await.unwrap();
+        assert_eq!(server.token_requests(), 4);
+    });
+}
+
+#[test]
+fn fails_when_the_auth_server_rejects_the_scope() {
+    block_on(async {
+        let server = MockTokenServer::start().await.unwrap();
+        server.set_allowed_scopes(vec!["read"]);
+
+        // The auth server doesn't issue a token for a scope which isn't allowed
+        assert!(
+            AuthorizedClient::connect(settings(&server).scope("write").build().unwrap())
+                .await
+                .is_err()
+        );
+        assert_eq!(server.token_requests(), 1);
+        assert!(server.issued_tokens().is_empty());
+
+        let client = AuthorizedClient::connect(settings(&server).scope("read").build().unwrap())
+            .await
+            .unwrap();
+        get(&client, &server).await.unwrap();
+        assert!(get(&client.with_scopes(vec!["admin"]), &server)
+            .await
+            .is_err());
+        assert_eq!(server.token_requests(), 3);
+        assert_eq!(server.issued_tokens().len(), 1);
+    });
+}
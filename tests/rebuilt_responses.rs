@@ -0,0 +1,104 @@
+use authorized_client::{
+    AuthorizedClient, BoxFuture, HttpTransport, Settings, SettingsBuilder, WireLogSettings,
+};
+use reqwest::{Method, Request, Response};
+use std::future::Future;
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/info";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// An extension the transport adds to every response, like the remote address added by reqwest
+#[derive(Clone, Debug, PartialEq)]
+struct Marker;
+
+struct MarkingTransport;
+
+impl HttpTransport for MarkingTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, anyhow::Result<Response>> {
+        Box::pin(async move {
+            let body = if request.url().as_str() == TOKEN_URL {
+                r#"{"access_token":"token","token_type":"bearer","expires_in":3600}"#
+            } else {
+                "hello"
+            };
+            let response = hyper::Response::builder()
+                .header("Content-Type", "application/json")
+                .extension(Marker)
+                .body(body)?;
+            Ok(Response::from(response))
+        })
+    }
+}
+
+async fn get(
+    configure: impl FnOnce(SettingsBuilder) -> SettingsBuilder,
+) -> (bool, Option<u64>, String) {
+    let settings = Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(TOKEN_URL);
+    let client = AuthorizedClient::connect_with_transport(
+        configure(settings).build().unwrap(),
+        MarkingTransport,
+    )
+    .await
+    .unwrap();
+
+    client
+        .request(
+            || Ok(Request::new(Method::GET, Url::parse(URL)?)),
+            |response| async move {
+                let marked = response.extensions().get::<Marker>().is_some();
+                let content_length = response.content_length();
+                Ok::<_, anyhow::Error>((marked, content_length, response.text().await?))
+            },
+        )
+        .await
+        .unwrap()
+}
+
+#[test]
+fn logging_the_body_keeps_the_response_extensions() {
+    block_on(async {
+        let wire_log = WireLogSettings {
+            log_bodies: true,
+            ..WireLogSettings::default()
+        };
+        let (marked, _, body) = get(|settings| settings.wire_log(wire_log)).await;
+        assert!(marked);
+        assert_eq!(body, "hello");
+    });
+}
+
+#[test]
+fn limiting_the_size_keeps_the_response_extensions_and_content_length() {
+    block_on(async {
+        let (marked, content_length, body) = get(|settings| settings.max_response_size(1024)).await;
+        assert!(marked);
+        assert_eq!(content_length, Some(5));
+        assert_eq!(body, "hello");
+    });
+}
+
+#[test]
+fn limiting_the_size_of_a_logged_body_keeps_the_response_extensions() {
+    block_on(async {
+        let wire_log = WireLogSettings {
+            log_bodies: true,
+            ..WireLogSettings::default()
+        };
+        let (marked, _, body) =
+            get(|settings| settings.wire_log(wire_log).max_response_size(1024)).await;
+        assert!(marked);
+        assert_eq!(body, "hello");
+    });
+}
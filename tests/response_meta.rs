@@ -0,0 +1,115 @@
+use authorized_client::{
+    AuthorizedClient, DeserializeError, MockResponse, MockTransport, ResponseMeta, Settings,
+    UnexpectedContentTypeError,
+};
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/info";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+async fn client(settings: Settings, transport: &MockTransport) -> AuthorizedClient {
+    transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+    AuthorizedClient::connect_with_transport(settings, transport.clone())
+        .await
+        .unwrap()
+}
+
+fn settings() -> authorized_client::SettingsBuilder {
+    Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(TOKEN_URL)
+}
+
+#[test]
+fn get_with_meta_checks_the_content_type() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(settings().build().unwrap(), &transport).await;
+        transport.push_response(
+            URL,
+            MockResponse::new(StatusCode::OK)
+                .header(CONTENT_TYPE, HeaderValue::from_static("text/html"))
+                .body("<html>Maintenance</html>"),
+        );
+
+        let error = client
+            .get_with_meta::<serde_json::Value>(Url::parse(URL).unwrap())
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<UnexpectedContentTypeError>().unwrap();
+        assert_eq!(error.actual, "text/html");
+        assert_eq!(error.body_snippet, "<html>Maintenance</html>");
+    });
+}
+
+#[test]
+fn get_with_meta_accepts_any_content_type_when_enabled() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(
+            settings().accept_any_content_type(true).build().unwrap(),
+            &transport,
+        )
+        .await;
+        transport.push_response(
+            URL,
+            MockResponse::new(StatusCode::OK)
+                .header(CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+                .body(r#"{"id":1}"#),
+        );
+
+        let response: ResponseMeta<serde_json::Value> = client
+            .get_with_meta(Url::parse(URL).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body["id"], 1);
+    });
+}
+
+#[test]
+fn get_with_meta_reports_where_the_body_does_not_match() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(settings().build().unwrap(), &transport).await;
+        transport.push_response(
+            URL,
+            MockResponse::new(StatusCode::OK)
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .body(r#"{"items":[{"id":"one"}]}"#),
+        );
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Item {
+            #[allow(dead_code)]
+            id: u64,
+        }
+        #[derive(Debug, serde::Deserialize)]
+        struct Items {
+            #[allow(dead_code)]
+            items: Vec<Item>,
+        }
+
+        let error = client
+            .get_with_meta::<Items>(Url::parse(URL).unwrap())
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<DeserializeError>().unwrap();
+        assert_eq!(error.status, StatusCode::OK);
+        assert_eq!(error.path, "items[0].id");
+        assert_eq!(error.body_snippet, r#"{"items":[{"id":"one"}]}"#);
+    });
+}
@@ -0,0 +1,93 @@
+use authorized_client::{
+    AuthorizedClient, ManualClock, MockResponse, MockTransport, RetryPolicy, Settings, StatusError,
+};
+use reqwest::header::{HeaderValue, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/info";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn throttled() -> MockResponse {
+    MockResponse::new(StatusCode::TOO_MANY_REQUESTS)
+        .header(RETRY_AFTER, HeaderValue::from_static("0"))
+}
+
+#[test]
+fn retry_after_retries_are_limited_by_the_retry_policy() {
+    block_on(async {
+        let transport = MockTransport::new();
+        transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+        for _ in 0..3 {
+            transport.push_response(URL, throttled());
+        }
+
+        let settings = Settings::builder()
+            .client_id("client")
+            .client_secret("secret")
+            .token_url(TOKEN_URL)
+            .retry_policy(RetryPolicy {
+                max_retry_after_retries: 1,
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap();
+        let client = AuthorizedClient::connect_with_transport(settings, transport.clone())
+            .await
+            .unwrap();
+
+        let error = client
+            .get::<serde_json::Value>(Url::parse(URL).unwrap())
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<StatusError>().unwrap();
+        assert_eq!(error.status, StatusCode::TOO_MANY_REQUESTS);
+
+        let requests = transport.requests();
+        let api_requests = requests
+            .iter()
+            .filter(|request| request.url.as_str() == URL);
+        assert_eq!(api_requests.count(), 2);
+    });
+}
+
+#[test]
+fn retry_after_dates_are_compared_with_the_clock_of_the_client() {
+    block_on(async {
+        let transport = MockTransport::new();
+        transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+        let settings = Settings::builder()
+            .client_id("client")
+            .client_secret("secret")
+            .token_url(TOKEN_URL)
+            .build()
+            .unwrap();
+        let clock = ManualClock::new();
+        let client = AuthorizedClient::connect_with_transport(settings, transport.clone())
+            .await
+            .unwrap()
+            .with_clock(clock.clone());
+
+        // The date is 10 minutes away, far beyond the budget, but it already passed on the clock of the client
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(600));
+        transport.push_response(
+            URL,
+            MockResponse::new(StatusCode::SERVICE_UNAVAILABLE)
+                .header(RETRY_AFTER, HeaderValue::from_str(&date).unwrap()),
+        );
+        transport.push_response(URL, MockResponse::new(StatusCode::OK).body("{}"));
+        clock.advance(Duration::from_secs(700));
+
+        let _: serde_json::Value = client.get(Url::parse(URL).unwrap()).await.unwrap();
+    });
+}
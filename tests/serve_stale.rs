@@ -0,0 +1,46 @@
+use authorized_client::testing::MockTokenServer;
+use authorized_client::{AuthorizedClient, Settings};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[test]
+fn lazy_client_reports_the_token_endpoint_error_instead_of_serving_stale() {
+    block_on(async {
+        let server = MockTokenServer::start().await.unwrap();
+        server.fail_next_token_requests(1, StatusCode::BAD_REQUEST);
+
+        let settings = Settings::builder()
+            .client_id("client")
+            .client_secret("secret")
+            .token_url(server.token_url())
+            .serve_stale(Duration::from_secs(3600))
+            .refresh_cooldown(Duration::ZERO)
+            .build()
+            .unwrap();
+        let client = AuthorizedClient::connect_lazy(settings).unwrap();
+
+        let error = client
+            .get::<serde_json::Value>(server.url("/info").unwrap())
+            .await
+            .unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(
+            !message.contains("not a valid header value"),
+            "the placeholder token was served: {}",
+            message
+        );
+        assert_eq!(server.token_requests(), 1);
+
+        // The next request gets a token
+        let _: serde_json::Value = client.get(server.url("/info").unwrap()).await.unwrap();
+    });
+}
@@ -0,0 +1,75 @@
+use authorized_client::{Backoff, Settings};
+use std::time::Duration;
+
+fn settings(extra: &str) -> anyhow::Result<Settings> {
+    let json = format!(
+        r#"{{
+            "client_id": "client",
+            "client_secret": "secret",
+            "token_url": "https://auth.example.com/token",
+            "scopes": []{}
+        }}"#,
+        extra
+    );
+    Settings::from_deserializer(&mut serde_json::Deserializer::from_str(&json))
+}
+
+#[test]
+fn durations_are_strings_with_a_unit() {
+    let settings = settings(
+        r#",
+        "request_timeout": "1m30s",
+        "refresh_leeway": "500ms",
+        "serve_stale": "2h",
+        "connection_pool": { "idle_timeout": "1.5s" },
+        "retry_policy": { "backoff": { "type": "fixed", "delay": "250ms" } }"#,
+    )
+    .unwrap();
+
+    assert_eq!(settings.request_timeout, Some(Duration::from_secs(90)));
+    assert_eq!(settings.refresh_leeway, Duration::from_millis(500));
+    assert_eq!(settings.serve_stale, Duration::from_secs(7200));
+    assert_eq!(
+        settings.connection_pool.idle_timeout,
+        Some(Duration::from_millis(1500))
+    );
+    assert!(matches!(
+        settings.retry_policy.backoff,
+        Backoff::Fixed { delay } if delay == Duration::from_millis(250)
+    ));
+}
+
+#[test]
+fn durations_are_numbers_of_seconds() {
+    let settings = settings(
+        r#",
+        "request_timeout": 30,
+        "refresh_cooldown": 0.25,
+        "hedge_after": null"#,
+    )
+    .unwrap();
+
+    assert_eq!(settings.request_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(settings.refresh_cooldown, Duration::from_millis(250));
+    assert_eq!(settings.hedge_after, None);
+}
+
+#[test]
+fn durations_are_objects_with_secs_and_nanos() {
+    let settings = settings(r#", "serve_stale": { "secs": 5, "nanos": 500000000 }"#).unwrap();
+
+    assert_eq!(settings.serve_stale, Duration::from_millis(5500));
+}
+
+#[test]
+fn an_invalid_duration_names_the_field() {
+    for duration in [r#""30 seconds""#, r#""""#, "-1"] {
+        let error = settings(&format!(r#", "request_timeout": {}"#, duration)).unwrap_err();
+
+        assert!(
+            format!("{:#}", error).contains("request_timeout"),
+            "{:#}",
+            error
+        );
+    }
+}
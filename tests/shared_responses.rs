@@ -0,0 +1,86 @@
+use authorized_client::{
+    AuthorizedClient, MockResponse, MockTransport, ResponseCacheSettings, Settings,
+    UnexpectedContentTypeError,
+};
+use reqwest::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
+use url::Url;
+
+const TOKEN_URL: &str = "https://auth.example.com/token";
+const URL: &str = "https://api.example.com/info";
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn html_page() -> MockResponse {
+    MockResponse::new(StatusCode::OK)
+        .header(CONTENT_TYPE, HeaderValue::from_static("text/html"))
+        .header(CACHE_CONTROL, HeaderValue::from_static("max-age=60"))
+        .body("<html>Maintenance</html>")
+}
+
+async fn client(settings: Settings, transport: &MockTransport) -> AuthorizedClient {
+    transport.push_token(TOKEN_URL, "token", Duration::from_secs(3600));
+    AuthorizedClient::connect_with_transport(settings, transport.clone())
+        .await
+        .unwrap()
+}
+
+fn settings() -> authorized_client::SettingsBuilder {
+    Settings::builder()
+        .client_id("client")
+        .client_secret("secret")
+        .token_url(TOKEN_URL)
+}
+
+#[test]
+fn deduplicated_get_checks_the_content_type() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(
+            settings().deduplicate_gets(true).build().unwrap(),
+            &transport,
+        )
+        .await;
+        transport.push_response(URL, html_page());
+
+        let error = client
+            .get::<serde_json::Value>(Url::parse(URL).unwrap())
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<UnexpectedContentTypeError>().unwrap();
+        assert_eq!(error.actual, "text/html");
+    });
+}
+
+#[test]
+fn cached_get_checks_the_content_type() {
+    block_on(async {
+        let transport = MockTransport::new();
+        let client = client(
+            settings()
+                .response_cache(ResponseCacheSettings::default())
+                .build()
+                .unwrap(),
+            &transport,
+        )
+        .await;
+        transport.push_response(URL, html_page());
+
+        // The second request is served from the cache
+        for _ in 0..2 {
+            let error = client
+                .get::<serde_json::Value>(Url::parse(URL).unwrap())
+                .await
+                .unwrap_err();
+            assert!(error.downcast_ref::<UnexpectedContentTypeError>().is_some());
+        }
+    });
+}
@@ -0,0 +1,32 @@
+use authorized_client::StatusError;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+fn status_error(body: String) -> StatusError {
+    StatusError {
+        status: StatusCode::BAD_GATEWAY,
+        headers: HeaderMap::new(),
+        body,
+    }
+}
+
+#[test]
+fn the_message_contains_a_short_body() {
+    let error = status_error("upstream unavailable".to_string());
+
+    assert_eq!(
+        error.to_string(),
+        "Unsupported status code (CODE=502): upstream unavailable"
+    );
+}
+
+#[test]
+fn the_message_truncates_a_large_body() {
+    let body = "x".repeat(1024 * 1024);
+    let error = status_error(body.clone());
+
+    let message = error.to_string();
+    assert!(message.len() < 600, "{}", message);
+    assert!(message.ends_with("... (1048576 bytes)"), "{}", message);
+    assert_eq!(error.body, body);
+}